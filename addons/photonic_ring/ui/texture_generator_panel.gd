@@ -8,6 +8,14 @@ extends PanelContainer
 @onready var output_path_edit: LineEdit = %OutputPathEdit
 @onready var browse_albedo_button: Button = %BrowseAlbedoButton
 @onready var browse_output_button: Button = %BrowseOutputButton
+@onready var naming_template_edit: LineEdit = %NamingTemplateEdit
+@onready var map_format_selector: OptionButton = %MapFormatSelector
+@onready var existing_file_selector: OptionButton = %ExistingFileSelector
+@onready var legacy_color_checkbox: CheckButton = %LegacyColorCheckbox
+@onready var delight_checkbox: CheckButton = %DelightCheckbox
+@onready var height_mode_selector: OptionButton = %HeightModeSelector
+@onready var normal_detail_selector: OptionButton = %NormalDetailSelector
+@onready var roughness_mode_selector: OptionButton = %RoughnessModeSelector
 
 # === Packer Mode References ===
 @onready var packer_mode_container: VBoxContainer = %PackerModeContainer
@@ -23,6 +31,21 @@ extends PanelContainer
 @onready var pack2_roughness_browse: Button = %Pack2RoughnessBrowse
 @onready var packer_output_edit: LineEdit = %PackerOutputEdit
 @onready var packer_output_browse: Button = %PackerOutputBrowse
+@onready var mip_filter_selector: OptionButton = %MipFilterSelector
+@onready var albedo_h_format_selector: OptionButton = %AlbedoHFormatSelector
+@onready var container_selector: OptionButton = %ContainerSelector
+@onready var compression_quality_selector: OptionButton = %CompressionQualitySelector
+@onready var normal_encoding_selector: OptionButton = %NormalEncodingSelector
+@onready var resize_policy_selector: OptionButton = %ResizePolicySelector
+@onready var max_resolution_spin: SpinBox = %MaxResolutionSpin
+@onready var normal_r_format_selector: OptionButton = %NormalRFormatSelector
+@onready var create_asset_checkbox: CheckButton = %CreateAssetCheckbox
+@onready var asset_options_container: VBoxContainer = %AssetOptionsContainer
+@onready var uv_scale_spin: SpinBox = %UvScaleSpin
+@onready var detiling_rotation_spin: SpinBox = %DetilingRotationSpin
+@onready var detiling_shift_spin: SpinBox = %DetilingShiftSpin
+@onready var assets_path_edit: LineEdit = %AssetsPathEdit
+@onready var assets_path_browse: Button = %AssetsPathBrowse
 
 # === Common Controls ===
 @onready var generate_button: Button = %GenerateButton
@@ -34,6 +57,7 @@ extends PanelContainer
 @onready var roughness_result: Label = %RoughnessResult
 @onready var packed_result1: Label = %PackedResult1
 @onready var packed_result2: Label = %PackedResult2
+@onready var packed_asset_result: Label = %PackedAssetResult
 
 # === Image Converter References ===
 @onready var converter_mode_container: VBoxContainer = %ConverterModeContainer
@@ -41,6 +65,14 @@ extends PanelContainer
 @onready var browse_convert_input_button: Button = %BrowseConvertInputButton
 @onready var convert_output_path_edit: LineEdit = %ConvertOutputPathEdit
 @onready var browse_convert_output_button: Button = %BrowseConvertOutputButton
+@onready var browse_convert_folder_button: Button = %BrowseConvertFolderButton
+@onready var convert_format_selector: OptionButton = %ConvertFormatSelector
+@onready var convert_bit_depth_selector: OptionButton = %ConvertBitDepthSelector
+@onready var convert_color_selector: OptionButton = %ConvertColorSelector
+@onready var convert_max_size_spin: SpinBox = %ConvertMaxSizeSpin
+@onready var convert_existing_file_selector: OptionButton = %ConvertExistingFileSelector
+@onready var convert_strip_alpha_checkbox: CheckButton = %ConvertStripAlphaCheckbox
+@onready var unpack_dds_checkbox: CheckButton = %UnpackDdsCheckbox
 @onready var convert_result_label: Label = %ConvertResultLabel
 
 @onready var performance_label: Label = %PerformanceLabel
@@ -50,9 +82,38 @@ var file_dialog: EditorFileDialog
 var dir_dialog: EditorFileDialog
 var current_target_edit: LineEdit = null
 var generation_start_time: int = 0
+# Result of the last Standard PBR run, used by Auto-Pack
+var last_generated: Dictionary = {}
 
 enum Mode { STANDARD_PBR = 0, TERRAIN3D_PACKER = 1, IMAGE_CONVERTER = 2 }
 
+# Generated map formats understood by TextureGenerator (index = OptionButton id)
+const MAP_FORMATS: Array[String] = ["auto", "png", "png16", "tga", "tiff", "webp", "exr"]
+# Height modes understood by TextureGenerator (index = OptionButton id)
+const HEIGHT_MODES: Array[String] = ["luminance", "shading"]
+const NORMAL_MODES: Array[String] = ["single", "multiscale"]
+const ROUGHNESS_MODES: Array[String] = ["variance", "segmented"]
+# Existing file policies understood by TextureGenerator (index = OptionButton id)
+const EXISTING_FILE_POLICIES: Array[String] = ["overwrite", "skip", "version"]
+# Converter output formats understood by TextureGenerator (index = OptionButton id)
+const CONVERT_FORMATS: Array[String] = ["png", "tga", "tiff", "webp", "exr", "dds"]
+# Converter bit depths understood by TextureGenerator (index = OptionButton id)
+const CONVERT_BIT_DEPTHS: Array[String] = ["keep", "8", "16", "32"]
+# Converter colour conversions understood by TextureGenerator (index = OptionButton id)
+const CONVERT_COLORS: Array[String] = ["keep", "to_linear", "to_srgb"]
+# Mip filter names understood by TextureGenerator (index = OptionButton id)
+const MIP_FILTERS: Array[String] = ["kaiser", "lanczos", "box", "none"]
+# Block formats understood by TextureGenerator (index = OptionButton id)
+const TEXTURE_FORMATS: Array[String] = ["bc3", "bc7", "bc1", "bc4", "bc5", "rgba8", "rgba16f"]
+# Containers understood by TextureGenerator (index = OptionButton id)
+const CONTAINERS: Array[String] = ["dds", "ktx2"]
+# Compression qualities understood by TextureGenerator (index = OptionButton id)
+const COMPRESSION_QUALITIES: Array[String] = ["normal", "fast", "high"]
+# Normal encodings understood by TextureGenerator (index = OptionButton id)
+const NORMAL_ENCODINGS: Array[String] = ["renormalize", "raw", "xy"]
+# Resize policies understood by TextureGenerator (index = OptionButton id)
+const RESIZE_POLICIES: Array[String] = ["pad", "match", "pot", "fit", "error"]
+
 func _ready() -> void:
 	_ensure_generator()
 	
@@ -60,24 +121,100 @@ func _ready() -> void:
 	mode_selector.clear()
 	mode_selector.add_item("🖼️ Standard PBR Generator", Mode.STANDARD_PBR)
 	mode_selector.add_item("🗺️ Terrain3D Packer", Mode.TERRAIN3D_PACKER)
-	mode_selector.add_item("♻️ Image Converter (JPG -> PNG)", Mode.IMAGE_CONVERTER)
+	mode_selector.add_item("♻️ Image Converter", Mode.IMAGE_CONVERTER)
 	mode_selector.item_selected.connect(_on_mode_selected)
 	
 	# === Standard Mode Connections ===
 	browse_albedo_button.pressed.connect(_on_browse_albedo_pressed)
 	browse_output_button.pressed.connect(_on_browse_output_pressed)
+	map_format_selector.clear()
+	map_format_selector.add_item("Auto (PNG, 16-bit height, EXR for float sources)", 0)
+	map_format_selector.add_item("PNG 8-bit", 1)
+	map_format_selector.add_item("PNG 16-bit", 2)
+	map_format_selector.add_item("TGA", 3)
+	map_format_selector.add_item("TIFF 16-bit", 4)
+	map_format_selector.add_item("WebP (lossless)", 5)
+	map_format_selector.add_item("OpenEXR (float)", 6)
+	height_mode_selector.clear()
+	height_mode_selector.add_item("Luminance (bright = high)", 0)
+	height_mode_selector.add_item("Shape from shading (estimated light)", 1)
+	normal_detail_selector.clear()
+	normal_detail_selector.add_item("Fine (pixel detail)", 0)
+	normal_detail_selector.add_item("Multi-scale (fine to very coarse)", 1)
+	roughness_mode_selector.clear()
+	roughness_mode_selector.add_item("Texture variance (global)", 0)
+	roughness_mode_selector.add_item("Per material (segmented)", 1)
+	existing_file_selector.clear()
+	existing_file_selector.add_item("Overwrite", 0)
+	existing_file_selector.add_item("Skip", 1)
+	existing_file_selector.add_item("New version (_v2, _v3...)", 2)
 	
 	# === Packer Mode Connections ===
+	mip_filter_selector.clear()
+	mip_filter_selector.add_item("Kaiser (recommended)", 0)
+	mip_filter_selector.add_item("Lanczos (sharp)", 1)
+	mip_filter_selector.add_item("Box (fast)", 2)
+	mip_filter_selector.add_item("None", 3)
+	for selector in [albedo_h_format_selector, normal_r_format_selector]:
+		selector.clear()
+		selector.add_item("BC3 / DXT5 (RGBA)", 0)
+		selector.add_item("BC7 (RGBA, high quality)", 1)
+		selector.add_item("BC1 (RGB, no alpha)", 2)
+		selector.add_item("BC4 (R only)", 3)
+		selector.add_item("BC5 (RG only)", 4)
+		selector.add_item("RGBA8 (uncompressed)", 5)
+		selector.add_item("RGBA16F (uncompressed, float)", 6)
+	container_selector.clear()
+	container_selector.add_item("DDS", 0)
+	container_selector.add_item("KTX2", 1)
+	compression_quality_selector.clear()
+	compression_quality_selector.add_item("Normal (cluster fit)", 0)
+	compression_quality_selector.add_item("Fast (range fit)", 1)
+	compression_quality_selector.add_item("High (iterative cluster fit)", 2)
+	normal_encoding_selector.clear()
+	normal_encoding_selector.add_item("Renormalized XYZ", 0)
+	normal_encoding_selector.add_item("Raw XYZ", 1)
+	normal_encoding_selector.add_item("XY only (reconstruct Z in shader)", 2)
+	resize_policy_selector.clear()
+	resize_policy_selector.add_item("Pad to block size", 0)
+	resize_policy_selector.add_item("Match albedo size, then pad", 1)
+	resize_policy_selector.add_item("Nearest power of two", 2)
+	resize_policy_selector.add_item("Fit to max resolution", 3)
+	resize_policy_selector.add_item("Error on bad size", 4)
+	resize_policy_selector.item_selected.connect(_on_resize_policy_selected)
 	auto_pack_checkbox.toggled.connect(_on_auto_pack_toggled)
 	pack1_albedo_browse.pressed.connect(func(): _open_file_dialog(pack1_albedo_edit))
 	pack1_height_browse.pressed.connect(func(): _open_file_dialog(pack1_height_edit))
 	pack2_normal_browse.pressed.connect(func(): _open_file_dialog(pack2_normal_edit))
 	pack2_roughness_browse.pressed.connect(func(): _open_file_dialog(pack2_roughness_edit))
 	packer_output_browse.pressed.connect(func(): _open_dir_dialog(packer_output_edit))
+	create_asset_checkbox.toggled.connect(func(enabled: bool): asset_options_container.visible = enabled)
+	assets_path_browse.pressed.connect(func(): _open_file_dialog(assets_path_edit))
 	
 	# === Converter Mode Connections ===
 	browse_convert_input_button.pressed.connect(func(): _open_file_dialog(convert_input_path_edit))
+	browse_convert_folder_button.pressed.connect(func(): _open_dir_dialog(convert_input_path_edit))
 	browse_convert_output_button.pressed.connect(func(): _open_dir_dialog(convert_output_path_edit))
+	convert_format_selector.clear()
+	convert_format_selector.add_item("PNG", 0)
+	convert_format_selector.add_item("TGA", 1)
+	convert_format_selector.add_item("TIFF", 2)
+	convert_format_selector.add_item("WebP (lossless)", 3)
+	convert_format_selector.add_item("OpenEXR (float)", 4)
+	convert_format_selector.add_item("DDS (BC7)", 5)
+	convert_bit_depth_selector.clear()
+	convert_bit_depth_selector.add_item("Keep source", 0)
+	convert_bit_depth_selector.add_item("8-bit", 1)
+	convert_bit_depth_selector.add_item("16-bit", 2)
+	convert_bit_depth_selector.add_item("32-bit float", 3)
+	convert_color_selector.clear()
+	convert_color_selector.add_item("Keep", 0)
+	convert_color_selector.add_item("sRGB -> Linear", 1)
+	convert_color_selector.add_item("Linear -> sRGB", 2)
+	convert_existing_file_selector.clear()
+	convert_existing_file_selector.add_item("Overwrite", 0)
+	convert_existing_file_selector.add_item("Skip", 1)
+	convert_existing_file_selector.add_item("New version (_v2, _v3...)", 2)
 	
 	# === Common ===
 	generate_button.pressed.connect(_on_generate_pressed)
@@ -88,6 +225,9 @@ func _ready() -> void:
 	file_dialog.access = EditorFileDialog.ACCESS_FILESYSTEM
 	file_dialog.add_filter("*.png", "PNG Images")
 	file_dialog.add_filter("*.jpg,*.jpeg", "JPEG Images")
+	file_dialog.add_filter("*.dds", "DDS Textures")
+	file_dialog.add_filter("*.exr", "OpenEXR Images")
+	file_dialog.add_filter("*.tres", "Terrain3DAssets Resources")
 	file_dialog.file_selected.connect(_on_file_selected)
 	add_child(file_dialog)
 	
@@ -127,6 +267,7 @@ func _update_result_labels_visibility() -> void:
 	roughness_result.visible = (current_mode == Mode.STANDARD_PBR)
 	packed_result1.visible = (current_mode == Mode.TERRAIN3D_PACKER)
 	packed_result2.visible = (current_mode == Mode.TERRAIN3D_PACKER)
+	packed_asset_result.visible = (current_mode == Mode.TERRAIN3D_PACKER) and create_asset_checkbox.button_pressed
 	convert_result_label.visible = (current_mode == Mode.IMAGE_CONVERTER)
 
 func _update_generate_button_text() -> void:
@@ -137,11 +278,14 @@ func _update_generate_button_text() -> void:
 		Mode.TERRAIN3D_PACKER:
 			generate_button.text = "📦 Pack for Terrain3D"
 		Mode.IMAGE_CONVERTER:
-			generate_button.text = "♻️ Convert to PNG"
+			generate_button.text = "♻️ Convert Images"
 
 func _on_auto_pack_toggled(enabled: bool) -> void:
 	_update_packer_inputs_visibility()
 
+func _on_resize_policy_selected(index: int) -> void:
+	max_resolution_spin.visible = (RESIZE_POLICIES[index] == "fit")
+
 func _update_packer_inputs_visibility() -> void:
 	var auto_mode = auto_pack_checkbox.button_pressed
 	manual_pack_container.visible = not auto_mode
@@ -188,21 +332,35 @@ func _run_image_conversion() -> void:
 	var output_dir = convert_output_path_edit.text
 	
 	if input_path.is_empty():
-		_show_error("Please select a JPG/JPEG image first!")
+		_show_error("Please select an image or a folder first!")
 		return
 	
-	if not FileAccess.file_exists(input_path):
+	if not FileAccess.file_exists(input_path) and not DirAccess.dir_exists_absolute(input_path):
 		_show_error("File does not exist: " + input_path)
 		return
 	
-	_start_progress("Converting to PNG...")
+	var output_format = CONVERT_FORMATS[convert_format_selector.selected]
+	_start_progress("Converting to %s..." % output_format.to_upper())
 	generation_start_time = Time.get_ticks_msec()
 	
 	if not _ensure_generator():
 		return
 	
-	print("♻️ Converting to PNG...")
-	var result = texture_generator.convert_to_png(input_path, output_dir)
+	var result: Dictionary
+	if input_path.get_extension().to_lower() == "dds" and unpack_dds_checkbox.button_pressed:
+		print("📦 Unpacking DDS...")
+		result = texture_generator.unpack_dds(input_path, output_dir)
+	else:
+		print("♻️ Converting to %s..." % output_format.to_upper())
+		var options := {
+			"output_dir": output_dir,
+			"bit_depth": CONVERT_BIT_DEPTHS[convert_bit_depth_selector.selected],
+			"max_size": int(convert_max_size_spin.value),
+			"color": CONVERT_COLORS[convert_color_selector.selected],
+			"strip_alpha": convert_strip_alpha_checkbox.button_pressed,
+			"existing": EXISTING_FILE_POLICIES[convert_existing_file_selector.selected],
+		}
+		result = texture_generator.convert_image(input_path, output_format, options)
 	
 	_process_converter_result(result)
 
@@ -217,7 +375,25 @@ func _process_converter_result(result: Dictionary) -> void:
 		
 		result_container.visible = true
 		_update_result_labels_visibility()
-		convert_result_label.text = "🖼️ PNG Image: " + result.get("output_path", "").get_file()
+		if result.has("rgb_path"):
+			convert_result_label.text = "🖼️ RGB: %s\n🖼️ Alpha: %s" % [
+				result.get("rgb_path", "").get_file(),
+				result.get("alpha_path", "").get_file()
+			]
+		else:
+			var output_paths: PackedStringArray = result.get("output_paths", PackedStringArray())
+			var lines: PackedStringArray = []
+			if output_paths.size() == 1:
+				lines.append("🖼️ Image: " + output_paths[0].get_file())
+			else:
+				lines.append("🖼️ Converted %d images" % output_paths.size())
+			var skipped: PackedStringArray = result.get("skipped", PackedStringArray())
+			if not skipped.is_empty():
+				lines.append("⏭️ Skipped %d existing" % skipped.size())
+			var failed: PackedStringArray = result.get("failed", PackedStringArray())
+			if not failed.is_empty():
+				lines.append("⚠️ Failed %d: %s" % [failed.size(), failed[0]])
+			convert_result_label.text = "\n".join(lines)
 		
 		performance_label.visible = true
 		performance_label.text = "⚡ Converted in %.2f seconds" % generation_time
@@ -252,7 +428,17 @@ func _run_standard_generation() -> void:
 		return
 	
 	print("🚀 Generating maps...")
-	var result = texture_generator.generate_maps(albedo_path, output_path)
+	var config = {
+		"template": naming_template_edit.text,
+		"format": MAP_FORMATS[map_format_selector.selected],
+		"existing": EXISTING_FILE_POLICIES[existing_file_selector.selected],
+		"color_processing": "legacy" if legacy_color_checkbox.button_pressed else "linear",
+		"delight": delight_checkbox.button_pressed,
+		"height_mode": HEIGHT_MODES[height_mode_selector.selected],
+		"normal_mode": NORMAL_MODES[normal_detail_selector.selected],
+		"roughness_mode": ROUGHNESS_MODES[roughness_mode_selector.selected],
+	}
+	var result = texture_generator.generate_maps_with_config(albedo_path, output_path, config)
 	
 	_process_standard_result(result)
 
@@ -270,9 +456,14 @@ func _process_standard_result(result: Dictionary) -> void:
 		height_result.text = "🏔️ Height Map: " + result.get("height_path", "").get_file()
 		normal_result.text = "🌊 Normal Map: " + result.get("normal_path", "").get_file()
 		roughness_result.text = "✨ Roughness Map: " + result.get("roughness_path", "").get_file()
+		last_generated = result
+		last_generated["albedo_path"] = albedo_path_edit.text
 		
 		performance_label.visible = true
 		performance_label.text = "⚡ Generated in %.2f seconds" % generation_time
+		var skipped: PackedStringArray = result.get("skipped", PackedStringArray())
+		if not skipped.is_empty():
+			performance_label.text += " (kept existing: %s)" % ", ".join(skipped)
 		performance_label.modulate = Color.CYAN
 		
 		EditorInterface.get_resource_filesystem().scan()
@@ -303,10 +494,14 @@ func _run_auto_pack() -> void:
 	var base_dir = albedo.get_base_dir()
 	var stem = albedo.get_file().get_basename()
 	
-	# Scan for existing files
+	# Prefer the files written by the last run (custom names/formats), else scan for defaults
 	var height_file = base_dir.path_join(stem + "_height.png")
 	var normal_file = base_dir.path_join(stem + "_normal.png")
 	var roughness_file = base_dir.path_join(stem + "_roughness.png")
+	if last_generated.get("albedo_path", "") == albedo:
+		height_file = last_generated.get("height_path", height_file)
+		normal_file = last_generated.get("normal_path", normal_file)
+		roughness_file = last_generated.get("roughness_path", roughness_file)
 	
 	if not FileAccess.file_exists(height_file):
 		_show_error("Height map not found: " + height_file.get_file() + "\nPlease run Standard PBR Generator first.")
@@ -339,14 +534,43 @@ func _run_manual_pack() -> void:
 	_execute_packing(p1_albedo, p1_height, p2_normal, p2_roughness, output_dir)
 
 func _execute_packing(albedo: String, height: String, normal: String, roughness: String, output_dir: String) -> void:
-	_start_progress("Packing for Terrain3D (DDS)...")
+	var container = CONTAINERS[container_selector.selected]
+	_start_progress("Packing for Terrain3D (%s)..." % container.to_upper())
 	generation_start_time = Time.get_ticks_msec()
 	
 	if not _ensure_generator():
 		return
 	
 	print("📦 Packing for Terrain3D...")
-	var result = texture_generator.pack_terrain_3d_manual(albedo, height, normal, roughness, output_dir)
+	var options = {
+		"container": container,
+		"mip_filter": MIP_FILTERS[mip_filter_selector.selected],
+		"albedo_h_format": TEXTURE_FORMATS[albedo_h_format_selector.selected],
+		"normal_r_format": TEXTURE_FORMATS[normal_r_format_selector.selected],
+		"resize_policy": RESIZE_POLICIES[resize_policy_selector.selected],
+		"max_resolution": int(max_resolution_spin.value),
+		"quality": COMPRESSION_QUALITIES[compression_quality_selector.selected],
+		"normal_encoding": NORMAL_ENCODINGS[normal_encoding_selector.selected],
+	}
+	var result = texture_generator.pack_terrain_3d_manual(
+		albedo, height, normal, roughness, output_dir, options
+	)
+	
+	if result.get("success", false) and create_asset_checkbox.button_pressed:
+		var asset_result = texture_generator.create_terrain_3d_texture_asset(
+			result.get("albedo_h_path", ""),
+			result.get("normal_r_path", ""),
+			"",
+			uv_scale_spin.value,
+			detiling_rotation_spin.value,
+			detiling_shift_spin.value,
+			assets_path_edit.text
+		)
+		if asset_result.get("success", false):
+			result["asset_path"] = asset_result.get("asset_path", "")
+		else:
+			result["success"] = false
+			result["error"] = asset_result.get("error", "Failed to create texture asset")
 	
 	_process_packer_result(result)
 
@@ -363,9 +587,21 @@ func _process_packer_result(result: Dictionary) -> void:
 		_update_result_labels_visibility()
 		packed_result1.text = "📦 Albedo+H: " + result.get("albedo_h_path", "").get_file()
 		packed_result2.text = "📦 Normal+R: " + result.get("normal_r_path", "").get_file()
+		if result.has("normal_mean_angle_error"):
+			packed_result2.text += " (%.2f° mean / %.2f° max error)" % [
+				result.get("normal_mean_angle_error", 0.0),
+				result.get("normal_max_angle_error", 0.0)
+			]
+		packed_asset_result.text = "🗺️ Texture Asset: " + result.get("asset_path", "").get_file()
 		
 		performance_label.visible = true
-		performance_label.text = "⚡ Packed in %.2f seconds" % generation_time
+		performance_label.text = "⚡ Packed %dx%d (resize: %s) in %.2f seconds" % [
+			result.get("width", 0), result.get("height", 0),
+			result.get("resize_policy", ""), generation_time
+		]
+		var resized_inputs: PackedStringArray = result.get("resized_inputs", PackedStringArray())
+		if not resized_inputs.is_empty():
+			performance_label.text += " — resized: " + ", ".join(resized_inputs)
 		performance_label.modulate = Color.CYAN
 		
 		EditorInterface.get_resource_filesystem().scan()
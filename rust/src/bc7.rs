@@ -0,0 +1,761 @@
+use rayon::prelude::*;
+
+/// BC7 block encoder
+/// Encodes every block in mode 6 (single subset, RGBA 7.7.7.7 endpoints with a
+/// per-endpoint p-bit and 4-bit indices). Mode 6 alone gives high quality RGBA
+/// output without the search cost of the partitioned modes.
+pub struct Bc7Encoder;
+
+/// 4-bit index interpolation weights (out of 64)
+const WEIGHTS_4: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+const BLOCK_SIZE: usize = 16;
+
+/// Encoder settings
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bc7Params {
+    /// Maximum least-squares endpoint refinement passes (0 = principal axis only)
+    pub refine_passes: usize,
+    /// Per-channel (RGBA) error weights used when choosing indices and p-bits
+    pub weights: [f32; 4],
+}
+
+impl Default for Bc7Params {
+    fn default() -> Self {
+        Self {
+            refine_passes: 1,
+            weights: [1.0; 4],
+        }
+    }
+}
+
+impl Bc7Encoder {
+    /// Compress tightly packed RGBA8 pixels, one row of blocks per rayon task.
+    /// Partial edge blocks repeat the last row/column so padding does not pull the endpoints.
+    pub fn compress(rgba: &[u8], width: usize, height: usize, params: Bc7Params, output: &mut [u8]) {
+        let blocks_wide = width.div_ceil(4);
+
+        output
+            .par_chunks_mut(blocks_wide * BLOCK_SIZE)
+            .enumerate()
+            .for_each(|(by, row)| {
+                for (bx, block) in row.chunks_mut(BLOCK_SIZE).enumerate() {
+                    let mut pixels = [[0u8; 4]; 16];
+                    for (i, pixel) in pixels.iter_mut().enumerate() {
+                        let x = (bx * 4 + i % 4).min(width - 1);
+                        let y = (by * 4 + i / 4).min(height - 1);
+                        let offset = 4 * (y * width + x);
+                        pixel.copy_from_slice(&rgba[offset..offset + 4]);
+                    }
+                    Self::compress_block(&pixels, params, block);
+                }
+            });
+    }
+
+    /// Encode one 4x4 block (row-major RGBA pixels) into 16 bytes.
+    pub fn compress_block(pixels: &[[u8; 4]; 16], params: Bc7Params, output: &mut [u8]) {
+        let colors: Vec<[f32; 4]> = pixels
+            .iter()
+            .map(|p| [p[0] as f32, p[1] as f32, p[2] as f32, p[3] as f32])
+            .collect();
+
+        // Initial endpoints along the principal axis of the block
+        let (e0, e1) = Self::principal_endpoints(&colors);
+
+        let mut best = Self::quantize(&colors, &e0, &e1, &params.weights);
+
+        // Least-squares refinement using the chosen indices, until it stops improving
+        for _ in 0..params.refine_passes {
+            let Some((r0, r1)) = Self::refine(&colors, &best.indices) else {
+                break;
+            };
+            let refined = Self::quantize(&colors, &r0, &r1, &params.weights);
+            if refined.error >= best.error {
+                break;
+            }
+            best = refined;
+        }
+
+        Self::write_block(&best, output);
+    }
+
+    fn principal_endpoints(colors: &[[f32; 4]]) -> ([f32; 4], [f32; 4]) {
+        let n = colors.len() as f32;
+        let mut mean = [0.0f32; 4];
+        for c in colors {
+            for i in 0..4 {
+                mean[i] += c[i] / n;
+            }
+        }
+
+        let mut cov = [[0.0f32; 4]; 4];
+        for c in colors {
+            for i in 0..4 {
+                for j in 0..4 {
+                    cov[i][j] += (c[i] - mean[i]) * (c[j] - mean[j]);
+                }
+            }
+        }
+
+        // Power iteration for the dominant eigenvector, starting from the covariance row of
+        // the most varying channel (a fixed start vector can be orthogonal to the real axis)
+        let widest = (0..4).fold(0, |best, i| if cov[i][i] > cov[best][best] { i } else { best });
+        let mut axis = cov[widest];
+        for _ in 0..8 {
+            let mut next = [0.0f32; 4];
+            for i in 0..4 {
+                for j in 0..4 {
+                    next[i] += cov[i][j] * axis[j];
+                }
+            }
+            let length = next.iter().map(|v| v * v).sum::<f32>().sqrt();
+            if length < 1e-6 {
+                break;
+            }
+            axis = next.map(|v| v / length);
+        }
+
+        let (mut t_min, mut t_max) = (f32::MAX, f32::MIN);
+        for c in colors {
+            let t: f32 = (0..4).map(|i| (c[i] - mean[i]) * axis[i]).sum();
+            t_min = t_min.min(t);
+            t_max = t_max.max(t);
+        }
+        if t_min > t_max {
+            return (mean, mean);
+        }
+
+        let e0 = std::array::from_fn(|i| mean[i] + axis[i] * t_min);
+        let e1 = std::array::from_fn(|i| mean[i] + axis[i] * t_max);
+        (e0, e1)
+    }
+
+    /// Quantize both endpoints to 7 bits + p-bit, trying all p-bit combinations.
+    /// Fully opaque blocks keep both p-bits set so alpha decodes to exactly 255.
+    fn quantize(colors: &[[f32; 4]], e0: &[f32; 4], e1: &[f32; 4], weights: &[f32; 4]) -> EncodedBlock {
+        let opaque = colors.iter().all(|c| c[3] >= 255.0);
+        let pbits: &[u8] = if opaque { &[1] } else { &[0, 1] };
+        let mut best: Option<EncodedBlock> = None;
+
+        for &p0 in pbits {
+            for &p1 in pbits {
+                let mut q0 = Self::quantize_endpoint(e0, p0);
+                let mut q1 = Self::quantize_endpoint(e1, p1);
+                if opaque {
+                    q0[3] = 127;
+                    q1[3] = 127;
+                }
+                let candidate = Self::assign_indices(colors, q0, q1, p0, p1, weights);
+                if best.as_ref().is_none_or(|b| candidate.error < b.error) {
+                    best = Some(candidate);
+                }
+            }
+        }
+
+        best.expect("at least one p-bit combination")
+    }
+
+    fn quantize_endpoint(endpoint: &[f32; 4], pbit: u8) -> [u8; 4] {
+        endpoint.map(|v| ((v - pbit as f32) / 2.0).round().clamp(0.0, 127.0) as u8)
+    }
+
+    fn expand(q: [u8; 4], pbit: u8) -> [u32; 4] {
+        q.map(|v| ((v as u32) << 1) | pbit as u32)
+    }
+
+    fn assign_indices(
+        colors: &[[f32; 4]],
+        q0: [u8; 4],
+        q1: [u8; 4],
+        p0: u8,
+        p1: u8,
+        weights: &[f32; 4],
+    ) -> EncodedBlock {
+        let a = Self::expand(q0, p0);
+        let b = Self::expand(q1, p1);
+
+        let palette: [[f32; 4]; 16] = std::array::from_fn(|i| {
+            let w = WEIGHTS_4[i];
+            std::array::from_fn(|c| (((64 - w) * a[c] + w * b[c] + 32) >> 6) as f32)
+        });
+
+        let mut indices = [0u8; 16];
+        let mut error = 0.0f32;
+        for (pixel, index) in colors.iter().zip(indices.iter_mut()) {
+            let (best_index, best_error) = palette
+                .iter()
+                .enumerate()
+                .map(|(i, p)| (i, (0..4).map(|c| weights[c] * (p[c] - pixel[c]).powi(2)).sum::<f32>()))
+                .fold((0, f32::MAX), |acc, x| if x.1 < acc.1 { x } else { acc });
+            *index = best_index as u8;
+            error += best_error;
+        }
+
+        EncodedBlock {
+            endpoints: [q0, q1],
+            pbits: [p0, p1],
+            indices,
+            error,
+        }
+    }
+
+    /// Solve for endpoints minimizing squared error given fixed interpolation weights.
+    fn refine(colors: &[[f32; 4]], indices: &[u8; 16]) -> Option<([f32; 4], [f32; 4])> {
+        let (mut aa, mut ab, mut bb) = (0.0f32, 0.0f32, 0.0f32);
+        let mut ax = [0.0f32; 4];
+        let mut bx = [0.0f32; 4];
+
+        for (c, &i) in colors.iter().zip(indices) {
+            let w = WEIGHTS_4[i as usize] as f32 / 64.0;
+            let alpha = 1.0 - w;
+            aa += alpha * alpha;
+            ab += alpha * w;
+            bb += w * w;
+            for k in 0..4 {
+                ax[k] += alpha * c[k];
+                bx[k] += w * c[k];
+            }
+        }
+
+        let det = aa * bb - ab * ab;
+        if det.abs() < 1e-6 {
+            return None;
+        }
+
+        let e0 = std::array::from_fn(|k| ((bb * ax[k] - ab * bx[k]) / det).clamp(0.0, 255.0));
+        let e1 = std::array::from_fn(|k| ((aa * bx[k] - ab * ax[k]) / det).clamp(0.0, 255.0));
+        Some((e0, e1))
+    }
+
+    fn write_block(block: &EncodedBlock, output: &mut [u8]) {
+        let mut endpoints = block.endpoints;
+        let mut pbits = block.pbits;
+        let mut indices = block.indices;
+
+        // The anchor index (pixel 0) is stored with its MSB implied as 0
+        if indices[0] >= 8 {
+            endpoints.swap(0, 1);
+            pbits.swap(0, 1);
+            for index in &mut indices {
+                *index = 15 - *index;
+            }
+        }
+
+        let mut writer = BitWriter::new(output);
+        writer.write(1 << 6, 7); // mode 6
+        for (&c0, &c1) in endpoints[0].iter().zip(&endpoints[1]) {
+            writer.write(c0 as u32, 7);
+            writer.write(c1 as u32, 7);
+        }
+        writer.write(pbits[0] as u32, 1);
+        writer.write(pbits[1] as u32, 1);
+        writer.write(indices[0] as u32, 3);
+        for &index in &indices[1..] {
+            writer.write(index as u32, 4);
+        }
+    }
+}
+
+struct EncodedBlock {
+    endpoints: [[u8; 4]; 2],
+    pbits: [u8; 2],
+    indices: [u8; 16],
+    error: f32,
+}
+
+/// LSB-first bit writer over a 16-byte block
+struct BitWriter<'a> {
+    output: &'a mut [u8],
+    position: usize,
+}
+
+impl<'a> BitWriter<'a> {
+    fn new(output: &'a mut [u8]) -> Self {
+        output[..BLOCK_SIZE].fill(0);
+        Self { output, position: 0 }
+    }
+
+    fn write(&mut self, value: u32, bits: usize) {
+        for bit in 0..bits {
+            if value & (1 << bit) != 0 {
+                let p = self.position + bit;
+                self.output[p / 8] |= 1 << (p % 8);
+            }
+        }
+        self.position += bits;
+    }
+}
+
+/// BC7 block decoder (all eight modes)
+pub struct Bc7Decoder;
+
+/// Bit layout of one BC7 mode
+struct ModeInfo {
+    subsets: usize,
+    partition_bits: usize,
+    rotation_bits: usize,
+    selection_bits: usize,
+    color_bits: usize,
+    alpha_bits: usize,
+    endpoint_pbits: bool,
+    shared_pbits: bool,
+    index_bits: usize,
+    index2_bits: usize,
+}
+
+const MODES: [ModeInfo; 8] = [
+    ModeInfo {
+        subsets: 3,
+        partition_bits: 4,
+        rotation_bits: 0,
+        selection_bits: 0,
+        color_bits: 4,
+        alpha_bits: 0,
+        endpoint_pbits: true,
+        shared_pbits: false,
+        index_bits: 3,
+        index2_bits: 0,
+    },
+    ModeInfo {
+        subsets: 2,
+        partition_bits: 6,
+        rotation_bits: 0,
+        selection_bits: 0,
+        color_bits: 6,
+        alpha_bits: 0,
+        endpoint_pbits: false,
+        shared_pbits: true,
+        index_bits: 3,
+        index2_bits: 0,
+    },
+    ModeInfo {
+        subsets: 3,
+        partition_bits: 6,
+        rotation_bits: 0,
+        selection_bits: 0,
+        color_bits: 5,
+        alpha_bits: 0,
+        endpoint_pbits: false,
+        shared_pbits: false,
+        index_bits: 2,
+        index2_bits: 0,
+    },
+    ModeInfo {
+        subsets: 2,
+        partition_bits: 6,
+        rotation_bits: 0,
+        selection_bits: 0,
+        color_bits: 7,
+        alpha_bits: 0,
+        endpoint_pbits: true,
+        shared_pbits: false,
+        index_bits: 2,
+        index2_bits: 0,
+    },
+    ModeInfo {
+        subsets: 1,
+        partition_bits: 0,
+        rotation_bits: 2,
+        selection_bits: 1,
+        color_bits: 5,
+        alpha_bits: 6,
+        endpoint_pbits: false,
+        shared_pbits: false,
+        index_bits: 2,
+        index2_bits: 3,
+    },
+    ModeInfo {
+        subsets: 1,
+        partition_bits: 0,
+        rotation_bits: 2,
+        selection_bits: 0,
+        color_bits: 7,
+        alpha_bits: 8,
+        endpoint_pbits: false,
+        shared_pbits: false,
+        index_bits: 2,
+        index2_bits: 2,
+    },
+    ModeInfo {
+        subsets: 1,
+        partition_bits: 0,
+        rotation_bits: 0,
+        selection_bits: 0,
+        color_bits: 7,
+        alpha_bits: 7,
+        endpoint_pbits: true,
+        shared_pbits: false,
+        index_bits: 4,
+        index2_bits: 0,
+    },
+    ModeInfo {
+        subsets: 2,
+        partition_bits: 6,
+        rotation_bits: 0,
+        selection_bits: 0,
+        color_bits: 5,
+        alpha_bits: 5,
+        endpoint_pbits: true,
+        shared_pbits: false,
+        index_bits: 2,
+        index2_bits: 0,
+    },
+];
+
+const WEIGHTS_2: [u32; 4] = [0, 21, 43, 64];
+const WEIGHTS_3: [u32; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+
+/// Two-subset partitions, bit i set = pixel i belongs to subset 1
+const PARTITIONS_2: [u16; 64] = [
+    0xCCCC, 0x8888, 0xEEEE, 0xECC8, 0xC880, 0xFEEC, 0xFEC8, 0xEC80,
+    0xC800, 0xFFEC, 0xFE80, 0xE800, 0xFFE8, 0xFF00, 0xFFF0, 0xF000,
+    0xF710, 0x008E, 0x7100, 0x08CE, 0x008C, 0x7310, 0x3100, 0x8CCE,
+    0x088C, 0x3110, 0x6666, 0x366C, 0x17E8, 0x0FF0, 0x718E, 0x399C,
+    0xAAAA, 0xF0F0, 0x5A5A, 0x33CC, 0x3C3C, 0x55AA, 0x9696, 0xA55A,
+    0x73CE, 0x13C8, 0x324C, 0x3BDC, 0x6996, 0xC33C, 0x9966, 0x0660,
+    0x0272, 0x04E4, 0x4E40, 0x2720, 0xC936, 0x936C, 0x39C6, 0x639C,
+    0x9336, 0x9CC6, 0x817E, 0xE718, 0xCCF0, 0x0FCC, 0x7744, 0xEE22,
+];
+
+/// Three-subset partitions, subset index per pixel
+const PARTITIONS_3: [[u8; 16]; 64] = [
+    [0, 0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 1, 2, 2, 2, 2],
+    [0, 0, 0, 1, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 2, 0, 0, 1, 2, 2, 1, 1, 2, 2, 1, 1],
+    [0, 2, 2, 2, 0, 0, 2, 2, 0, 0, 1, 1, 0, 1, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2],
+    [0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 2, 2, 0, 0, 2, 2],
+    [0, 0, 2, 2, 0, 0, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1],
+    [0, 0, 1, 1, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2],
+    [0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2],
+    [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2],
+    [0, 1, 1, 2, 0, 1, 1, 2, 0, 1, 1, 2, 0, 1, 1, 2],
+    [0, 1, 2, 2, 0, 1, 2, 2, 0, 1, 2, 2, 0, 1, 2, 2],
+    [0, 0, 1, 1, 0, 1, 1, 2, 1, 1, 2, 2, 1, 2, 2, 2],
+    [0, 0, 1, 1, 2, 0, 0, 1, 2, 2, 0, 0, 2, 2, 2, 0],
+    [0, 0, 0, 1, 0, 0, 1, 1, 0, 1, 1, 2, 1, 1, 2, 2],
+    [0, 1, 1, 1, 0, 0, 1, 1, 2, 0, 0, 1, 2, 2, 0, 0],
+    [0, 0, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2],
+    [0, 0, 2, 2, 0, 0, 2, 2, 0, 0, 2, 2, 1, 1, 1, 1],
+    [0, 1, 1, 1, 0, 1, 1, 1, 0, 2, 2, 2, 0, 2, 2, 2],
+    [0, 0, 0, 1, 0, 0, 0, 1, 2, 2, 2, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 0, 0, 1, 1, 0, 1, 2, 2, 0, 1, 2, 2],
+    [0, 0, 0, 0, 1, 1, 0, 0, 2, 2, 1, 0, 2, 2, 1, 0],
+    [0, 1, 2, 2, 0, 1, 2, 2, 0, 0, 1, 1, 0, 0, 0, 0],
+    [0, 0, 1, 2, 0, 0, 1, 2, 1, 1, 2, 2, 2, 2, 2, 2],
+    [0, 1, 1, 0, 1, 2, 2, 1, 1, 2, 2, 1, 0, 1, 1, 0],
+    [0, 0, 0, 0, 0, 1, 1, 0, 1, 2, 2, 1, 1, 2, 2, 1],
+    [0, 0, 2, 2, 1, 1, 0, 2, 1, 1, 0, 2, 0, 0, 2, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 2, 0, 0, 2, 2, 2, 2, 2],
+    [0, 0, 1, 1, 0, 1, 2, 2, 0, 1, 2, 2, 0, 0, 1, 1],
+    [0, 0, 0, 0, 2, 0, 0, 0, 2, 2, 1, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 2, 2, 2],
+    [0, 2, 2, 2, 0, 0, 2, 2, 0, 0, 1, 2, 0, 0, 1, 1],
+    [0, 0, 1, 1, 0, 0, 1, 2, 0, 0, 2, 2, 0, 2, 2, 2],
+    [0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0],
+    [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0],
+    [0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0],
+    [0, 1, 2, 0, 2, 0, 1, 2, 1, 2, 0, 1, 0, 1, 2, 0],
+    [0, 0, 1, 1, 2, 2, 0, 0, 1, 1, 2, 2, 0, 0, 1, 1],
+    [0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0, 1, 1],
+    [0, 1, 0, 1, 0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 2, 1, 2, 1, 2, 1],
+    [0, 0, 2, 2, 1, 1, 2, 2, 0, 0, 2, 2, 1, 1, 2, 2],
+    [0, 0, 2, 2, 0, 0, 1, 1, 0, 0, 2, 2, 0, 0, 1, 1],
+    [0, 2, 2, 0, 1, 2, 2, 1, 0, 2, 2, 0, 1, 2, 2, 1],
+    [0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 0, 1, 0, 1],
+    [0, 0, 0, 0, 2, 1, 2, 1, 2, 1, 2, 1, 2, 1, 2, 1],
+    [0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 2, 2, 2, 2],
+    [0, 2, 2, 2, 0, 1, 1, 1, 0, 2, 2, 2, 0, 1, 1, 1],
+    [0, 0, 0, 2, 1, 1, 1, 2, 0, 0, 0, 2, 1, 1, 1, 2],
+    [0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2],
+    [0, 2, 2, 2, 0, 1, 1, 1, 0, 1, 1, 1, 0, 2, 2, 2],
+    [0, 0, 0, 2, 1, 1, 1, 2, 1, 1, 1, 2, 0, 0, 0, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 1, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 2, 2, 0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 2, 2],
+    [0, 0, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2, 0, 0, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2],
+    [0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 1],
+    [0, 2, 2, 2, 1, 2, 2, 2, 0, 2, 2, 2, 1, 2, 2, 2],
+    [0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 1, 1, 1, 2, 0, 1, 1, 2, 2, 0, 1, 2, 2, 2, 0],
+];
+
+/// Anchor pixel of subset 1 for two-subset partitions
+const ANCHORS_2: [usize; 64] = [
+    15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15,
+    15, 2, 8, 2, 2, 8, 8, 15, 2, 8, 2, 2, 8, 8, 2, 2,
+    15, 15, 6, 8, 2, 8, 15, 15, 2, 8, 2, 2, 2, 15, 15, 6,
+    6, 2, 6, 8, 15, 15, 2, 2, 15, 15, 15, 15, 15, 2, 2, 15,
+];
+
+/// Anchor pixels of subsets 1 and 2 for three-subset partitions
+const ANCHORS_3_SECOND: [usize; 64] = [
+    3, 3, 15, 15, 8, 3, 15, 15, 8, 8, 6, 6, 6, 5, 3, 3,
+    3, 3, 8, 15, 3, 3, 6, 10, 5, 8, 8, 6, 8, 5, 15, 15,
+    8, 15, 3, 5, 6, 10, 8, 15, 15, 3, 15, 5, 15, 15, 15, 15,
+    3, 15, 5, 5, 5, 8, 5, 10, 5, 10, 8, 13, 15, 12, 3, 3,
+];
+const ANCHORS_3_THIRD: [usize; 64] = [
+    15, 8, 8, 3, 15, 15, 3, 8, 15, 15, 15, 15, 15, 15, 15, 8,
+    15, 8, 15, 3, 15, 8, 15, 8, 3, 15, 6, 10, 15, 15, 10, 8,
+    15, 3, 15, 10, 10, 8, 9, 10, 6, 15, 8, 15, 3, 6, 6, 8,
+    15, 3, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 3, 15, 15, 8,
+];
+
+impl Bc7Decoder {
+    /// Decompress BC7 data into tightly packed RGBA8 pixels.
+    pub fn decompress(data: &[u8], width: usize, height: usize, output: &mut [u8]) {
+        let blocks_wide = width.div_ceil(4);
+
+        for (i, block) in data.chunks_exact(BLOCK_SIZE).enumerate() {
+            let (bx, by) = (i % blocks_wide, i / blocks_wide);
+            let pixels = Self::decompress_block(block);
+            for (p, pixel) in pixels.iter().enumerate() {
+                let x = bx * 4 + p % 4;
+                let y = by * 4 + p / 4;
+                if x < width && y < height {
+                    let offset = 4 * (y * width + x);
+                    output[offset..offset + 4].copy_from_slice(pixel);
+                }
+            }
+        }
+    }
+
+    /// Decode one 16-byte block into row-major RGBA pixels.
+    /// Reserved mode 8 blocks decode to transparent black, as the spec requires.
+    pub fn decompress_block(block: &[u8]) -> [[u8; 4]; 16] {
+        let Some(mode) = (0..8).find(|&m| block[0] & (1 << m) != 0) else {
+            return [[0; 4]; 16];
+        };
+        let ModeInfo {
+            subsets,
+            partition_bits,
+            rotation_bits,
+            selection_bits,
+            color_bits,
+            alpha_bits,
+            endpoint_pbits,
+            shared_pbits,
+            index_bits,
+            index2_bits,
+        } = MODES[mode];
+
+        let mut reader = BitReader::new(block);
+        reader.read(mode + 1);
+        let partition = reader.read(partition_bits) as usize;
+        let rotation = reader.read(rotation_bits);
+        let selection = reader.read(selection_bits);
+
+        // Endpoints: all R values, then G, B and A, two per subset
+        let mut endpoints = [[0u32; 4]; 6];
+        for channel in 0..4 {
+            let bits = if channel < 3 { color_bits } else { alpha_bits };
+            for endpoint in endpoints.iter_mut().take(subsets * 2) {
+                endpoint[channel] = if bits > 0 { reader.read(bits) } else { 255 };
+            }
+        }
+
+        // p-bits extend each endpoint by one LSB
+        let mut pbits = [0u32; 6];
+        if endpoint_pbits {
+            for pbit in pbits.iter_mut().take(subsets * 2) {
+                *pbit = reader.read(1);
+            }
+        } else if shared_pbits {
+            for subset in 0..subsets {
+                let pbit = reader.read(1);
+                pbits[subset * 2] = pbit;
+                pbits[subset * 2 + 1] = pbit;
+            }
+        }
+
+        let has_pbit = endpoint_pbits || shared_pbits;
+        for (endpoint, &pbit) in endpoints.iter_mut().zip(&pbits).take(subsets * 2) {
+            for (channel, component) in endpoint.iter_mut().enumerate() {
+                let mut bits = if channel < 3 { color_bits } else { alpha_bits };
+                if bits == 0 {
+                    continue;
+                }
+                let mut value = *component;
+                if has_pbit {
+                    value = (value << 1) | pbit;
+                    bits += 1;
+                }
+                *component = (value << (8 - bits)) | (value >> (2 * bits - 8));
+            }
+        }
+
+        let subset_of = |pixel: usize| -> usize {
+            match subsets {
+                2 => ((PARTITIONS_2[partition] >> pixel) & 1) as usize,
+                3 => PARTITIONS_3[partition][pixel] as usize,
+                _ => 0,
+            }
+        };
+        let is_anchor = |pixel: usize| -> bool {
+            pixel == 0
+                || match subsets {
+                    2 => pixel == ANCHORS_2[partition],
+                    3 => pixel == ANCHORS_3_SECOND[partition] || pixel == ANCHORS_3_THIRD[partition],
+                    _ => false,
+                }
+        };
+
+        let mut indices = [0u32; 16];
+        for (pixel, index) in indices.iter_mut().enumerate() {
+            let bits = if is_anchor(pixel) { index_bits - 1 } else { index_bits };
+            *index = reader.read(bits);
+        }
+        let mut indices2 = [0u32; 16];
+        if index2_bits > 0 {
+            for (pixel, index) in indices2.iter_mut().enumerate() {
+                let bits = if pixel == 0 { index2_bits - 1 } else { index2_bits };
+                *index = reader.read(bits);
+            }
+        }
+
+        let interpolate = |e0: u32, e1: u32, index: u32, bits: usize| -> u8 {
+            let weight = match bits {
+                2 => WEIGHTS_2[index as usize],
+                3 => WEIGHTS_3[index as usize],
+                _ => WEIGHTS_4[index as usize],
+            };
+            (((64 - weight) * e0 + weight * e1 + 32) >> 6) as u8
+        };
+
+        let mut pixels = [[0u8; 4]; 16];
+        for (p, pixel) in pixels.iter_mut().enumerate() {
+            let subset = subset_of(p);
+            let e0 = endpoints[subset * 2];
+            let e1 = endpoints[subset * 2 + 1];
+
+            // Modes 4 and 5 carry separate colour and alpha indices
+            let (color_index, color_index_bits, alpha_index, alpha_index_bits) = if index2_bits == 0 {
+                (indices[p], index_bits, indices[p], index_bits)
+            } else if selection == 0 {
+                (indices[p], index_bits, indices2[p], index2_bits)
+            } else {
+                (indices2[p], index2_bits, indices[p], index_bits)
+            };
+
+            for channel in 0..3 {
+                pixel[channel] = interpolate(e0[channel], e1[channel], color_index, color_index_bits);
+            }
+            pixel[3] = if alpha_bits > 0 {
+                interpolate(e0[3], e1[3], alpha_index, alpha_index_bits)
+            } else {
+                255
+            };
+
+            match rotation {
+                1 => pixel.swap(0, 3),
+                2 => pixel.swap(1, 3),
+                3 => pixel.swap(2, 3),
+                _ => {}
+            }
+        }
+
+        pixels
+    }
+}
+
+/// LSB-first bit reader over a 16-byte block
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, position: 0 }
+    }
+
+    fn read(&mut self, bits: usize) -> u32 {
+        let mut value = 0u32;
+        for bit in 0..bits {
+            let p = self.position + bit;
+            if self.data[p / 8] & (1 << (p % 8)) != 0 {
+                value |= 1 << bit;
+            }
+        }
+        self.position += bits;
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(rgba: &[u8], width: usize, height: usize) -> Vec<u8> {
+        let blocks = width.div_ceil(4) * height.div_ceil(4);
+        let mut compressed = vec![0u8; blocks * BLOCK_SIZE];
+        Bc7Encoder::compress(rgba, width, height, Bc7Params::default(), &mut compressed);
+        let mut decoded = vec![0u8; width * height * 4];
+        Bc7Decoder::decompress(&compressed, width, height, &mut decoded);
+        decoded
+    }
+
+    fn psnr(a: &[u8], b: &[u8]) -> f64 {
+        let mse = a.iter().zip(b).map(|(&x, &y)| (x as f64 - y as f64).powi(2)).sum::<f64>() / a.len() as f64;
+        10.0 * (255.0 * 255.0 / mse.max(1e-12)).log10()
+    }
+
+    #[test]
+    fn flat_block_round_trips() {
+        let rgba = [10u8, 120, 240, 128].repeat(16);
+        let decoded = round_trip(&rgba, 4, 4);
+        for (original, decoded) in rgba.chunks(4).zip(decoded.chunks(4)) {
+            for c in 0..4 {
+                assert!((original[c] as i32 - decoded[c] as i32).abs() <= 1, "{:?} vs {:?}", original, decoded);
+            }
+        }
+    }
+
+    #[test]
+    fn opaque_block_stays_opaque() {
+        let rgba = [200u8, 100, 50, 255].repeat(16);
+        let decoded = round_trip(&rgba, 4, 4);
+        assert!(decoded.chunks(4).all(|pixel| pixel[3] == 255));
+
+        let gradient: Vec<u8> = (0..64u8).flat_map(|i| [i * 4, 255 - i * 4, i, 255]).collect();
+        let decoded = round_trip(&gradient, 8, 8);
+        assert!(decoded.chunks(4).all(|pixel| pixel[3] == 255));
+    }
+
+    #[test]
+    fn gradient_keeps_quality() {
+        let (width, height) = (32usize, 32usize);
+        let gradient = |colour: fn(u32, u32) -> [u8; 4]| -> Vec<u8> {
+            (0..width * height)
+                .flat_map(|i| colour((i % width) as u32, (i / width) as u32))
+                .collect()
+        };
+
+        // Colours along one line per block fit mode 6 almost exactly
+        let ramp = gradient(|x, _| [(x * 8) as u8, 90, (255 - x * 8) as u8, (128 + x * 2) as u8]);
+        let decoded = round_trip(&ramp, width, height);
+        assert!(psnr(&ramp, &decoded) > 42.0, "ramp PSNR {}", psnr(&ramp, &decoded));
+
+        let planar = gradient(|x, y| [(x * 8) as u8, (y * 8) as u8, ((x + y) * 4) as u8, 255]);
+        let decoded = round_trip(&planar, width, height);
+        assert!(psnr(&planar, &decoded) > 32.0, "planar PSNR {}", psnr(&planar, &decoded));
+    }
+
+    #[test]
+    fn partial_edge_blocks() {
+        let (width, height) = (5usize, 6usize);
+        let rgba: Vec<u8> = (0..width * height)
+            .flat_map(|i| [(i * 8) as u8, 90, (255 - i * 8) as u8, 255])
+            .collect();
+        let decoded = round_trip(&rgba, width, height);
+        assert_eq!(decoded.len(), rgba.len());
+        assert!(psnr(&rgba, &decoded) > 40.0, "PSNR {}", psnr(&rgba, &decoded));
+        assert!(decoded.chunks(4).all(|pixel| pixel[3] == 255));
+    }
+}
@@ -1,29 +1,676 @@
-use image::{DynamicImage, GenericImageView, RgbaImage, Rgba};
+use image::{DynamicImage, GenericImageView, GrayImage, Luma, Rgb, RgbImage, RgbaImage, Rgba};
 use std::io::Write;
 use std::path::Path;
 
+use crate::bc7::{Bc7Decoder, Bc7Encoder, Bc7Params};
+use crate::ktx2::Ktx2Writer;
+use crate::mipmap::{srgb_to_linear, MipFilter, MipmapGenerator, TextureContent};
+use half::f16;
+use rayon::prelude::*;
+
+/// Pixel format for packed textures.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextureFormat {
+    /// RGB, no alpha (8 bytes per block)
+    Bc1,
+    /// RGB + interpolated alpha (16 bytes per block)
+    Bc3,
+    /// Single channel (R) - height or roughness (8 bytes per block)
+    Bc4,
+    /// Two channels (RG) - normal XY (16 bytes per block)
+    Bc5,
+    /// High quality RGBA (16 bytes per block, requires a DX10 header)
+    Bc7,
+    /// Uncompressed 8-bit RGBA (lossless, for debugging packing issues)
+    Rgba8,
+    /// Uncompressed half-float RGBA (always linear)
+    Rgba16F,
+}
+
+/// File container for encoded textures.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Container {
+    /// DirectDraw Surface (legacy or DX10 header)
+    Dds,
+    /// Khronos KTX 2.0 (no supercompression)
+    Ktx2,
+}
+
+impl Container {
+    /// Parse a container name as passed from GDScript ("" selects DDS).
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        match name.trim().trim_start_matches('.').to_ascii_lowercase().as_str() {
+            "" | "dds" => Ok(Container::Dds),
+            "ktx2" | "ktx" => Ok(Container::Ktx2),
+            other => Err(format!("Unknown container '{}' (expected dds or ktx2)", other)),
+        }
+    }
+
+    /// File extension without the dot
+    pub fn extension(self) -> &'static str {
+        match self {
+            Container::Dds => "dds",
+            Container::Ktx2 => "ktx2",
+        }
+    }
+}
+
+/// Colour space tag written to the DDS header (and KTX2 format descriptor).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorSpace {
+    /// Legacy header without colour space information (where the format allows it)
+    Unspecified,
+    /// DX10 header with an `_UNORM_SRGB` format (albedo)
+    Srgb,
+    /// DX10 header with a plain `_UNORM` format (normals, masks)
+    Linear,
+}
+
+/// Block compression effort.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CompressionQuality {
+    /// Range fit - fastest, lowest quality
+    Fast,
+    /// Cluster fit
+    Normal,
+    /// Iterative cluster fit - slowest, best quality
+    High,
+}
+
+impl CompressionQuality {
+    /// Parse a quality name as passed from GDScript.
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "fast" | "range" => Ok(CompressionQuality::Fast),
+            "" | "normal" | "cluster" => Ok(CompressionQuality::Normal),
+            "high" | "iterative" => Ok(CompressionQuality::High),
+            other => Err(format!(
+                "Unknown compression quality '{}' (expected fast, normal or high)",
+                other
+            )),
+        }
+    }
+
+    fn algorithm(self) -> texpresso::Algorithm {
+        match self {
+            CompressionQuality::Fast => texpresso::Algorithm::RangeFit,
+            CompressionQuality::Normal => texpresso::Algorithm::ClusterFit,
+            CompressionQuality::High => texpresso::Algorithm::IterativeClusterFit,
+        }
+    }
+
+    /// BC7 endpoint refinement passes
+    fn refine_passes(self) -> usize {
+        match self {
+            CompressionQuality::Fast => 0,
+            CompressionQuality::Normal => 1,
+            CompressionQuality::High => 8,
+        }
+    }
+}
+
+/// Relative importance of the RGB channels when fitting blocks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorWeights {
+    /// Weighted by perceived brightness (albedo)
+    Perceptual,
+    /// All channels equal (normal maps, packed masks)
+    Uniform,
+}
+
+impl ColorWeights {
+    fn rgb(self) -> texpresso::ColourWeights {
+        match self {
+            ColorWeights::Perceptual => texpresso::COLOUR_WEIGHTS_PERCEPTUAL,
+            ColorWeights::Uniform => texpresso::COLOUR_WEIGHTS_UNIFORM,
+        }
+    }
+}
+
+impl TextureFormat {
+    /// Parse a format name as passed from GDScript ("bc1", "bc3"/"dxt5", ...).
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "bc1" | "dxt1" => Ok(TextureFormat::Bc1),
+            "" | "bc3" | "dxt5" => Ok(TextureFormat::Bc3),
+            "bc4" => Ok(TextureFormat::Bc4),
+            "bc5" => Ok(TextureFormat::Bc5),
+            "bc7" => Ok(TextureFormat::Bc7),
+            "rgba8" => Ok(TextureFormat::Rgba8),
+            "rgba16f" => Ok(TextureFormat::Rgba16F),
+            other => Err(format!(
+                "Unknown texture format '{}' (expected bc1, bc3, bc4, bc5, bc7, rgba8 or rgba16f)",
+                other
+            )),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            TextureFormat::Bc1 => "BC1",
+            TextureFormat::Bc3 => "BC3",
+            TextureFormat::Bc4 => "BC4",
+            TextureFormat::Bc5 => "BC5",
+            TextureFormat::Bc7 => "BC7",
+            TextureFormat::Rgba8 => "RGBA8",
+            TextureFormat::Rgba16F => "RGBA16F",
+        }
+    }
+
+    /// Whether the format is block compressed (4x4 blocks)
+    pub fn is_compressed(self) -> bool {
+        !matches!(self, TextureFormat::Rgba8 | TextureFormat::Rgba16F)
+    }
+
+    /// Bytes per 4x4 block for compressed formats, bytes per pixel otherwise
+    pub fn block_size(self) -> usize {
+        match self {
+            TextureFormat::Bc1 | TextureFormat::Bc4 => 8,
+            TextureFormat::Bc3 | TextureFormat::Bc5 | TextureFormat::Bc7 => 16,
+            TextureFormat::Rgba8 => 4,
+            TextureFormat::Rgba16F => 8,
+        }
+    }
+
+    /// Whether the format keeps the alpha channel
+    pub fn has_alpha(self) -> bool {
+        matches!(
+            self,
+            TextureFormat::Bc3 | TextureFormat::Bc7 | TextureFormat::Rgba8 | TextureFormat::Rgba16F
+        )
+    }
+
+    /// Legacy FourCC code, or `None` if the format has none
+    /// (RGBA8 uses bit masks instead, BC7 needs a DX10 header)
+    fn four_cc(self) -> Option<[u8; 4]> {
+        match self {
+            TextureFormat::Bc1 => Some(*b"DXT1"),
+            TextureFormat::Bc3 => Some(*b"DXT5"),
+            TextureFormat::Bc4 => Some(*b"ATI1"),
+            TextureFormat::Bc5 => Some(*b"ATI2"),
+            TextureFormat::Bc7 | TextureFormat::Rgba8 => None,
+            TextureFormat::Rgba16F => Some(113u32.to_le_bytes()), // D3DFMT_A16B16G16R16F
+        }
+    }
+
+    /// DXGI_FORMAT value used in the DX10 header.
+    /// Formats without an sRGB variant (BC4, BC5, float) always use the linear one.
+    fn dxgi_format(self, srgb: bool) -> u32 {
+        match (self, srgb) {
+            (TextureFormat::Bc1, false) => 71, // DXGI_FORMAT_BC1_UNORM
+            (TextureFormat::Bc1, true) => 72,  // DXGI_FORMAT_BC1_UNORM_SRGB
+            (TextureFormat::Bc3, false) => 77, // DXGI_FORMAT_BC3_UNORM
+            (TextureFormat::Bc3, true) => 78,  // DXGI_FORMAT_BC3_UNORM_SRGB
+            (TextureFormat::Bc4, _) => 80,     // DXGI_FORMAT_BC4_UNORM
+            (TextureFormat::Bc5, _) => 83,     // DXGI_FORMAT_BC5_UNORM
+            (TextureFormat::Bc7, false) => 98, // DXGI_FORMAT_BC7_UNORM
+            (TextureFormat::Bc7, true) => 99,  // DXGI_FORMAT_BC7_UNORM_SRGB
+            (TextureFormat::Rgba8, false) => 28, // DXGI_FORMAT_R8G8B8A8_UNORM
+            (TextureFormat::Rgba8, true) => 29,  // DXGI_FORMAT_R8G8B8A8_UNORM_SRGB
+            (TextureFormat::Rgba16F, _) => 10,   // DXGI_FORMAT_R16G16B16A16_FLOAT
+        }
+    }
+
+    /// Size in bytes of one level (compressed levels are padded to whole blocks)
+    fn level_size(self, width: u32, height: u32) -> usize {
+        if self.is_compressed() {
+            (width as usize).div_ceil(4) * (height as usize).div_ceil(4) * self.block_size()
+        } else {
+            width as usize * height as usize * self.block_size()
+        }
+    }
+
+    /// Encode one RGBA8 level (compressed levels below 4x4 still occupy one block).
+    /// Block formats are compressed one row of blocks per rayon task.
+    fn encode(self, image: &RgbaImage, options: &TextureOptions) -> Vec<u8> {
+        let (width, height) = (image.width() as usize, image.height() as usize);
+        let level_buffer = || vec![0u8; self.level_size(image.width(), image.height())];
+
+        let texpresso_format = match self {
+            TextureFormat::Bc1 => texpresso::Format::Bc1,
+            TextureFormat::Bc3 => texpresso::Format::Bc3,
+            TextureFormat::Bc4 => texpresso::Format::Bc4,
+            TextureFormat::Bc5 => texpresso::Format::Bc5,
+            TextureFormat::Bc7 => {
+                let [r, g, b] = options.weights.rgb();
+                let params = Bc7Params {
+                    refine_passes: options.quality.refine_passes(),
+                    weights: [r, g, b, 1.0],
+                };
+                let mut output = level_buffer();
+                Bc7Encoder::compress(image.as_raw(), width, height, params, &mut output);
+                return output;
+            }
+            TextureFormat::Rgba8 => return image.as_raw().clone(),
+            TextureFormat::Rgba16F => {
+                // Float formats are linear, so sRGB colour is decoded first
+                let mut output = level_buffer();
+                for (pixel, out) in image.pixels().zip(output.chunks_exact_mut(8)) {
+                    for c in 0..4 {
+                        let mut value = pixel[c] as f32 / 255.0;
+                        if c < 3 && options.content == TextureContent::Color {
+                            value = srgb_to_linear(value);
+                        }
+                        out[c * 2..c * 2 + 2].copy_from_slice(&f16::from_f32(value).to_le_bytes());
+                    }
+                }
+                return output;
+            }
+        };
+
+        let params = texpresso::Params {
+            algorithm: options.quality.algorithm(),
+            weights: options.weights.rgb(),
+            // Alpha is unrelated data (roughness, height) in normal maps
+            weigh_colour_by_alpha: self == TextureFormat::Bc3 && options.content != TextureContent::Normal,
+        };
+        let block_size = self.block_size();
+        let rgba = image.as_raw();
+
+        // BC1 would turn texels with alpha below 128 into punch-through black,
+        // but packed alpha is height/roughness data, not coverage
+        let opaque;
+        let rgba = if self == TextureFormat::Bc1 {
+            let mut copy = rgba.clone();
+            for pixel in copy.chunks_exact_mut(4) {
+                pixel[3] = 255;
+            }
+            opaque = copy;
+            &opaque
+        } else {
+            rgba
+        };
+
+        let mut output = level_buffer();
+        output
+            .par_chunks_mut(width.div_ceil(4) * block_size)
+            .enumerate()
+            .for_each(|(by, row)| {
+                for (bx, block) in row.chunks_mut(block_size).enumerate() {
+                    // Pixels outside the image are masked out of the fit
+                    let mut pixels = [[0u8; 4]; 16];
+                    let mut mask = 0u32;
+                    for (i, pixel) in pixels.iter_mut().enumerate() {
+                        let x = bx * 4 + i % 4;
+                        let y = by * 4 + i / 4;
+                        if x < width && y < height {
+                            let offset = 4 * (y * width + x);
+                            pixel.copy_from_slice(&rgba[offset..offset + 4]);
+                            mask |= 1 << i;
+                        }
+                    }
+                    texpresso_format.compress_block_masked(pixels, mask, params, block);
+                }
+            });
+        output
+    }
+
+    /// Decode one level back to RGBA8 (used to measure compression error).
+    fn decode(self, data: &[u8], width: u32, height: u32) -> RgbaImage {
+        let (w, h) = (width as usize, height as usize);
+        let mut rgba = vec![0u8; w * h * 4];
+
+        match self {
+            TextureFormat::Bc1 => texpresso::Format::Bc1.decompress(data, w, h, &mut rgba),
+            TextureFormat::Bc3 => texpresso::Format::Bc3.decompress(data, w, h, &mut rgba),
+            TextureFormat::Bc4 => texpresso::Format::Bc4.decompress(data, w, h, &mut rgba),
+            TextureFormat::Bc5 => texpresso::Format::Bc5.decompress(data, w, h, &mut rgba),
+            TextureFormat::Bc7 => Bc7Decoder::decompress(data, w, h, &mut rgba),
+            TextureFormat::Rgba8 => {
+                let length = rgba.len();
+                rgba.copy_from_slice(&data[..length]);
+            }
+            TextureFormat::Rgba16F => {
+                for (out, half) in rgba.iter_mut().zip(data.chunks_exact(2)) {
+                    let value = f16::from_le_bytes([half[0], half[1]]).to_f32();
+                    *out = (value.clamp(0.0, 1.0) * 255.0 + 0.5) as u8;
+                }
+            }
+        }
+
+        RgbaImage::from_raw(width, height, rgba).expect("buffer matches dimensions")
+    }
+}
+
+/// How input sizes are reconciled when packing.
+/// The first input defines the reference size; every input ends up at the same target size.
+/// Inputs of different sizes are an error unless the policy is `MatchFirst`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResizePolicy {
+    /// Keep the size and wrap-pad up to the next multiple of 4 for block compression
+    PadToBlock,
+    /// Resize every input to the first one's size, then pad like `PadToBlock`
+    MatchFirst,
+    /// Resize to the nearest power of two in each dimension
+    PowerOfTwo,
+    /// Downscale (keeping the aspect ratio) so neither side exceeds the given size
+    FitMax(u32),
+    /// Fail on mismatched input sizes or dimensions that cannot be block compressed
+    Error,
+}
+
+impl ResizePolicy {
+    /// Parse a policy name as passed from GDScript ("pad", "match", "pot", "fit" or "error").
+    /// `max_resolution` is only used by "fit".
+    pub fn from_name(name: &str, max_resolution: u32) -> Result<Self, String> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "" | "pad" => Ok(ResizePolicy::PadToBlock),
+            "match" | "match_first" => Ok(ResizePolicy::MatchFirst),
+            "pot" | "power_of_two" => Ok(ResizePolicy::PowerOfTwo),
+            "fit" => {
+                if max_resolution < 4 {
+                    return Err(format!("Max resolution {} is too small", max_resolution));
+                }
+                Ok(ResizePolicy::FitMax(max_resolution))
+            }
+            "error" => Ok(ResizePolicy::Error),
+            other => Err(format!(
+                "Unknown resize policy '{}' (expected pad, match, pot, fit or error)",
+                other
+            )),
+        }
+    }
+
+    pub fn name(self) -> String {
+        match self {
+            ResizePolicy::PadToBlock => "pad".to_string(),
+            ResizePolicy::MatchFirst => "match".to_string(),
+            ResizePolicy::PowerOfTwo => "pot".to_string(),
+            ResizePolicy::FitMax(max) => format!("fit {}", max),
+            ResizePolicy::Error => "error".to_string(),
+        }
+    }
+
+    /// Output size for a `width` x `height` reference image.
+    /// With `block_aligned` the result is always a multiple of 4.
+    pub fn target_size(self, width: u32, height: u32, block_aligned: bool) -> Result<(u32, u32), String> {
+        let align = |v: u32| if block_aligned { v.div_ceil(4) * 4 } else { v };
+
+        match self {
+            ResizePolicy::PadToBlock | ResizePolicy::MatchFirst => Ok((align(width), align(height))),
+            ResizePolicy::PowerOfTwo => {
+                let nearest = |v: u32| {
+                    let pot = 1u32 << (v.max(1) as f32).log2().round() as u32;
+                    if block_aligned {
+                        pot.max(4)
+                    } else {
+                        pot
+                    }
+                };
+                Ok((nearest(width), nearest(height)))
+            }
+            ResizePolicy::FitMax(max) => {
+                let scale = (max as f32 / width.max(height) as f32).min(1.0);
+                let fit = |v: u32| {
+                    let v = ((v as f32 * scale).round() as u32).max(1);
+                    // Round down so the aligned size still fits
+                    if block_aligned {
+                        (v / 4 * 4).max(4)
+                    } else {
+                        v
+                    }
+                };
+                Ok((fit(width), fit(height)))
+            }
+            ResizePolicy::Error => {
+                if block_aligned && !(width.is_multiple_of(4) && height.is_multiple_of(4)) {
+                    return Err(format!(
+                        "Image dimensions {}x{} must be multiples of 4 for block compression",
+                        width, height
+                    ));
+                }
+                Ok((width, height))
+            }
+        }
+    }
+}
+
+/// How tangent-space normals are stored when `content` is `TextureContent::Normal`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NormalEncoding {
+    /// Encode the RGB values as they are
+    Raw,
+    /// Renormalize every texel to unit length before encoding
+    Renormalized,
+    /// Renormalized, X in R and Y in G with B cleared (the layout BC5 stores at full
+    /// precision); the shader reconstructs Z = sqrt(1 - x² - y²)
+    Xy,
+}
+
+impl NormalEncoding {
+    /// Parse an encoding name as passed from GDScript ("raw", "renormalize" or "xy").
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "" | "raw" | "xyz" => Ok(NormalEncoding::Raw),
+            "renormalize" | "renormalized" => Ok(NormalEncoding::Renormalized),
+            "xy" => Ok(NormalEncoding::Xy),
+            other => Err(format!(
+                "Unknown normal encoding '{}' (expected raw, renormalize or xy)",
+                other
+            )),
+        }
+    }
+}
+
+/// Angle between source and compressed normals of the top mip level, in degrees.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AngularError {
+    pub mean_degrees: f32,
+    pub max_degrees: f32,
+}
+
+/// What `save_as_dds` / `save_as_ktx2` actually wrote.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EncodeReport {
+    pub width: u32,
+    pub height: u32,
+    /// Compression error for normal maps (`TextureContent::Normal` only)
+    pub normal_error: Option<AngularError>,
+}
+
+/// Mip levels produced by `ChannelPacker::encode_levels`, largest first.
+struct EncodedTexture {
+    width: u32,
+    height: u32,
+    levels: Vec<Vec<u8>>,
+    normal_error: Option<AngularError>,
+}
+
+/// Options for DDS and KTX2 output.
+pub struct TextureOptions {
+    /// File container
+    pub container: Container,
+    /// Block compression format
+    pub format: TextureFormat,
+    /// Filter used to build the mip chain (`None` writes the top level only)
+    pub mip_filter: Option<MipFilter>,
+    /// How RGB is filtered when building mips (sRGB albedo, normals or linear data)
+    pub content: TextureContent,
+    /// Colour space tag; anything but `Unspecified` forces a DX10 header
+    pub color_space: ColorSpace,
+    /// Applied to the packed inputs (and to the image itself if its size cannot be encoded)
+    pub resize: ResizePolicy,
+    /// Block fitting effort
+    pub quality: CompressionQuality,
+    /// Channel weighting for block fitting
+    pub weights: ColorWeights,
+    /// Normal layout (ignored unless `content` is `TextureContent::Normal`)
+    pub normal_encoding: NormalEncoding,
+}
+
+impl Default for TextureOptions {
+    fn default() -> Self {
+        Self {
+            container: Container::Dds,
+            format: TextureFormat::Bc3,
+            mip_filter: Some(MipFilter::Kaiser),
+            content: TextureContent::Linear,
+            color_space: ColorSpace::Unspecified,
+            resize: ResizePolicy::Error,
+            quality: CompressionQuality::Normal,
+            weights: ColorWeights::Perceptual,
+            normal_encoding: NormalEncoding::Raw,
+        }
+    }
+}
+
+/// Where one output channel of a packed texture comes from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ChannelSource {
+    /// Channel `channel` (0-3 = R, G, B, A) of input `input`, optionally inverted (1 - x)
+    Input {
+        input: usize,
+        channel: usize,
+        invert: bool,
+    },
+    /// Fixed value in [0, 1]
+    Constant(f32),
+}
+
+impl ChannelSource {
+    /// Parse a source expression such as `"albedo.r"`, `"1-roughness.r"` or `"0.5"`.
+    /// `inputs` lists the input names in the order they are passed to `pack_channels`.
+    pub fn parse(expression: &str, inputs: &[String]) -> Result<Self, String> {
+        let expression = expression.trim();
+
+        if let Ok(value) = expression.parse::<f32>() {
+            if !(0.0..=1.0).contains(&value) {
+                return Err(format!("Constant {} is outside the range 0-1", value));
+            }
+            return Ok(ChannelSource::Constant(value));
+        }
+
+        let (invert, reference) = match expression.strip_prefix("1-") {
+            Some(rest) => (true, rest.trim()),
+            None => (false, expression),
+        };
+
+        let (name, channel) = reference
+            .rsplit_once('.')
+            .ok_or_else(|| format!("Expected 'input.channel' in '{}'", expression))?;
+        let channel = match channel.to_ascii_lowercase().as_str() {
+            "r" => 0,
+            "g" => 1,
+            "b" => 2,
+            "a" => 3,
+            other => return Err(format!("Unknown channel '{}' (expected r, g, b or a)", other)),
+        };
+        let input = inputs
+            .iter()
+            .position(|n| n == name)
+            .ok_or_else(|| format!("Unknown input '{}' in '{}'", name, expression))?;
+
+        Ok(ChannelSource::Input { input, channel, invert })
+    }
+}
+
+/// Swizzle mapping for the R, G, B and A channels of a packed texture.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ChannelMapping {
+    pub channels: [ChannelSource; 4],
+}
+
 /// Channel Packer for Terrain3D (v0.0.2)
-/// Packs RGBA textures and saves them in DDS (BC3/DXT5) format.
+/// Packs RGBA textures and saves them in DDS (BC1/BC3/BC4/BC5/BC7 or uncompressed) format.
 pub struct ChannelPacker;
 
 impl ChannelPacker {
+    /// Bring all `inputs` to the size `policy` picks for the first one.
+    /// Inputs that differ from the first are an error, unless the policy is `MatchFirst`,
+    /// which resizes them to the first one (see `mismatched_inputs`).
+    pub fn conform_inputs(
+        inputs: &[&DynamicImage],
+        policy: ResizePolicy,
+        block_aligned: bool,
+    ) -> Result<Vec<DynamicImage>, String> {
+        let first = inputs.first().ok_or("No input images to pack")?;
+        let (width, height) = first.dimensions();
+
+        if policy != ResizePolicy::MatchFirst {
+            if let Some(&index) = Self::mismatched_inputs(inputs).first() {
+                let (w, h) = inputs[index].dimensions();
+                return Err(format!(
+                    "Input sizes differ ({}x{} vs {}x{}); use the match resize policy to resize them to the first input",
+                    width, height, w, h
+                ));
+            }
+        }
+
+        let (target_width, target_height) = policy.target_size(width, height, block_aligned)?;
+
+        Ok(inputs
+            .iter()
+            .map(|input| {
+                let matched = if input.dimensions() != (width, height) {
+                    input.resize_exact(width, height, image::imageops::FilterType::Lanczos3)
+                } else {
+                    (*input).clone()
+                };
+
+                if (target_width, target_height) == (width, height) {
+                    matched
+                } else if matches!(policy, ResizePolicy::PadToBlock | ResizePolicy::MatchFirst) {
+                    Self::wrap_pad(&matched, target_width, target_height)
+                } else {
+                    matched.resize_exact(
+                        target_width,
+                        target_height,
+                        image::imageops::FilterType::Lanczos3,
+                    )
+                }
+            })
+            .collect())
+    }
+
+    /// Indices of the inputs whose size differs from the first one.
+    pub fn mismatched_inputs(inputs: &[&DynamicImage]) -> Vec<usize> {
+        let Some(first) = inputs.first() else {
+            return Vec::new();
+        };
+        inputs
+            .iter()
+            .enumerate()
+            .filter(|(_, input)| input.dimensions() != first.dimensions())
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// Pad by repeating the image, so tiling textures keep tiling seamlessly.
+    fn wrap_pad(image: &DynamicImage, width: u32, height: u32) -> DynamicImage {
+        let source = image.to_rgba32f();
+        let (source_width, source_height) = source.dimensions();
+        let padded = image::Rgba32FImage::from_fn(width, height, |x, y| {
+            *source.get_pixel(x % source_width, y % source_height)
+        });
+
+        // Keep 8-bit inputs 8-bit
+        match image {
+            DynamicImage::ImageRgba32F(_) | DynamicImage::ImageRgb32F(_) => DynamicImage::ImageRgba32F(padded),
+            DynamicImage::ImageLuma16(_)
+            | DynamicImage::ImageLumaA16(_)
+            | DynamicImage::ImageRgb16(_)
+            | DynamicImage::ImageRgba16(_) => DynamicImage::ImageRgba16(DynamicImage::ImageRgba32F(padded).to_rgba16()),
+            _ => DynamicImage::ImageRgba8(DynamicImage::ImageRgba32F(padded).to_rgba8()),
+        }
+    }
+
     /// Pack RGB from `rgb_source` and Alpha from `alpha_source` into a single RGBA image.
-    /// If dimensions differ, `alpha_source` will be resized to match `rgb_source`.
+    /// Both must have the same size (see `conform_inputs`).
     pub fn pack_rgba(
         rgb_source: &DynamicImage,
         alpha_source: &DynamicImage,
-    ) -> RgbaImage {
+    ) -> Result<RgbaImage, String> {
         let (width, height) = rgb_source.dimensions();
-        
-        // Resize alpha source if dimensions don't match
-        let alpha_resized = if alpha_source.dimensions() != (width, height) {
-            alpha_source.resize_exact(width, height, image::imageops::FilterType::Lanczos3)
-        } else {
-            alpha_source.clone()
-        };
+        if alpha_source.dimensions() != (width, height) {
+            let (w, h) = alpha_source.dimensions();
+            return Err(format!(
+                "Input sizes differ ({}x{} vs {}x{})",
+                width, height, w, h
+            ));
+        }
         
         let rgb_rgba = rgb_source.to_rgba8();
-        let alpha_gray = alpha_resized.to_luma8();
+        let alpha_gray = alpha_source.to_luma8();
         
         let mut output = RgbaImage::new(width, height);
         
@@ -35,40 +682,223 @@ impl ChannelPacker {
             }
         }
         
-        output
+        Ok(output)
     }
     
-    /// Compress an RGBA image to BC3/DXT5 format and save as DDS file.
+    /// Build an RGBA image where every channel is taken from `mapping`.
+    /// Inputs are brought to a common size with `resize` (see `conform_inputs`).
+    pub fn pack_channels(
+        inputs: &[DynamicImage],
+        mapping: &ChannelMapping,
+        resize: ResizePolicy,
+        block_aligned: bool,
+    ) -> Result<RgbaImage, String> {
+        let inputs: Vec<&DynamicImage> = inputs.iter().collect();
+        let sources: Vec<RgbaImage> = Self::conform_inputs(&inputs, resize, block_aligned)?
+            .iter()
+            .map(|input| input.to_rgba8())
+            .collect();
+        let (width, height) = sources[0].dimensions();
+
+        for source in &mapping.channels {
+            if let ChannelSource::Input { input, .. } = *source {
+                if input >= sources.len() {
+                    return Err(format!("Mapping refers to missing input {}", input));
+                }
+            }
+        }
+
+        let mut output = RgbaImage::new(width, height);
+        for (x, y, pixel) in output.enumerate_pixels_mut() {
+            for (c, source) in mapping.channels.iter().enumerate() {
+                pixel[c] = match *source {
+                    ChannelSource::Input { input, channel, invert } => {
+                        let value = sources[input].get_pixel(x, y)[channel];
+                        if invert {
+                            255 - value
+                        } else {
+                            value
+                        }
+                    }
+                    ChannelSource::Constant(value) => (value * 255.0 + 0.5) as u8,
+                };
+            }
+        }
+
+        Ok(output)
+    }
+
+    /// Split a packed RGBA image back into its RGB and alpha parts.
+    pub fn unpack_rgba(image: &RgbaImage) -> (RgbImage, GrayImage) {
+        let (width, height) = image.dimensions();
+        let mut rgb = RgbImage::new(width, height);
+        let mut alpha = GrayImage::new(width, height);
+
+        for (x, y, pixel) in image.enumerate_pixels() {
+            rgb.put_pixel(x, y, Rgb([pixel[0], pixel[1], pixel[2]]));
+            alpha.put_pixel(x, y, Luma([pixel[3]]));
+        }
+
+        (rgb, alpha)
+    }
+
+    /// Conform, mip and encode an RGBA image with `options.format`.
+    /// Shared by the DDS and KTX2 writers.
+    fn encode_levels(image: &RgbaImage, options: &TextureOptions) -> Result<EncodedTexture, String> {
+        let format = options.format;
+
+        // Block compression requires dimensions to be multiples of 4
+        let (width, height) = image.dimensions();
+        let (target_width, target_height) = options
+            .resize
+            .target_size(width, height, format.is_compressed())
+            .map_err(|e| format!("{} ({})", e, format.name()))?;
+        let conformed;
+        let image = if (target_width, target_height) != (width, height) {
+            let source = DynamicImage::ImageRgba8(image.clone());
+            conformed = Self::conform_inputs(&[&source], options.resize, format.is_compressed())?
+                .remove(0)
+                .to_rgba8();
+            &conformed
+        } else {
+            image
+        };
+        let (width, height) = image.dimensions();
+
+        let is_normal = options.content == TextureContent::Normal;
+        let renormalized;
+        let image = if is_normal && options.normal_encoding != NormalEncoding::Raw {
+            renormalized = Self::renormalize(image);
+            &renormalized
+        } else {
+            image
+        };
+
+        let mut levels = match options.mip_filter {
+            Some(filter) => MipmapGenerator::generate_chain(image, filter, options.content),
+            None => vec![image.clone()],
+        };
+        if is_normal && options.normal_encoding == NormalEncoding::Xy {
+            // Z is dropped after filtering so every level is built from full vectors
+            for level in &mut levels {
+                for pixel in level.pixels_mut() {
+                    pixel[2] = 0;
+                }
+            }
+        }
+
+        let encoded: Vec<Vec<u8>> = levels.iter().map(|level| format.encode(level, options)).collect();
+
+        let normal_error = is_normal.then(|| {
+            // BC5 only keeps RG, so the shader has to reconstruct Z as well
+            let reconstruct_z = options.normal_encoding == NormalEncoding::Xy || format == TextureFormat::Bc5;
+            let decoded = format.decode(&encoded[0], width, height);
+            Self::angular_error(image, &decoded, reconstruct_z)
+        });
+
+        Ok(EncodedTexture {
+            width,
+            height,
+            levels: encoded,
+            normal_error,
+        })
+    }
+
+    /// Encode an RGBA image with `options.format` and save as DDS file.
+    /// A full mip chain is generated when `options.mip_filter` is set.
     pub fn save_as_dds(
         image: &RgbaImage,
         output_path: &Path,
-    ) -> Result<(), String> {
-        let (width, height) = image.dimensions();
-        
-        // BC3 requires dimensions to be multiples of 4
-        if width % 4 != 0 || height % 4 != 0 {
-            return Err(format!(
-                "Image dimensions {}x{} must be multiples of 4 for BC3 compression",
-                width, height
-            ));
+        options: &TextureOptions,
+    ) -> Result<EncodeReport, String> {
+        let EncodedTexture {
+            width,
+            height,
+            levels,
+            normal_error,
+        } = Self::encode_levels(image, options)?;
+        Self::write_dds(output_path, options, width, height, &[levels])?;
+
+        Ok(EncodeReport {
+            width,
+            height,
+            normal_error,
+        })
+    }
+
+    /// Encode every layer with the same `options` and save them as one texture array
+    /// in `options.container`.
+    /// All layers must have the same size; the resize policy is not applied across layers.
+    /// The report holds the worst normal error of all layers.
+    pub fn save_texture_array(
+        layers: &[RgbaImage],
+        output_path: &Path,
+        options: &TextureOptions,
+    ) -> Result<EncodeReport, String> {
+        let first = layers.first().ok_or("No layers for the texture array")?;
+        for (index, layer) in layers.iter().enumerate().skip(1) {
+            if layer.dimensions() != first.dimensions() {
+                return Err(format!(
+                    "Array layer {} is {}x{}, expected {}x{}",
+                    index,
+                    layer.width(),
+                    layer.height(),
+                    first.width(),
+                    first.height()
+                ));
+            }
         }
-        
-        // Compress to BC3/DXT5
-        let raw_pixels = image.as_raw();
-        let block_count = ((width / 4) * (height / 4)) as usize;
-        let mut compressed = vec![0u8; block_count * 16]; // BC3 is 16 bytes per 4x4 block
-        
-        texpresso::Format::Bc3.compress(
-            raw_pixels,
-            width as usize,
-            height as usize,
-            texpresso::Params {
-                algorithm: texpresso::Algorithm::ClusterFit,
-                weights: texpresso::COLOUR_WEIGHTS_PERCEPTUAL,
-                weigh_colour_by_alpha: true,
-            },
-            &mut compressed,
-        );
+
+        let encoded = layers
+            .par_iter()
+            .map(|layer| Self::encode_levels(layer, options))
+            .collect::<Result<Vec<_>, String>>()?;
+        let (width, height) = (encoded[0].width, encoded[0].height);
+
+        let normal_error = encoded
+            .iter()
+            .filter_map(|layer| layer.normal_error)
+            .reduce(|a, b| AngularError {
+                mean_degrees: a.mean_degrees.max(b.mean_degrees),
+                max_degrees: a.max_degrees.max(b.max_degrees),
+            });
+
+        let layers: Vec<Vec<Vec<u8>>> = encoded.into_iter().map(|layer| layer.levels).collect();
+        match options.container {
+            Container::Dds => Self::write_dds(output_path, options, width, height, &layers)?,
+            Container::Ktx2 => Ktx2Writer::write(
+                output_path,
+                options.format,
+                options.color_space == ColorSpace::Srgb,
+                width,
+                height,
+                &layers,
+            )?,
+        }
+
+        Ok(EncodeReport {
+            width,
+            height,
+            normal_error,
+        })
+    }
+
+    /// Write encoded layers (each a mip chain, largest level first) as a DDS file.
+    /// More than one layer produces a texture array.
+    fn write_dds(
+        output_path: &Path,
+        options: &TextureOptions,
+        width: u32,
+        height: u32,
+        layers: &[Vec<Vec<u8>>],
+    ) -> Result<(), String> {
+        let format = options.format;
+        let levels = &layers[0];
+
+        // Colour space tags, BC7 and arrays are only expressible with the DX10 extension
+        let use_dx10 = options.color_space != ColorSpace::Unspecified
+            || format == TextureFormat::Bc7
+            || layers.len() > 1;
         
         // Create DDS header manually
         let mut dds_data = Vec::new();
@@ -78,10 +908,22 @@ impl ChannelPacker {
         
         // DDS_HEADER (124 bytes)
         let header_size: u32 = 124;
-        let flags: u32 = 0x1 | 0x2 | 0x4 | 0x1000 | 0x80000; // CAPS | HEIGHT | WIDTH | PIXELFORMAT | LINEARSIZE
-        let pitch_or_linear_size: u32 = (block_count as u32) * 16;
+        let mut flags: u32 = 0x1 | 0x2 | 0x4 | 0x1000; // CAPS | HEIGHT | WIDTH | PIXELFORMAT
+        if format.is_compressed() {
+            flags |= 0x80000; // DDSD_LINEARSIZE
+        } else {
+            flags |= 0x8; // DDSD_PITCH
+        }
+        if levels.len() > 1 {
+            flags |= 0x20000; // DDSD_MIPMAPCOUNT
+        }
+        let pitch_or_linear_size: u32 = if format.is_compressed() {
+            format.level_size(width, height) as u32
+        } else {
+            width * format.block_size() as u32
+        };
         let depth: u32 = 0;
-        let mipmap_count: u32 = 1;
+        let mipmap_count: u32 = levels.len() as u32;
         
         dds_data.extend_from_slice(&header_size.to_le_bytes());
         dds_data.extend_from_slice(&flags.to_le_bytes());
@@ -98,13 +940,29 @@ impl ChannelPacker {
         
         // DDS_PIXELFORMAT (32 bytes)
         let pf_size: u32 = 32;
-        let pf_flags: u32 = 0x4; // DDPF_FOURCC
-        let four_cc: [u8; 4] = *b"DXT5";
-        let rgb_bit_count: u32 = 0;
-        let r_bitmask: u32 = 0;
-        let g_bitmask: u32 = 0;
-        let b_bitmask: u32 = 0;
-        let a_bitmask: u32 = 0;
+        let mut pf_flags: u32 = 0x4; // DDPF_FOURCC
+        let mut four_cc: [u8; 4] = *b"DX10";
+        let mut rgb_bit_count: u32 = 0;
+        let mut r_bitmask: u32 = 0;
+        let mut g_bitmask: u32 = 0;
+        let mut b_bitmask: u32 = 0;
+        let mut a_bitmask: u32 = 0;
+        
+        if !use_dx10 {
+            match format.four_cc() {
+                Some(code) => four_cc = code,
+                None => {
+                    // Uncompressed RGBA8 is described by bit masks
+                    pf_flags = 0x40 | 0x1; // DDPF_RGB | DDPF_ALPHAPIXELS
+                    four_cc = [0; 4];
+                    rgb_bit_count = 32;
+                    r_bitmask = 0x0000_00ff;
+                    g_bitmask = 0x0000_ff00;
+                    b_bitmask = 0x00ff_0000;
+                    a_bitmask = 0xff00_0000;
+                }
+            }
+        }
         
         dds_data.extend_from_slice(&pf_size.to_le_bytes());
         dds_data.extend_from_slice(&pf_flags.to_le_bytes());
@@ -116,7 +974,10 @@ impl ChannelPacker {
         dds_data.extend_from_slice(&a_bitmask.to_le_bytes());
         
         // Caps
-        let caps: u32 = 0x1000; // DDSCAPS_TEXTURE
+        let mut caps: u32 = 0x1000; // DDSCAPS_TEXTURE
+        if levels.len() > 1 {
+            caps |= 0x8 | 0x400000; // DDSCAPS_COMPLEX | DDSCAPS_MIPMAP
+        }
         let caps2: u32 = 0;
         let caps3: u32 = 0;
         let caps4: u32 = 0;
@@ -128,8 +989,26 @@ impl ChannelPacker {
         dds_data.extend_from_slice(&caps4.to_le_bytes());
         dds_data.extend_from_slice(&reserved2.to_le_bytes());
         
-        // Append compressed data
-        dds_data.extend_from_slice(&compressed);
+        // DDS_HEADER_DXT10 (20 bytes)
+        if use_dx10 {
+            let resource_dimension: u32 = 3; // D3D10_RESOURCE_DIMENSION_TEXTURE2D
+            let misc_flag: u32 = 0;
+            let array_size: u32 = layers.len() as u32;
+            let misc_flags2: u32 = 0; // DDS_ALPHA_MODE_UNKNOWN
+            
+            let dxgi_format = format.dxgi_format(options.color_space == ColorSpace::Srgb);
+            
+            dds_data.extend_from_slice(&dxgi_format.to_le_bytes());
+            dds_data.extend_from_slice(&resource_dimension.to_le_bytes());
+            dds_data.extend_from_slice(&misc_flag.to_le_bytes());
+            dds_data.extend_from_slice(&array_size.to_le_bytes());
+            dds_data.extend_from_slice(&misc_flags2.to_le_bytes());
+        }
+        
+        // Append pixel data (every layer's full mip chain in turn)
+        for level in layers.iter().flatten() {
+            dds_data.extend_from_slice(level);
+        }
         
         // Write to file
         let file = std::fs::File::create(output_path)
@@ -140,18 +1019,108 @@ impl ChannelPacker {
             .map_err(|e| format!("Failed to write DDS: {}", e))?;
         
         writer.flush()
-            .map_err(|e| format!("Failed to flush file: {}", e))?;
-        
-        Ok(())
+            .map_err(|e| format!("Failed to flush file: {}", e))
+    }
+
+    /// Encode an RGBA image with `options.format` and save as KTX2 file.
+    /// The colour space is recorded in the Vulkan format and the Data Format Descriptor.
+    pub fn save_as_ktx2(
+        image: &RgbaImage,
+        output_path: &Path,
+        options: &TextureOptions,
+    ) -> Result<EncodeReport, String> {
+        let encoded = Self::encode_levels(image, options)?;
+        Ktx2Writer::write(
+            output_path,
+            options.format,
+            options.color_space == ColorSpace::Srgb,
+            encoded.width,
+            encoded.height,
+            &[encoded.levels],
+        )?;
+
+        Ok(EncodeReport {
+            width: encoded.width,
+            height: encoded.height,
+            normal_error: encoded.normal_error,
+        })
+    }
+
+    /// Save with the writer selected by `options.container`.
+    pub fn save_texture(
+        image: &RgbaImage,
+        output_path: &Path,
+        options: &TextureOptions,
+    ) -> Result<EncodeReport, String> {
+        match options.container {
+            Container::Dds => Self::save_as_dds(image, output_path, options),
+            Container::Ktx2 => Self::save_as_ktx2(image, output_path, options),
+        }
+    }
+
+    /// Scale every texel's RGB vector to unit length (alpha untouched).
+    fn renormalize(image: &RgbaImage) -> RgbaImage {
+        let mut output = image.clone();
+        for pixel in output.pixels_mut() {
+            let [x, y, z] = Self::unpack_normal(pixel, false);
+            pixel[0] = ((x * 0.5 + 0.5) * 255.0 + 0.5) as u8;
+            pixel[1] = ((y * 0.5 + 0.5) * 255.0 + 0.5) as u8;
+            pixel[2] = ((z * 0.5 + 0.5) * 255.0 + 0.5) as u8;
+        }
+        output
+    }
+
+    /// Unit normal from an RGB texel, optionally reconstructing Z from XY.
+    fn unpack_normal(pixel: &Rgba<u8>, reconstruct_z: bool) -> [f32; 3] {
+        let x = pixel[0] as f32 / 255.0 * 2.0 - 1.0;
+        let y = pixel[1] as f32 / 255.0 * 2.0 - 1.0;
+        let z = if reconstruct_z {
+            (1.0 - x * x - y * y).max(0.0).sqrt()
+        } else {
+            pixel[2] as f32 / 255.0 * 2.0 - 1.0
+        };
+
+        let length = (x * x + y * y + z * z).sqrt();
+        if length > 1e-6 {
+            [x / length, y / length, z / length]
+        } else {
+            [0.0, 0.0, 1.0]
+        }
+    }
+
+    fn angular_error(source: &RgbaImage, decoded: &RgbaImage, reconstruct_z: bool) -> AngularError {
+        let mut sum = 0.0f64;
+        let mut max = 0.0f32;
+        for (a, b) in source.pixels().zip(decoded.pixels()) {
+            let n0 = Self::unpack_normal(a, false);
+            let n1 = Self::unpack_normal(b, reconstruct_z);
+            let dot = (n0[0] * n1[0] + n0[1] * n1[1] + n0[2] * n1[2]).clamp(-1.0, 1.0);
+            let angle = dot.acos().to_degrees();
+            sum += angle as f64;
+            max = max.max(angle);
+        }
+
+        let count = (source.width() * source.height()).max(1) as f64;
+        AngularError {
+            mean_degrees: (sum / count) as f32,
+            max_degrees: max,
+        }
     }
     
-    /// Convenience method: Pack and save directly to DDS.
-    pub fn pack_and_save_dds(
+    /// Convenience method: Pack and save directly to DDS or KTX2.
+    /// The report holds the size of the written texture after `options.resize` was applied.
+    pub fn pack_and_save(
         rgb_source: &DynamicImage,
         alpha_source: &DynamicImage,
         output_path: &Path,
-    ) -> Result<(), String> {
-        let packed = Self::pack_rgba(rgb_source, alpha_source);
-        Self::save_as_dds(&packed, output_path)
+        options: &TextureOptions,
+    ) -> Result<EncodeReport, String> {
+        let inputs = Self::conform_inputs(
+            &[rgb_source, alpha_source],
+            options.resize,
+            options.format.is_compressed(),
+        )?;
+        let packed = Self::pack_rgba(&inputs[0], &inputs[1])?;
+        Self::save_texture(&packed, output_path, options)
     }
 }
@@ -0,0 +1,291 @@
+use image::{DynamicImage, ImageBuffer, Luma};
+use png::{BitDepth, ColorType, ScaledFloat, SrgbRenderingIntent};
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+use crate::channel_packer::ColorSpace;
+use crate::mipmap::{linear_to_srgb, srgb_to_linear};
+use crate::openexr::GrayF32Image;
+
+/// How the values of a source image are encoded.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TransferFunction {
+    Srgb,
+    Linear,
+    /// Pure power curve with this decoding exponent (e.g. 2.2)
+    Gamma(f32),
+}
+
+impl TransferFunction {
+    /// Parse a transfer function name as passed from GDScript.
+    /// "" or "auto" returns `None` (detect from the file).
+    pub fn from_name(name: &str) -> Result<Option<Self>, String> {
+        let name = name.trim().to_ascii_lowercase();
+        match name.as_str() {
+            "" | "auto" => Ok(None),
+            "srgb" => Ok(Some(TransferFunction::Srgb)),
+            "linear" => Ok(Some(TransferFunction::Linear)),
+            other => other
+                .strip_prefix("gamma")
+                .unwrap_or(other)
+                .trim()
+                .parse::<f32>()
+                .ok()
+                .filter(|gamma| *gamma > 0.0)
+                .map(|gamma| Some(Self::from_exponent(gamma)))
+                .ok_or_else(|| {
+                    format!(
+                        "Unknown colour space '{}' (expected auto, srgb, linear or a gamma such as 2.2)",
+                        other
+                    )
+                }),
+        }
+    }
+
+    /// Detect the encoding from the file: EXR/HDR are linear, PNG sRGB/gAMA/iCCP
+    /// chunks and embedded ICC profiles (JPEG, TIFF, WebP) are honoured, anything
+    /// else is assumed to be sRGB.
+    pub fn detect(path: &Path) -> Self {
+        let extension = path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_ascii_lowercase())
+            .unwrap_or_default();
+
+        let detected = match extension.as_str() {
+            "exr" | "hdr" => Some(TransferFunction::Linear),
+            "png" => Self::from_png(path),
+            "jpg" | "jpeg" | "tif" | "tiff" | "webp" => {
+                Self::embedded_icc_profile(path, &extension).and_then(|icc| Self::from_icc(&icc))
+            }
+            _ => None,
+        };
+        detected.unwrap_or(TransferFunction::Srgb)
+    }
+
+    /// Decode one encoded value (0.0-1.0) to linear light.
+    pub fn to_linear(self, value: f32) -> f32 {
+        match self {
+            TransferFunction::Srgb => srgb_to_linear(value),
+            TransferFunction::Linear => value,
+            TransferFunction::Gamma(gamma) => value.max(0.0).powf(gamma),
+        }
+    }
+
+    /// Encode one linear value back with this transfer function.
+    pub fn encode(self, value: f32) -> f32 {
+        match self {
+            TransferFunction::Srgb => linear_to_srgb(value),
+            TransferFunction::Linear => value,
+            TransferFunction::Gamma(gamma) => value.max(0.0).powf(1.0 / gamma),
+        }
+    }
+
+    /// Name as reported to GDScript
+    pub fn name(self) -> String {
+        match self {
+            TransferFunction::Srgb => "srgb".to_string(),
+            TransferFunction::Linear => "linear".to_string(),
+            TransferFunction::Gamma(gamma) => format!("gamma {:.2}", gamma),
+        }
+    }
+
+    fn from_exponent(gamma: f32) -> Self {
+        if (gamma - 1.0).abs() < 0.05 {
+            TransferFunction::Linear
+        } else {
+            TransferFunction::Gamma(gamma)
+        }
+    }
+
+    /// sRGB chunk, then iCCP, then gAMA (the PNG precedence order)
+    fn from_png(path: &Path) -> Option<Self> {
+        let file = File::open(path).ok()?;
+        let reader = png::Decoder::new(BufReader::new(file)).read_info().ok()?;
+        let info = reader.info();
+
+        if info.srgb.is_some() {
+            return Some(TransferFunction::Srgb);
+        }
+        if let Some(transfer) = info.icc_profile.as_deref().and_then(Self::from_icc) {
+            return Some(transfer);
+        }
+        // gAMA stores the encoding exponent (0.45455 for a 2.2 curve)
+        info.source_gamma
+            .map(|gamma| gamma.into_value())
+            .filter(|gamma| *gamma > 0.0)
+            .map(|gamma| Self::from_exponent(1.0 / gamma))
+    }
+
+    fn embedded_icc_profile(path: &Path, extension: &str) -> Option<Vec<u8>> {
+        use image::codecs::{jpeg::JpegDecoder, tiff::TiffDecoder, webp::WebPDecoder};
+        use image::ImageDecoder;
+
+        let reader = BufReader::new(File::open(path).ok()?);
+        match extension {
+            "jpg" | "jpeg" => JpegDecoder::new(reader).ok()?.icc_profile(),
+            "tif" | "tiff" => TiffDecoder::new(reader).ok()?.icc_profile(),
+            _ => WebPDecoder::new(reader).ok()?.icc_profile(),
+        }
+    }
+
+    /// Transfer function of the red (or grey) tone curve of an ICC profile
+    fn from_icc(profile: &[u8]) -> Option<Self> {
+        let read_u32 = |offset: usize| {
+            profile
+                .get(offset..offset + 4)
+                .map(|bytes| u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        };
+        let read_u16 = |offset: usize| {
+            profile
+                .get(offset..offset + 2)
+                .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+        };
+
+        let tag_count = read_u32(128)? as usize;
+        let curve = (0..tag_count).find_map(|index| {
+            let entry = 132 + index * 12;
+            let signature = profile.get(entry..entry + 4)?;
+            (signature == b"rTRC" || signature == b"kTRC").then(|| read_u32(entry + 4))?
+        })? as usize;
+
+        match profile.get(curve..curve + 4)? {
+            b"curv" => match read_u32(curve + 8)? {
+                // No entries, or just the two end points: identity
+                0 | 2 => Some(TransferFunction::Linear),
+                1 => Some(Self::from_exponent(read_u16(curve + 12)? as f32 / 256.0)),
+                count => {
+                    // Sampled curve: estimate the exponent at mid-grey
+                    let middle = read_u16(curve + 12 + (count as usize / 2) * 2)? as f32 / 65535.0;
+                    let input = (count / 2) as f32 / (count - 1) as f32;
+                    let gamma = middle.max(1e-6).ln() / input.ln();
+                    Some(if (gamma - 2.2).abs() < 0.15 {
+                        TransferFunction::Srgb
+                    } else {
+                        Self::from_exponent(gamma)
+                    })
+                }
+            },
+            b"para" => {
+                let function = read_u16(curve + 8)?;
+                let gamma = read_u32(curve + 12)? as i32 as f32 / 65536.0;
+                Some(match function {
+                    // sRGB-style curve with a linear toe
+                    3 | 4 if (gamma - 2.4).abs() < 0.05 => TransferFunction::Srgb,
+                    _ => Self::from_exponent(gamma),
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Whether generators work on decoded (linear) values.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorProcessing {
+    /// Decode to linear light before luminance and gradients
+    Linear,
+    /// Weight the encoded values directly (output matches versions before linear processing)
+    Legacy,
+}
+
+impl ColorProcessing {
+    /// Parse a processing mode as passed from GDScript ("" selects linear).
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "" | "linear" => Ok(ColorProcessing::Linear),
+            "legacy" => Ok(ColorProcessing::Legacy),
+            other => Err(format!(
+                "Unknown colour processing '{}' (expected linear or legacy)",
+                other
+            )),
+        }
+    }
+}
+
+/// Source decoding and output tagging
+pub struct ColorManagement;
+
+impl ColorManagement {
+    /// Rec.709 luminance (0.0-1.0 for integer sources).
+    /// `Linear` decodes with `transfer` first; `Legacy` weights the encoded values.
+    pub fn luminance(image: &DynamicImage, transfer: TransferFunction, processing: ColorProcessing) -> GrayF32Image {
+        let rgb = image.to_rgb32f();
+        let decode = |value: f32| match processing {
+            ColorProcessing::Linear => transfer.to_linear(value),
+            ColorProcessing::Legacy => value,
+        };
+
+        ImageBuffer::from_fn(rgb.width(), rgb.height(), |x, y| {
+            let [r, g, b] = rgb.get_pixel(x, y).0;
+            Luma([0.2126 * decode(r) + 0.7152 * decode(g) + 0.0722 * decode(b)])
+        })
+    }
+
+    /// Save as PNG with an sRGB chunk (`Srgb`) or a gAMA 1.0 chunk (`Linear`).
+    /// `Unspecified` writes no colour information. Float images are stored as 16-bit.
+    pub fn save_png(image: &DynamicImage, path: &Path, color_space: ColorSpace) -> Result<(), String> {
+        let sixteen = |samples: &[u16]| samples.iter().flat_map(|v| v.to_be_bytes()).collect::<Vec<u8>>();
+        let (color, depth, data) = match image {
+            DynamicImage::ImageLuma8(i) => (ColorType::Grayscale, BitDepth::Eight, i.as_raw().clone()),
+            DynamicImage::ImageLumaA8(i) => (ColorType::GrayscaleAlpha, BitDepth::Eight, i.as_raw().clone()),
+            DynamicImage::ImageRgb8(i) => (ColorType::Rgb, BitDepth::Eight, i.as_raw().clone()),
+            DynamicImage::ImageRgba8(i) => (ColorType::Rgba, BitDepth::Eight, i.as_raw().clone()),
+            DynamicImage::ImageLuma16(i) => (ColorType::Grayscale, BitDepth::Sixteen, sixteen(i.as_raw())),
+            DynamicImage::ImageLumaA16(i) => (ColorType::GrayscaleAlpha, BitDepth::Sixteen, sixteen(i.as_raw())),
+            DynamicImage::ImageRgb16(i) => (ColorType::Rgb, BitDepth::Sixteen, sixteen(i.as_raw())),
+            DynamicImage::ImageRgba16(i) => (ColorType::Rgba, BitDepth::Sixteen, sixteen(i.as_raw())),
+            other if other.color().has_alpha() => {
+                return Self::save_png(&DynamicImage::ImageRgba16(other.to_rgba16()), path, color_space)
+            }
+            other => return Self::save_png(&DynamicImage::ImageRgb16(other.to_rgb16()), path, color_space),
+        };
+
+        let file = File::create(path).map_err(|e| format!("Failed to create file: {}", e))?;
+        let mut encoder = png::Encoder::new(BufWriter::new(file), image.width(), image.height());
+        encoder.set_color(color);
+        encoder.set_depth(depth);
+        match color_space {
+            ColorSpace::Srgb => encoder.set_source_srgb(SrgbRenderingIntent::Perceptual),
+            ColorSpace::Linear => encoder.set_source_gamma(ScaledFloat::new(1.0)),
+            ColorSpace::Unspecified => {}
+        }
+
+        encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(&data))
+            .map_err(|e| format!("Failed to save {}: {}", path.display(), e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Minimal profile with one rTRC tag pointing at a sampled `curv`.
+    fn profile_with_curve(samples: &[u16]) -> Vec<u8> {
+        let mut profile = vec![0u8; 128];
+        profile.extend_from_slice(&1u32.to_be_bytes());
+        profile.extend_from_slice(b"rTRC");
+        profile.extend_from_slice(&144u32.to_be_bytes());
+        profile.extend_from_slice(&(12 + samples.len() as u32 * 2).to_be_bytes());
+        profile.extend_from_slice(b"curv");
+        profile.extend_from_slice(&[0; 4]);
+        profile.extend_from_slice(&(samples.len() as u32).to_be_bytes());
+        for sample in samples {
+            profile.extend_from_slice(&sample.to_be_bytes());
+        }
+        profile
+    }
+
+    #[test]
+    fn two_point_curve_is_linear() {
+        assert_eq!(TransferFunction::from_icc(&profile_with_curve(&[0, 65535])), Some(TransferFunction::Linear));
+    }
+
+    #[test]
+    fn sampled_curve_estimates_the_exponent() {
+        let samples: Vec<u16> = (0..256).map(|i| ((i as f32 / 255.0).powf(2.2) * 65535.0).round() as u16).collect();
+        assert_eq!(TransferFunction::from_icc(&profile_with_curve(&samples)), Some(TransferFunction::Srgb));
+    }
+}
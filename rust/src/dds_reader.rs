@@ -0,0 +1,340 @@
+use ddsfile::{D3DFormat, Dds, DxgiFormat, PixelFormatFlags};
+use half::f16;
+use image::{DynamicImage, ImageBuffer, RgbaImage};
+use std::path::Path;
+
+use crate::bc7::Bc7Decoder;
+
+/// Pixel layouts the reader can decode
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum SourceFormat {
+    Bc1,
+    Bc2,
+    Bc3,
+    Bc4,
+    Bc5,
+    Bc7,
+    Rgba8,
+    Bgra8,
+    Bgrx8,
+    R8,
+    /// Alpha only; RGB decodes to black
+    A8,
+    Rgba16F,
+    Rgba32F,
+}
+
+impl SourceFormat {
+    fn detect(dds: &Dds) -> Result<Self, String> {
+        if let Some(dxgi) = dds.header10.as_ref().map(|h| h.dxgi_format) {
+            return match dxgi {
+                DxgiFormat::BC1_Typeless | DxgiFormat::BC1_UNorm | DxgiFormat::BC1_UNorm_sRGB => Ok(Self::Bc1),
+                DxgiFormat::BC2_Typeless | DxgiFormat::BC2_UNorm | DxgiFormat::BC2_UNorm_sRGB => Ok(Self::Bc2),
+                DxgiFormat::BC3_Typeless | DxgiFormat::BC3_UNorm | DxgiFormat::BC3_UNorm_sRGB => Ok(Self::Bc3),
+                DxgiFormat::BC4_Typeless | DxgiFormat::BC4_UNorm => Ok(Self::Bc4),
+                DxgiFormat::BC5_Typeless | DxgiFormat::BC5_UNorm => Ok(Self::Bc5),
+                DxgiFormat::BC7_Typeless | DxgiFormat::BC7_UNorm | DxgiFormat::BC7_UNorm_sRGB => Ok(Self::Bc7),
+                DxgiFormat::R8G8B8A8_Typeless | DxgiFormat::R8G8B8A8_UNorm | DxgiFormat::R8G8B8A8_UNorm_sRGB => {
+                    Ok(Self::Rgba8)
+                }
+                DxgiFormat::B8G8R8A8_Typeless | DxgiFormat::B8G8R8A8_UNorm | DxgiFormat::B8G8R8A8_UNorm_sRGB => {
+                    Ok(Self::Bgra8)
+                }
+                DxgiFormat::B8G8R8X8_Typeless | DxgiFormat::B8G8R8X8_UNorm | DxgiFormat::B8G8R8X8_UNorm_sRGB => {
+                    Ok(Self::Bgrx8)
+                }
+                DxgiFormat::R8_UNorm => Ok(Self::R8),
+                DxgiFormat::A8_UNorm => Ok(Self::A8),
+                DxgiFormat::R16G16B16A16_Float => Ok(Self::Rgba16F),
+                DxgiFormat::R32G32B32A32_Float => Ok(Self::Rgba32F),
+                DxgiFormat::BC6H_Typeless | DxgiFormat::BC6H_UF16 | DxgiFormat::BC6H_SF16 => {
+                    Err("BC6H (HDR) DDS files are not supported".to_string())
+                }
+                other => Err(format!("Unsupported DXGI format {:?}", other)),
+            };
+        }
+
+        // Legacy header: ddsfile does not map the BC4U/BC5U FourCC aliases
+        match dds.header.spf.fourcc.as_ref().map(|f| f.0.to_le_bytes()) {
+            Some(code) if &code == b"ATI1" || &code == b"BC4U" => return Ok(Self::Bc4),
+            Some(code) if &code == b"ATI2" || &code == b"BC5U" => return Ok(Self::Bc5),
+            _ => {}
+        }
+
+        // Nor alpha-only A8, whose bit count it only reads for RGB/luminance formats
+        if dds.header.spf.flags == PixelFormatFlags::ALPHA && dds.header.spf.a_bit_mask == Some(0xff) {
+            return Ok(Self::A8);
+        }
+
+        match dds.get_d3d_format() {
+            Some(D3DFormat::DXT1) => Ok(Self::Bc1),
+            Some(D3DFormat::DXT2) | Some(D3DFormat::DXT3) => Ok(Self::Bc2),
+            Some(D3DFormat::DXT4) | Some(D3DFormat::DXT5) => Ok(Self::Bc3),
+            Some(D3DFormat::A8B8G8R8) => Ok(Self::Rgba8),
+            Some(D3DFormat::A8R8G8B8) => Ok(Self::Bgra8),
+            Some(D3DFormat::X8R8G8B8) => Ok(Self::Bgrx8),
+            Some(D3DFormat::L8) => Ok(Self::R8),
+            Some(D3DFormat::A8) => Ok(Self::A8),
+            Some(D3DFormat::A16B16G16R16F) => Ok(Self::Rgba16F),
+            Some(D3DFormat::A32B32G32R32F) => Ok(Self::Rgba32F),
+            Some(other) => Err(format!("Unsupported DDS format {:?}", other)),
+            None => Err("Unrecognised DDS pixel format".to_string()),
+        }
+    }
+
+    fn texpresso_format(self) -> Option<texpresso::Format> {
+        match self {
+            Self::Bc1 => Some(texpresso::Format::Bc1),
+            Self::Bc2 => Some(texpresso::Format::Bc2),
+            Self::Bc3 => Some(texpresso::Format::Bc3),
+            Self::Bc4 => Some(texpresso::Format::Bc4),
+            Self::Bc5 => Some(texpresso::Format::Bc5),
+            _ => None,
+        }
+    }
+
+    /// Bytes occupied by the top mip level
+    fn level_size(self, width: usize, height: usize) -> usize {
+        let blocks = width.div_ceil(4) * height.div_ceil(4);
+        match self {
+            Self::Bc1 | Self::Bc4 => blocks * 8,
+            Self::Bc2 | Self::Bc3 | Self::Bc5 | Self::Bc7 => blocks * 16,
+            Self::R8 | Self::A8 => width * height,
+            Self::Rgba8 | Self::Bgra8 | Self::Bgrx8 => width * height * 4,
+            Self::Rgba16F => width * height * 8,
+            Self::Rgba32F => width * height * 16,
+        }
+    }
+}
+
+/// DDS texture reader
+/// Decodes the top mip level of the first layer of BC1-BC5, BC7 and
+/// uncompressed DDS files. Float formats are returned as `ImageRgba32F`,
+/// everything else as `ImageRgba8`.
+pub struct DdsReader;
+
+impl DdsReader {
+    pub fn load(path: &Path) -> Result<DynamicImage, String> {
+        let file = std::fs::File::open(path)
+            .map_err(|e| format!("Failed to open DDS file: {}", e))?;
+        let dds = Dds::read(std::io::BufReader::new(file))
+            .map_err(|e| format!("Failed to parse DDS file: {}", e))?;
+
+        let format = SourceFormat::detect(&dds)?;
+        let width = dds.get_width() as usize;
+        let height = dds.get_height() as usize;
+        let data = dds
+            .get_data(0)
+            .map_err(|e| format!("Failed to read DDS data: {}", e))?;
+
+        let size = format.level_size(width, height);
+        if data.len() < size {
+            return Err(format!(
+                "DDS data is truncated ({} bytes, expected {})",
+                data.len(),
+                size
+            ));
+        }
+        let data = &data[..size];
+
+        let image = match format {
+            SourceFormat::Rgba16F => {
+                let pixels = data
+                    .chunks_exact(2)
+                    .map(|c| f16::from_le_bytes([c[0], c[1]]).to_f32())
+                    .collect();
+                DynamicImage::ImageRgba32F(Self::float_buffer(width, height, pixels)?)
+            }
+            SourceFormat::Rgba32F => {
+                let pixels = data
+                    .chunks_exact(4)
+                    .map(|c| f32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+                    .collect();
+                DynamicImage::ImageRgba32F(Self::float_buffer(width, height, pixels)?)
+            }
+            _ => DynamicImage::ImageRgba8(Self::decode_rgba8(format, data, width, height)?),
+        };
+
+        Ok(image)
+    }
+
+    fn decode_rgba8(
+        format: SourceFormat,
+        data: &[u8],
+        width: usize,
+        height: usize,
+    ) -> Result<RgbaImage, String> {
+        let mut rgba = vec![0u8; width * height * 4];
+
+        match format {
+            SourceFormat::Bc7 => Bc7Decoder::decompress(data, width, height, &mut rgba),
+            SourceFormat::Rgba8 => rgba.copy_from_slice(data),
+            SourceFormat::Bgra8 | SourceFormat::Bgrx8 => {
+                for (out, bgra) in rgba.chunks_exact_mut(4).zip(data.chunks_exact(4)) {
+                    let alpha = if format == SourceFormat::Bgrx8 { 255 } else { bgra[3] };
+                    out.copy_from_slice(&[bgra[2], bgra[1], bgra[0], alpha]);
+                }
+            }
+            SourceFormat::R8 => {
+                for (out, &value) in rgba.chunks_exact_mut(4).zip(data) {
+                    out.copy_from_slice(&[value, value, value, 255]);
+                }
+            }
+            SourceFormat::A8 => {
+                for (out, &value) in rgba.chunks_exact_mut(4).zip(data) {
+                    out.copy_from_slice(&[0, 0, 0, value]);
+                }
+            }
+            _ => {
+                let bc = format
+                    .texpresso_format()
+                    .ok_or_else(|| format!("No decoder for {:?}", format))?;
+                bc.decompress(data, width, height, &mut rgba);
+            }
+        }
+
+        RgbaImage::from_raw(width as u32, height as u32, rgba)
+            .ok_or_else(|| "Decoded DDS buffer has the wrong size".to_string())
+    }
+
+    fn float_buffer(
+        width: usize,
+        height: usize,
+        pixels: Vec<f32>,
+    ) -> Result<ImageBuffer<image::Rgba<f32>, Vec<f32>>, String> {
+        ImageBuffer::from_raw(width as u32, height as u32, pixels)
+            .ok_or_else(|| "Decoded DDS buffer has the wrong size".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channel_packer::{ChannelPacker, ColorSpace, TextureFormat, TextureOptions};
+    use crate::mipmap::MipFilter;
+    use image::GenericImageView;
+
+    const FORMATS: [TextureFormat; 7] = [
+        TextureFormat::Bc1,
+        TextureFormat::Bc3,
+        TextureFormat::Bc4,
+        TextureFormat::Bc5,
+        TextureFormat::Bc7,
+        TextureFormat::Rgba8,
+        TextureFormat::Rgba16F,
+    ];
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("photonic_ring_{}_{}.dds", name, std::process::id()))
+    }
+
+    fn gradient(width: u32, height: u32) -> RgbaImage {
+        RgbaImage::from_fn(width, height, |x, y| {
+            image::Rgba([
+                (x * 255 / (width - 1)) as u8,
+                (y * 255 / (height - 1)) as u8,
+                ((x + y) * 255 / (width + height - 2)) as u8,
+                (255 - y * 128 / (height - 1)) as u8,
+            ])
+        })
+    }
+
+    /// Channels a format keeps
+    fn channels(format: TextureFormat) -> &'static [usize] {
+        match format {
+            TextureFormat::Bc1 => &[0, 1, 2],
+            TextureFormat::Bc4 => &[0],
+            TextureFormat::Bc5 => &[0, 1],
+            _ => &[0, 1, 2, 3],
+        }
+    }
+
+    fn assert_close(original: &RgbaImage, decoded: &DynamicImage, format: TextureFormat, context: &str) {
+        assert_eq!(decoded.dimensions(), original.dimensions(), "{}", context);
+        let decoded = decoded.to_rgba8();
+        let tolerance = if format.is_compressed() { 8.0 } else { 1.0 };
+        for &channel in channels(format) {
+            let error = original
+                .pixels()
+                .zip(decoded.pixels())
+                .map(|(a, b)| (a[channel] as f64 - b[channel] as f64).abs())
+                .sum::<f64>()
+                / (original.width() * original.height()) as f64;
+            assert!(error < tolerance, "{}: channel {} mean error {}", context, channel, error);
+        }
+    }
+
+    fn round_trip(image: &RgbaImage, options: &TextureOptions, name: &str) -> DynamicImage {
+        let path = temp_path(name);
+        ChannelPacker::save_as_dds(image, &path, options).unwrap();
+        let decoded = DdsReader::load(&path);
+        let _ = std::fs::remove_file(&path);
+        decoded.unwrap()
+    }
+
+    #[test]
+    fn reads_every_written_format() {
+        let image = gradient(64, 32);
+        for format in FORMATS {
+            for color_space in [ColorSpace::Unspecified, ColorSpace::Linear] {
+                let options = TextureOptions {
+                    format,
+                    color_space,
+                    mip_filter: Some(MipFilter::Box),
+                    ..Default::default()
+                };
+                let context = format!("{} {:?}", format.name(), color_space);
+                let decoded = round_trip(&image, &options, &context.replace(' ', "_"));
+                assert_close(&image, &decoded, format, &context);
+                if format == TextureFormat::Rgba16F {
+                    assert!(matches!(decoded, DynamicImage::ImageRgba32F(_)), "{}", context);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn reads_first_array_layer() {
+        let first = gradient(64, 64);
+        let mut second = first.clone();
+        second.pixels_mut().for_each(|pixel| pixel.0 = pixel.0.map(|value| 255 - value));
+        let layers = [first, second];
+        for format in [TextureFormat::Bc7, TextureFormat::Rgba8] {
+            let options = TextureOptions {
+                format,
+                ..Default::default()
+            };
+            let path = temp_path(&format!("array_{}", format.name()));
+            ChannelPacker::save_texture_array(&layers, &path, &options).unwrap();
+            let decoded = DdsReader::load(&path);
+            let _ = std::fs::remove_file(&path);
+            assert_close(&layers[0], &decoded.unwrap(), format, format.name());
+        }
+    }
+
+    #[test]
+    fn alpha_only_loads_into_alpha() {
+        let mut dds = Dds::new_d3d(ddsfile::NewD3dParams {
+            height: 4,
+            width: 4,
+            depth: None,
+            format: D3DFormat::A8,
+            mipmap_levels: None,
+            caps2: None,
+        })
+        .unwrap();
+        dds.data = (0..16).map(|i| i * 16).collect();
+        // ddsfile flags A8 as RGB when writing; real A8 files only carry the alpha flag
+        dds.header.spf.flags = PixelFormatFlags::ALPHA;
+
+        let path = temp_path("a8");
+        dds.write(&mut std::fs::File::create(&path).unwrap()).unwrap();
+        let decoded = DdsReader::load(&path);
+        let _ = std::fs::remove_file(&path);
+
+        let decoded = decoded.unwrap().to_rgba8();
+        for (i, pixel) in decoded.pixels().enumerate() {
+            assert_eq!(pixel.0, [0, 0, 0, i as u8 * 16]);
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, ImageBuffer, Luma, Rgba32FImage};
+use rayon::prelude::*;
+
+use crate::color_management::TransferFunction;
+use crate::image_converter::{BitDepth, ImageConverter};
+use crate::openexr::GrayF32Image;
+
+/// Settings for `AlbedoDelighter`.
+#[derive(Clone, Copy, Debug)]
+pub struct DelightOptions {
+    /// Blend between the original (0.0) and fully flattened (1.0) large-scale lighting
+    pub strength: f32,
+    /// Radius of the illumination estimate in pixels (0 = 1/8 of the larger side)
+    pub radius: f32,
+    /// How much medium-scale darkening (ambient occlusion) is lifted (0.0-1.0)
+    pub occlusion: f32,
+    /// How much bright, desaturated highlights are compressed (0.0-1.0)
+    pub highlights: f32,
+}
+
+impl Default for DelightOptions {
+    fn default() -> Self {
+        Self {
+            strength: 1.0,
+            radius: 0.0,
+            occlusion: 0.5,
+            highlights: 0.5,
+        }
+    }
+}
+
+/// A de-lit albedo.
+pub struct DelightReport {
+    /// Same layout and encoding as the source
+    pub image: DynamicImage,
+    /// Brightest / darkest estimated illumination that was removed
+    pub illumination_range: f32,
+}
+
+/// Removes baked lighting from photo-sourced albedos
+/// Works on log luminance in linear light: large-scale illumination is flattened
+/// towards the mean, medium-scale occlusion is lifted and specular highlights are
+/// compressed. RGB is scaled by the luminance change, so colour is preserved.
+pub struct AlbedoDelighter;
+
+impl AlbedoDelighter {
+    pub fn delight(albedo: &DynamicImage, transfer: TransferFunction, options: &DelightOptions) -> DelightReport {
+        let source = albedo.to_rgba32f();
+        let (width, height) = source.dimensions();
+
+        let mut linear = source.clone();
+        linear.par_chunks_mut(4).for_each(|pixel| {
+            for value in &mut pixel[..3] {
+                *value = transfer.to_linear(*value);
+            }
+        });
+
+        let log_luminance: GrayF32Image = ImageBuffer::from_fn(width, height, |x, y| {
+            let [r, g, b, _] = linear.get_pixel(x, y).0;
+            Luma([(0.2126 * r + 0.7152 * g + 0.0722 * b).max(1e-4).ln()])
+        });
+
+        let radius = if options.radius > 0.0 {
+            options.radius
+        } else {
+            width.max(height) as f32 / 8.0
+        };
+        let illumination = Self::low_pass(&log_luminance, radius);
+        let shading = Self::low_pass(&log_luminance, (radius / 8.0).max(1.0));
+
+        let pixel_count = (width as f64 * height as f64).max(1.0);
+        let mean = (log_luminance.pixels().map(|p| p[0] as f64).sum::<f64>() / pixel_count) as f32;
+        let (darkest, brightest) = illumination
+            .pixels()
+            .fold((f32::MAX, f32::MIN), |(min, max), p| (min.min(p[0]), max.max(p[0])));
+
+        // Highlights start at twice the mean luminance
+        let knee = mean + std::f32::consts::LN_2;
+        let clamp_max = if BitDepth::of(albedo.color()) == BitDepth::Float { f32::MAX } else { 1.0 };
+
+        let mut output: Rgba32FImage = ImageBuffer::new(width, height);
+        output
+            .par_chunks_mut(4)
+            .zip(linear.par_chunks(4))
+            .enumerate()
+            .for_each(|(index, (out, pixel))| {
+                let (x, y) = (index as u32 % width, index as u32 / width);
+                let log = log_luminance.get_pixel(x, y)[0];
+                let broad = illumination.get_pixel(x, y)[0];
+
+                let lighting = (broad - mean) * options.strength;
+                let occlusion = (shading.get_pixel(x, y)[0] - broad).min(0.0) * options.occlusion;
+                let mut target = log - lighting - occlusion;
+
+                if target > knee {
+                    // Specular highlights are bright and close to white
+                    let max = pixel[0].max(pixel[1]).max(pixel[2]);
+                    let min = pixel[0].min(pixel[1]).min(pixel[2]);
+                    let saturation = if max > 0.0 { (max - min) / max } else { 0.0 };
+                    let weight = options.highlights * (1.0 - saturation);
+                    target = knee + (target - knee) * (1.0 - weight);
+                }
+
+                let ratio = (target - log).exp();
+                for channel in 0..3 {
+                    out[channel] = transfer.encode((pixel[channel] * ratio).clamp(0.0, clamp_max));
+                }
+                out[3] = pixel[3];
+            });
+
+        let delit = if albedo.color().has_alpha() {
+            DynamicImage::ImageRgba32F(output)
+        } else {
+            DynamicImage::ImageRgb32F(DynamicImage::ImageRgba32F(output).to_rgb32f())
+        };
+
+        DelightReport {
+            image: ImageConverter::with_depth(&delit, BitDepth::of(albedo.color())),
+            illumination_range: (brightest - darkest).max(0.0).exp(),
+        }
+    }
+
+    /// Gaussian blur with a large radius, computed at reduced resolution
+    pub fn low_pass(image: &GrayF32Image, sigma: f32) -> GrayF32Image {
+        let (width, height) = image.dimensions();
+        // Resizing clamps negative samples, so work on values shifted above zero
+        let offset = image.pixels().fold(f32::MAX, |min, p| min.min(p[0]));
+        let shifted: GrayF32Image = ImageBuffer::from_fn(width, height, |x, y| Luma([image.get_pixel(x, y)[0] - offset]));
+
+        // About 2 pixels of blur at the reduced size keeps the cost independent of the radius
+        let scale = (2.0 / sigma).min(1.0);
+        let small_width = ((width as f32 * scale).round() as u32).max(1);
+        let small_height = ((height as f32 * scale).round() as u32).max(1);
+
+        let small = imageops::resize(&shifted, small_width, small_height, FilterType::Triangle);
+        let blurred = imageproc::filter::gaussian_blur_f32(&small, (sigma * scale).max(0.5));
+        let mut result = imageops::resize(&blurred, width, height, FilterType::Triangle);
+        result.pixels_mut().for_each(|p| p[0] += offset);
+        result
+    }
+}
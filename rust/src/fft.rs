@@ -0,0 +1,209 @@
+use nalgebra::Complex;
+use rayon::prelude::*;
+use std::f64::consts::PI;
+
+pub type Complex32 = Complex<f32>;
+
+/// Precomputed FFT of one length
+/// Powers of two use an iterative radix-2 transform, other lengths Bluestein's
+/// algorithm on top of a power-of-two transform.
+pub struct Fft {
+    len: usize,
+    plan: Plan,
+}
+
+enum Plan {
+    Radix2 { twiddles: Vec<Complex32> },
+    Bluestein {
+        inner: Box<Fft>,
+        chirp: Vec<Complex32>,
+        /// Transform of the conjugated, wrapped chirp
+        kernel: Vec<Complex32>,
+    },
+}
+
+impl Fft {
+    pub fn new(len: usize) -> Self {
+        let len = len.max(1);
+        if len.is_power_of_two() {
+            let twiddles = (0..len / 2)
+                .map(|k| Self::unit(-2.0 * PI * k as f64 / len as f64))
+                .collect();
+            return Self { len, plan: Plan::Radix2 { twiddles } };
+        }
+
+        let inner_len = (2 * len - 1).next_power_of_two();
+        let inner = Box::new(Fft::new(inner_len));
+        // k² is taken modulo 2n so the angle stays precise for long transforms
+        let chirp: Vec<Complex32> = (0..len)
+            .map(|k| Self::unit(-PI * ((k * k) % (2 * len)) as f64 / len as f64))
+            .collect();
+        let mut kernel = vec![Complex32::new(0.0, 0.0); inner_len];
+        kernel[0] = chirp[0].conj();
+        for k in 1..len {
+            kernel[k] = chirp[k].conj();
+            kernel[inner_len - k] = chirp[k].conj();
+        }
+        inner.forward(&mut kernel);
+
+        Self {
+            len,
+            plan: Plan::Bluestein { inner, chirp, kernel },
+        }
+    }
+
+    /// Unnormalized forward transform
+    pub fn forward(&self, data: &mut [Complex32]) {
+        debug_assert_eq!(data.len(), self.len);
+        match &self.plan {
+            Plan::Radix2 { twiddles } => Self::radix2(data, twiddles),
+            Plan::Bluestein { inner, chirp, kernel } => {
+                let mut buffer = vec![Complex32::new(0.0, 0.0); kernel.len()];
+                for ((slot, value), w) in buffer.iter_mut().zip(data.iter()).zip(chirp) {
+                    *slot = value * w;
+                }
+                inner.forward(&mut buffer);
+                for (slot, k) in buffer.iter_mut().zip(kernel) {
+                    *slot *= k;
+                }
+                inner.inverse(&mut buffer);
+                for ((value, result), w) in data.iter_mut().zip(&buffer).zip(chirp) {
+                    *value = result * w;
+                }
+            }
+        }
+    }
+
+    /// Inverse transform, scaled by 1/len
+    pub fn inverse(&self, data: &mut [Complex32]) {
+        data.iter_mut().for_each(|value| *value = value.conj());
+        self.forward(data);
+        let scale = 1.0 / self.len as f32;
+        data.iter_mut().for_each(|value| *value = value.conj() * scale);
+    }
+
+    fn radix2(data: &mut [Complex32], twiddles: &[Complex32]) {
+        let len = data.len();
+        let bits = len.trailing_zeros();
+        if bits == 0 {
+            return;
+        }
+        for i in 0..len {
+            let j = i.reverse_bits() >> (usize::BITS - bits);
+            if j > i {
+                data.swap(i, j);
+            }
+        }
+
+        let mut size = 2;
+        while size <= len {
+            let half = size / 2;
+            let step = len / size;
+            for start in (0..len).step_by(size) {
+                for k in 0..half {
+                    let t = data[start + k + half] * twiddles[k * step];
+                    let u = data[start + k];
+                    data[start + k] = u + t;
+                    data[start + k + half] = u - t;
+                }
+            }
+            size *= 2;
+        }
+    }
+
+    fn unit(angle: f64) -> Complex32 {
+        Complex32::new(angle.cos() as f32, angle.sin() as f32)
+    }
+}
+
+/// 2D transform of a row-major `width` x `height` grid (inverse is scaled by 1/(width*height))
+pub fn fft_2d(data: &mut [Complex32], width: usize, height: usize, inverse: bool) {
+    let transform = |fft: &Fft, line: &mut [Complex32]| {
+        if inverse {
+            fft.inverse(line)
+        } else {
+            fft.forward(line)
+        }
+    };
+
+    let rows = Fft::new(width);
+    data.par_chunks_mut(width).for_each(|row| transform(&rows, row));
+
+    let columns = Fft::new(height);
+    let mut transposed = vec![Complex32::new(0.0, 0.0); data.len()];
+    transposed.par_chunks_mut(height).enumerate().for_each(|(x, column)| {
+        for (y, value) in column.iter_mut().enumerate() {
+            *value = data[y * width + x];
+        }
+        transform(&columns, column);
+    });
+    data.par_chunks_mut(width).enumerate().for_each(|(y, row)| {
+        for (x, value) in row.iter_mut().enumerate() {
+            *value = transposed[x * height + y];
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic, non-symmetric test signal
+    fn signal(len: usize) -> Vec<Complex32> {
+        (0..len)
+            .map(|i| {
+                let t = i as f32;
+                Complex32::new((t * 0.37).sin() + 0.25 * t.sqrt(), (t * 1.3).cos() - 0.5)
+            })
+            .collect()
+    }
+
+    fn max_error(a: &[Complex32], b: &[Complex32]) -> f32 {
+        a.iter().zip(b).map(|(a, b)| (a - b).norm()).fold(0.0, f32::max)
+    }
+
+    #[test]
+    fn inverse_undoes_forward() {
+        // Powers of two take the radix-2 path, everything else Bluestein
+        for len in [1, 2, 8, 256, 3, 12, 37, 100, 1000] {
+            let original = signal(len);
+            let fft = Fft::new(len);
+            let mut data = original.clone();
+            fft.forward(&mut data);
+            fft.inverse(&mut data);
+            let error = max_error(&data, &original);
+            assert!(error < 1e-3, "length {}: error {}", len, error);
+        }
+    }
+
+    #[test]
+    fn forward_matches_direct_dft() {
+        for len in [8, 12, 37] {
+            let input = signal(len);
+            let expected: Vec<Complex32> = (0..len)
+                .map(|k| {
+                    input
+                        .iter()
+                        .enumerate()
+                        .map(|(n, value)| value * Fft::unit(-2.0 * PI * ((k * n) % len) as f64 / len as f64))
+                        .sum()
+                })
+                .collect();
+            let mut data = input.clone();
+            Fft::new(len).forward(&mut data);
+            let error = max_error(&data, &expected);
+            assert!(error < 1e-3, "length {}: error {}", len, error);
+        }
+    }
+
+    #[test]
+    fn inverse_2d_undoes_forward_2d() {
+        let (width, height) = (12, 8);
+        let original = signal(width * height);
+        let mut data = original.clone();
+        fft_2d(&mut data, width, height, false);
+        fft_2d(&mut data, width, height, true);
+        let error = max_error(&data, &original);
+        assert!(error < 1e-3, "error {}", error);
+    }
+}
@@ -0,0 +1,80 @@
+use image::{DynamicImage, ImageBuffer, Luma};
+
+use crate::openexr::GrayF32Image;
+use crate::surface_integration::SurfaceIntegrator;
+
+/// Direction of the green channel in a tangent-space normal map.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NormalConvention {
+    /// Green points up (Godot, Blender, Unity)
+    OpenGl,
+    /// Green points down (Unreal, 3ds Max)
+    DirectX,
+}
+
+impl NormalConvention {
+    /// Parse a convention name as passed from GDScript ("" selects OpenGL).
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "" | "opengl" | "gl" | "y+" => Ok(NormalConvention::OpenGl),
+            "directx" | "dx" | "y-" => Ok(NormalConvention::DirectX),
+            other => Err(format!(
+                "Unknown normal convention '{}' (expected opengl or directx)",
+                other
+            )),
+        }
+    }
+
+    /// Name as accepted by `from_name`
+    pub fn name(self) -> &'static str {
+        match self {
+            NormalConvention::OpenGl => "opengl",
+            NormalConvention::DirectX => "directx",
+        }
+    }
+}
+
+/// Reconstructed height.
+pub struct HeightFromNormal {
+    /// 0.0-1.0
+    pub height: GrayF32Image,
+    /// Difference between the highest and lowest point, in pixels
+    pub relief: f32,
+}
+
+/// Height map reconstruction from a tangent-space normal map
+/// Normals are turned into slopes and integrated with `SurfaceIntegrator`.
+pub struct HeightFromNormalGenerator;
+
+impl HeightFromNormalGenerator {
+    pub fn generate(normal_map: &DynamicImage, convention: NormalConvention, wrap: bool) -> HeightFromNormal {
+        let rgb = normal_map.to_rgb32f();
+        let (width, height) = rgb.dimensions();
+
+        let slopes = |x: u32, y: u32| {
+            let [r, g, b] = rgb.get_pixel(x, y).0;
+            let (nx, ny) = (r * 2.0 - 1.0, g * 2.0 - 1.0);
+            // Steeper than ~87° cannot be integrated meaningfully
+            let nz = (b * 2.0 - 1.0).max(0.05);
+            // Slopes with image y pointing down
+            let dy = match convention {
+                NormalConvention::OpenGl => ny / nz,
+                NormalConvention::DirectX => -ny / nz,
+            };
+            (-nx / nz, dy)
+        };
+        let dh_dx: GrayF32Image = ImageBuffer::from_fn(width, height, |x, y| Luma([slopes(x, y).0]));
+        let dh_dy: GrayF32Image = ImageBuffer::from_fn(width, height, |x, y| Luma([slopes(x, y).1]));
+
+        let mut surface = SurfaceIntegrator::integrate(&dh_dx, &dh_dy, wrap);
+        let (min, max) = surface
+            .pixels()
+            .fold((f32::MAX, f32::MIN), |(min, max), p| (min.min(p[0]), max.max(p[0])));
+        let relief = (max - min).max(0.0);
+        if relief > 0.0 {
+            surface.pixels_mut().for_each(|p| p[0] = (p[0] - min) / relief);
+        }
+
+        HeightFromNormal { height: surface, relief }
+    }
+}
@@ -1,6 +1,105 @@
 use image::{DynamicImage, GrayImage, ImageBuffer};
 use rayon::prelude::*;
 
+use crate::delight::AlbedoDelighter;
+use crate::openexr::GrayF32Image;
+use crate::surface_integration::SurfaceIntegrator;
+
+/// How height is derived from the albedo.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HeightMode {
+    /// Brightness is height
+    Luminance,
+    /// The albedo is a shaded image; slopes facing the light are bright
+    ShapeFromShading,
+}
+
+impl HeightMode {
+    /// Parse a height mode as passed from GDScript ("" selects luminance).
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "" | "luminance" => Ok(HeightMode::Luminance),
+            "shading" | "sfs" | "shape_from_shading" => Ok(HeightMode::ShapeFromShading),
+            other => Err(format!(
+                "Unknown height mode '{}' (expected luminance or shading)",
+                other
+            )),
+        }
+    }
+}
+
+/// Light direction in degrees.
+/// Azimuth is measured counter-clockwise from +X (90 = light from the top of the
+/// image), elevation from the surface plane (90 = straight on).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LightDirection {
+    pub azimuth: f32,
+    pub elevation: f32,
+}
+
+impl Default for LightDirection {
+    /// Top-left light, the usual photographic convention
+    fn default() -> Self {
+        Self {
+            azimuth: 135.0,
+            elevation: 45.0,
+        }
+    }
+}
+
+impl LightDirection {
+    /// Unit vector towards the light (image y pointing down)
+    fn vector(self) -> [f32; 3] {
+        let (azimuth, elevation) = (self.azimuth.to_radians(), self.elevation.to_radians());
+        [
+            elevation.cos() * azimuth.cos(),
+            -elevation.cos() * azimuth.sin(),
+            elevation.sin(),
+        ]
+    }
+
+    /// Estimate the light azimuth from a (linear) luminance image.
+    /// Shading varies along the light direction, so the dominant axis of the
+    /// luminance gradients (structure tensor) gives the light axis; of its two
+    /// directions the one from the top half of the image is chosen, as viewers
+    /// assume. The elevation only scales the slopes, which the final
+    /// normalization removes, so the default is kept. Falls back to the default
+    /// light when the gradients have no dominant axis.
+    pub fn estimate(luminance: &GrayF32Image) -> Self {
+        let (width, height) = luminance.dimensions();
+        let get = |x: i32, y: i32| {
+            luminance.get_pixel(
+                x.rem_euclid(width as i32) as u32,
+                y.rem_euclid(height as i32) as u32,
+            )[0] as f64
+        };
+
+        let (mut sxx, mut syy, mut sxy) = (0.0f64, 0.0f64, 0.0f64);
+        for y in 0..height as i32 {
+            for x in 0..width as i32 {
+                let gx = get(x + 1, y) - get(x - 1, y);
+                let gy = get(x, y + 1) - get(x, y - 1);
+                sxx += gx * gx;
+                syy += gy * gy;
+                sxy += gx * gy;
+            }
+        }
+
+        let anisotropy = ((sxx - syy).powi(2) + 4.0 * sxy * sxy).sqrt() / (sxx + syy).max(1e-12);
+        if anisotropy < 0.05 {
+            return Self::default();
+        }
+        // Axis angle in image space (y down), turned into an azimuth (y up)
+        let axis = 0.5 * (2.0 * sxy).atan2(sxx - syy);
+        let azimuth = (-axis).to_degrees().rem_euclid(180.0) as f32;
+
+        Self {
+            azimuth,
+            ..Self::default()
+        }
+    }
+}
+
 pub struct HeightMapGenerator;
 
 impl HeightMapGenerator {
@@ -30,6 +129,84 @@ impl HeightMapGenerator {
         DynamicImage::ImageLuma8(smoothed)
     }
 
+    /// Same pipeline as `generate` at 32-bit float precision, starting from a
+    /// luminance image (see `ColorManagement::luminance`). The result spans 0.0-1.0.
+    pub fn generate_from_luminance(luminance: &GrayF32Image) -> GrayF32Image {
+        let mut gray = luminance.clone();
+        Self::normalize_f32(&mut gray);
+
+        let enhanced = Self::local_contrast_enhancement_f32(&gray, 20, 3.0);
+        let mut smoothed = imageproc::filter::gaussian_blur_f32(&enhanced, 0.5);
+        Self::normalize_f32(&mut smoothed);
+
+        smoothed
+    }
+
+    /// Shape from shading: treat the luminance as a Lambertian image lit from `light`,
+    /// turn the shading into slopes along the light direction and integrate them
+    /// (see `SurfaceIntegrator`). `wrap` keeps the result tileable. The result spans 0.0-1.0.
+    pub fn generate_from_shading(luminance: &GrayF32Image, light: LightDirection, wrap: bool) -> GrayF32Image {
+        let (width, height) = luminance.dimensions();
+        let [lx, ly, lz] = light.vector();
+        let planar = (lx * lx + ly * ly).sqrt().max(1e-3);
+
+        // Dividing by the large-scale mean removes slow albedo and exposure changes
+        let radius = (width.max(height) as f32 / 16.0).max(1.0);
+        let local_mean = AlbedoDelighter::low_pass(luminance, radius);
+
+        // Linearised Lambertian shading: s - lz = -(p lx + q ly)
+        let slope = |x: u32, y: u32| {
+            let mean = local_mean.get_pixel(x, y)[0].max(1e-4);
+            let shading = (lz * luminance.get_pixel(x, y)[0] / mean).clamp(0.0, 1.0);
+            ((lz - shading) / planar).clamp(-4.0, 4.0)
+        };
+        let dh_dx: GrayF32Image = ImageBuffer::from_fn(width, height, |x, y| image::Luma([slope(x, y) * lx / planar]));
+        let dh_dy: GrayF32Image = ImageBuffer::from_fn(width, height, |x, y| image::Luma([slope(x, y) * ly / planar]));
+
+        let mut surface = SurfaceIntegrator::integrate(&dh_dx, &dh_dy, wrap);
+        Self::normalize_f32(&mut surface);
+        surface
+    }
+
+    /// Normalize to span the full 0.0-1.0 range
+    fn normalize_f32(image: &mut GrayF32Image) {
+        let (min, max) = image.pixels().fold((f32::MAX, f32::MIN), |(min, max), p| {
+            (min.min(p[0]), max.max(p[0]))
+        });
+
+        if max > min {
+            let range = max - min;
+            for p in image.pixels_mut() {
+                p[0] = (p[0] - min) / range;
+            }
+        }
+    }
+
+    fn local_contrast_enhancement_f32(image: &GrayF32Image, radius: i32, strength: f32) -> GrayF32Image {
+        let width = image.width();
+        let height = image.height();
+        let mut result: GrayF32Image = ImageBuffer::new(width, height);
+
+        result.enumerate_pixels_mut().par_bridge().for_each(|(x, y, pixel)| {
+            let mut sum = 0.0;
+            let mut count = 0.0;
+            for dy in -radius..=radius {
+                for dx in -radius..=radius {
+                    let nx = (x as i32 + dx).clamp(0, width as i32 - 1) as u32;
+                    let ny = (y as i32 + dy).clamp(0, height as i32 - 1) as u32;
+                    sum += image.get_pixel(nx, ny)[0];
+                    count += 1.0;
+                }
+            }
+
+            let mean = sum / count;
+            let val = image.get_pixel(x, y)[0];
+            pixel.0 = [(mean + (val - mean) * strength).clamp(0.0, 1.0)];
+        });
+
+        result
+    }
+
     /// Normalize histogram to span full 0-255 range
     fn normalize_histogram(image: &mut GrayImage) {
         let (min, max) = image.pixels().fold((255, 0), |(min, max), p| {
@@ -0,0 +1,276 @@
+use image::imageops::FilterType;
+use image::{ColorType, DynamicImage};
+use std::path::Path;
+
+use crate::channel_packer::{ChannelPacker, ColorSpace, ResizePolicy, TextureFormat, TextureOptions};
+use crate::color_management::ColorManagement;
+use crate::mipmap::{linear_to_srgb, srgb_to_linear, MipFilter, TextureContent};
+use crate::openexr::OpenExr;
+
+/// Target file format for `ImageConverter`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConvertFormat {
+    Png,
+    Tga,
+    Tiff,
+    /// Lossless WebP
+    Webp,
+    Exr,
+    Dds,
+}
+
+impl ConvertFormat {
+    /// Parse a format name as passed from GDScript.
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        match name.trim().trim_start_matches('.').to_ascii_lowercase().as_str() {
+            "png" => Ok(ConvertFormat::Png),
+            "tga" => Ok(ConvertFormat::Tga),
+            "tif" | "tiff" => Ok(ConvertFormat::Tiff),
+            "webp" => Ok(ConvertFormat::Webp),
+            "exr" => Ok(ConvertFormat::Exr),
+            "dds" => Ok(ConvertFormat::Dds),
+            other => Err(format!(
+                "Unknown output format '{}' (expected png, tga, tiff, webp, exr or dds)",
+                other
+            )),
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ConvertFormat::Png => "png",
+            ConvertFormat::Tga => "tga",
+            ConvertFormat::Tiff => "tiff",
+            ConvertFormat::Webp => "webp",
+            ConvertFormat::Exr => "exr",
+            ConvertFormat::Dds => "dds",
+        }
+    }
+
+    /// Highest precision the format stores
+    fn max_depth(self) -> BitDepth {
+        match self {
+            ConvertFormat::Png | ConvertFormat::Tiff => BitDepth::Sixteen,
+            ConvertFormat::Tga | ConvertFormat::Webp | ConvertFormat::Dds => BitDepth::Eight,
+            ConvertFormat::Exr => BitDepth::Float,
+        }
+    }
+}
+
+/// Bits per channel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum BitDepth {
+    Eight,
+    Sixteen,
+    /// 32-bit float
+    Float,
+}
+
+impl BitDepth {
+    /// Parse a depth as passed from GDScript ("" or "keep" returns `None`).
+    pub fn from_name(name: &str) -> Result<Option<Self>, String> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "" | "keep" => Ok(None),
+            "8" => Ok(Some(BitDepth::Eight)),
+            "16" => Ok(Some(BitDepth::Sixteen)),
+            "32" | "float" => Ok(Some(BitDepth::Float)),
+            other => Err(format!("Unknown bit depth '{}' (expected keep, 8, 16 or 32)", other)),
+        }
+    }
+
+    /// Depth of a pixel layout
+    pub fn of(color: ColorType) -> Self {
+        match color {
+            ColorType::Rgb32F | ColorType::Rgba32F => BitDepth::Float,
+            ColorType::L16 | ColorType::La16 | ColorType::Rgb16 | ColorType::Rgba16 => BitDepth::Sixteen,
+            _ => BitDepth::Eight,
+        }
+    }
+}
+
+/// Transfer function change applied to RGB (alpha untouched).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorConversion {
+    None,
+    SrgbToLinear,
+    LinearToSrgb,
+}
+
+impl ColorConversion {
+    /// Parse a conversion name as passed from GDScript ("" or "keep" selects none).
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "" | "keep" | "none" => Ok(ColorConversion::None),
+            "to_linear" | "srgb_to_linear" => Ok(ColorConversion::SrgbToLinear),
+            "to_srgb" | "linear_to_srgb" => Ok(ColorConversion::LinearToSrgb),
+            other => Err(format!(
+                "Unknown colour conversion '{}' (expected keep, to_linear or to_srgb)",
+                other
+            )),
+        }
+    }
+}
+
+/// Options for `ImageConverter`.
+pub struct ConvertOptions {
+    pub format: ConvertFormat,
+    /// `None` keeps the source depth; always limited to what `format` stores
+    pub bit_depth: Option<BitDepth>,
+    /// Target width (0 = keep, or follow `height` preserving the aspect ratio)
+    pub width: u32,
+    /// Target height (0 = keep, or follow `width` preserving the aspect ratio)
+    pub height: u32,
+    /// Downscale so the larger side is at most this (0 = no limit)
+    pub max_size: u32,
+    pub color: ColorConversion,
+    pub strip_alpha: bool,
+    /// Block format for DDS output
+    pub dds_format: TextureFormat,
+    /// Mip filter for DDS output (`None` writes the top level only)
+    pub mip_filter: Option<MipFilter>,
+}
+
+impl Default for ConvertOptions {
+    fn default() -> Self {
+        Self {
+            format: ConvertFormat::Png,
+            bit_depth: None,
+            width: 0,
+            height: 0,
+            max_size: 0,
+            color: ColorConversion::None,
+            strip_alpha: false,
+            dds_format: TextureFormat::Bc7,
+            mip_filter: Some(MipFilter::Kaiser),
+        }
+    }
+}
+
+/// Resize, colour and bit depth conversion between image formats
+pub struct ImageConverter;
+
+impl ImageConverter {
+    /// Apply resizing, colour conversion, alpha stripping and bit depth conversion.
+    pub fn convert(image: DynamicImage, options: &ConvertOptions) -> DynamicImage {
+        let source_depth = BitDepth::of(image.color());
+
+        let (width, height) = Self::target_size(image.width(), image.height(), options);
+        let image = if (width, height) != (image.width(), image.height()) {
+            image.resize_exact(width, height, FilterType::Lanczos3)
+        } else {
+            image
+        };
+
+        let image = Self::convert_color(image, options.color);
+
+        let channels = match (image.color().channel_count(), options.strip_alpha) {
+            (2, true) => 1,
+            (4, true) => 3,
+            (channels, _) => channels,
+        };
+        let depth = options.bit_depth.unwrap_or(source_depth).min(options.format.max_depth());
+        Self::with_layout(&image, channels, depth)
+    }
+
+    /// Save a converted image in `options.format`.
+    /// PNG and DDS output is tagged sRGB or linear when `options.color` converted it.
+    pub fn save(image: &DynamicImage, path: &Path, options: &ConvertOptions) -> Result<(), String> {
+        let color_space = match options.color {
+            ColorConversion::None => ColorSpace::Unspecified,
+            ColorConversion::SrgbToLinear => ColorSpace::Linear,
+            ColorConversion::LinearToSrgb => ColorSpace::Srgb,
+        };
+
+        match options.format {
+            ConvertFormat::Exr if image.color().channel_count() <= 2 => OpenExr::save_gray(&image.to_luma32f(), path),
+            ConvertFormat::Exr => OpenExr::save(image, path),
+            ConvertFormat::Dds => {
+                let texture = TextureOptions {
+                    format: options.dds_format,
+                    mip_filter: options.mip_filter,
+                    content: if color_space == ColorSpace::Srgb { TextureContent::Color } else { TextureContent::Linear },
+                    color_space,
+                    resize: ResizePolicy::PadToBlock,
+                    ..Default::default()
+                };
+                ChannelPacker::save_as_dds(&image.to_rgba8(), path, &texture).map(|_| ())
+            }
+            ConvertFormat::Png => ColorManagement::save_png(image, path, color_space),
+            _ => image
+                .save(path)
+                .map_err(|e| format!("Failed to save {}: {}", path.display(), e)),
+        }
+    }
+
+    /// Convert to `depth` bits per channel, keeping the channel count.
+    /// Float is only available for RGB(A); grey images stay 16-bit.
+    pub fn with_depth(image: &DynamicImage, depth: BitDepth) -> DynamicImage {
+        Self::with_layout(image, image.color().channel_count(), depth)
+    }
+
+    fn with_layout(image: &DynamicImage, channels: u8, depth: BitDepth) -> DynamicImage {
+        match (channels, depth) {
+            (1, BitDepth::Eight) => DynamicImage::ImageLuma8(image.to_luma8()),
+            (1, _) => DynamicImage::ImageLuma16(image.to_luma16()),
+            (2, BitDepth::Eight) => DynamicImage::ImageLumaA8(image.to_luma_alpha8()),
+            (2, _) => DynamicImage::ImageLumaA16(image.to_luma_alpha16()),
+            (3, BitDepth::Eight) => DynamicImage::ImageRgb8(image.to_rgb8()),
+            (3, BitDepth::Sixteen) => DynamicImage::ImageRgb16(image.to_rgb16()),
+            (3, BitDepth::Float) => DynamicImage::ImageRgb32F(image.to_rgb32f()),
+            (_, BitDepth::Eight) => DynamicImage::ImageRgba8(image.to_rgba8()),
+            (_, BitDepth::Sixteen) => DynamicImage::ImageRgba16(image.to_rgba16()),
+            (_, BitDepth::Float) => DynamicImage::ImageRgba32F(image.to_rgba32f()),
+        }
+    }
+
+    fn target_size(width: u32, height: u32, options: &ConvertOptions) -> (u32, u32) {
+        let scale = |value: u32, from: u32, to: u32| ((value as f64 * to as f64 / from as f64).round() as u32).max(1);
+        let (mut target_width, mut target_height) = match (options.width, options.height) {
+            (0, 0) => (width, height),
+            (w, 0) => (w, scale(height, width, w)),
+            (0, h) => (scale(width, height, h), h),
+            (w, h) => (w, h),
+        };
+
+        let larger = target_width.max(target_height);
+        if options.max_size > 0 && larger > options.max_size {
+            target_width = scale(target_width, larger, options.max_size);
+            target_height = scale(target_height, larger, options.max_size);
+        }
+        (target_width, target_height)
+    }
+
+    /// Grey images are converted at 16 bits, colour images at 32-bit float,
+    /// so the curve does not band.
+    fn convert_color(image: DynamicImage, conversion: ColorConversion) -> DynamicImage {
+        let curve: fn(f32) -> f32 = match conversion {
+            ColorConversion::None => return image,
+            ColorConversion::SrgbToLinear => srgb_to_linear,
+            ColorConversion::LinearToSrgb => linear_to_srgb,
+        };
+        let curve16 = |v: u16| (curve(v as f32 / 65535.0).clamp(0.0, 1.0) * 65535.0).round() as u16;
+
+        match image.color().channel_count() {
+            1 => {
+                let mut gray = image.to_luma16();
+                gray.pixels_mut().for_each(|p| p[0] = curve16(p[0]));
+                DynamicImage::ImageLuma16(gray)
+            }
+            2 => {
+                let mut gray = image.to_luma_alpha16();
+                gray.pixels_mut().for_each(|p| p[0] = curve16(p[0]));
+                DynamicImage::ImageLumaA16(gray)
+            }
+            3 => {
+                let mut rgb = image.to_rgb32f();
+                rgb.pixels_mut().for_each(|p| p.0.iter_mut().for_each(|c| *c = curve(*c)));
+                DynamicImage::ImageRgb32F(rgb)
+            }
+            _ => {
+                let mut rgba = image.to_rgba32f();
+                rgba.pixels_mut().for_each(|p| p.0[..3].iter_mut().for_each(|c| *c = curve(*c)));
+                DynamicImage::ImageRgba32F(rgba)
+            }
+        }
+    }
+}
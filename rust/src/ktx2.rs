@@ -0,0 +1,362 @@
+use std::path::Path;
+
+use crate::channel_packer::TextureFormat;
+
+/// KTX2 file identifier: «KTX 20»\r\n\x1A\n
+const IDENTIFIER: [u8; 12] = [0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A];
+
+/// Identifier + header + index
+const HEADER_SIZE: usize = 80;
+
+// Data Format Descriptor constants (Khronos Data Format Specification 1.3)
+const DF_MODEL_RGBSDA: u32 = 1;
+const DF_MODEL_BC1A: u32 = 128;
+const DF_MODEL_BC3: u32 = 130;
+const DF_MODEL_BC4: u32 = 131;
+const DF_MODEL_BC5: u32 = 132;
+const DF_MODEL_BC7: u32 = 134;
+const DF_PRIMARIES_BT709: u32 = 1;
+const DF_TRANSFER_LINEAR: u32 = 1;
+const DF_TRANSFER_SRGB: u32 = 2;
+const DF_CHANNEL_ALPHA: u8 = 15;
+const DF_QUALIFIER_LINEAR: u8 = 0x10;
+const DF_QUALIFIER_SIGNED: u8 = 0x40;
+const DF_QUALIFIER_FLOAT: u8 = 0x80;
+
+/// One sample of the basic DFD block
+struct Sample {
+    channel: u8,
+    qualifiers: u8,
+    bit_offset: u16,
+    bit_length: u8,
+    lower: u32,
+    upper: u32,
+}
+
+impl Sample {
+    /// Sample covering `bits` bits of a compressed block
+    fn block(channel: u8, bit_offset: u16, bits: u16) -> Self {
+        Self {
+            channel,
+            qualifiers: 0,
+            bit_offset,
+            bit_length: (bits - 1) as u8,
+            lower: 0,
+            upper: u32::MAX,
+        }
+    }
+}
+
+/// KTX2 container writer
+/// Writes pre-encoded layers (each a mip chain, largest level first) with a Vulkan
+/// format and a Data Format Descriptor carrying the colour space.
+/// More than one layer produces a texture array.
+pub struct Ktx2Writer;
+
+impl Ktx2Writer {
+    pub fn write(
+        output_path: &Path,
+        format: TextureFormat,
+        srgb: bool,
+        width: u32,
+        height: u32,
+        layers: &[Vec<Vec<u8>>],
+    ) -> Result<(), String> {
+        std::fs::write(output_path, Self::encode(format, srgb, width, height, layers))
+            .map_err(|e| format!("Failed to write KTX2: {}", e))
+    }
+
+    /// Build the whole KTX2 file in memory.
+    fn encode(format: TextureFormat, srgb: bool, width: u32, height: u32, layers: &[Vec<Vec<u8>>]) -> Vec<u8> {
+        // Formats without an sRGB variant are always linear
+        let srgb = srgb && matches!(
+            format,
+            TextureFormat::Bc1 | TextureFormat::Bc3 | TextureFormat::Bc7 | TextureFormat::Rgba8
+        );
+
+        let dfd = Self::data_format_descriptor(format, srgb);
+        let kvd = Self::key_value_data();
+        let level_count = layers[0].len();
+        // Every layer of a mip level is stored back to back
+        let level_sizes: Vec<usize> = (0..level_count)
+            .map(|index| layers.iter().map(|levels| levels[index].len()).sum())
+            .collect();
+
+        let level_index_offset = HEADER_SIZE;
+        let dfd_offset = level_index_offset + level_count * 24;
+        let kvd_offset = dfd_offset + dfd.len();
+        let data_start = kvd_offset + kvd.len();
+
+        // Mip data is stored smallest level first, each level aligned to lcm(texel block size, 4)
+        let alignment = Self::level_alignment(format);
+        let mut offsets = vec![0usize; level_count];
+        let mut position = data_start;
+        for index in (0..level_count).rev() {
+            position = position.div_ceil(alignment) * alignment;
+            offsets[index] = position;
+            position += level_sizes[index];
+        }
+
+        let mut ktx = Vec::with_capacity(position);
+        ktx.extend_from_slice(&IDENTIFIER);
+
+        // Header
+        let type_size: u32 = if format == TextureFormat::Rgba16F { 2 } else { 1 };
+        for value in [
+            Self::vk_format(format, srgb),
+            type_size,
+            width,
+            height,
+            0, // pixelDepth
+            if layers.len() > 1 { layers.len() as u32 } else { 0 }, // layerCount
+            1, // faceCount
+            level_count as u32,
+            0, // supercompressionScheme
+        ] {
+            ktx.extend_from_slice(&value.to_le_bytes());
+        }
+
+        // Index
+        ktx.extend_from_slice(&(dfd_offset as u32).to_le_bytes());
+        ktx.extend_from_slice(&(dfd.len() as u32).to_le_bytes());
+        ktx.extend_from_slice(&(kvd_offset as u32).to_le_bytes());
+        ktx.extend_from_slice(&(kvd.len() as u32).to_le_bytes());
+        ktx.extend_from_slice(&0u64.to_le_bytes()); // sgdByteOffset
+        ktx.extend_from_slice(&0u64.to_le_bytes()); // sgdByteLength
+
+        // Level index (level 0 first)
+        for (&size, &offset) in level_sizes.iter().zip(&offsets) {
+            ktx.extend_from_slice(&(offset as u64).to_le_bytes());
+            ktx.extend_from_slice(&(size as u64).to_le_bytes());
+            ktx.extend_from_slice(&(size as u64).to_le_bytes());
+        }
+
+        ktx.extend_from_slice(&dfd);
+        ktx.extend_from_slice(&kvd);
+
+        for index in (0..level_count).rev() {
+            ktx.resize(offsets[index], 0);
+            for levels in layers {
+                ktx.extend_from_slice(&levels[index]);
+            }
+        }
+
+        ktx
+    }
+
+    /// VkFormat value for the header
+    fn vk_format(format: TextureFormat, srgb: bool) -> u32 {
+        match (format, srgb) {
+            (TextureFormat::Bc1, false) => 131, // VK_FORMAT_BC1_RGB_UNORM_BLOCK
+            (TextureFormat::Bc1, true) => 132,  // VK_FORMAT_BC1_RGB_SRGB_BLOCK
+            (TextureFormat::Bc3, false) => 137, // VK_FORMAT_BC3_UNORM_BLOCK
+            (TextureFormat::Bc3, true) => 138,  // VK_FORMAT_BC3_SRGB_BLOCK
+            (TextureFormat::Bc4, _) => 139,     // VK_FORMAT_BC4_UNORM_BLOCK
+            (TextureFormat::Bc5, _) => 141,     // VK_FORMAT_BC5_UNORM_BLOCK
+            (TextureFormat::Bc7, false) => 145, // VK_FORMAT_BC7_UNORM_BLOCK
+            (TextureFormat::Bc7, true) => 146,  // VK_FORMAT_BC7_SRGB_BLOCK
+            (TextureFormat::Rgba8, false) => 37, // VK_FORMAT_R8G8B8A8_UNORM
+            (TextureFormat::Rgba8, true) => 43,  // VK_FORMAT_R8G8B8A8_SRGB
+            (TextureFormat::Rgba16F, _) => 97,   // VK_FORMAT_R16G16B16A16_SFLOAT
+        }
+    }
+
+    fn level_alignment(format: TextureFormat) -> usize {
+        // Block and texel sizes are 4, 8 or 16 bytes, so the lcm with 4 is the size itself
+        format.block_size().max(4)
+    }
+
+    /// Basic Data Format Descriptor block describing `format`
+    fn data_format_descriptor(format: TextureFormat, srgb: bool) -> Vec<u8> {
+        let (model, samples) = match format {
+            TextureFormat::Bc1 => (DF_MODEL_BC1A, vec![Sample::block(0, 0, 64)]),
+            TextureFormat::Bc3 => (
+                DF_MODEL_BC3,
+                vec![Sample::block(DF_CHANNEL_ALPHA, 0, 64), Sample::block(0, 64, 64)],
+            ),
+            TextureFormat::Bc4 => (DF_MODEL_BC4, vec![Sample::block(0, 0, 64)]),
+            TextureFormat::Bc5 => (
+                DF_MODEL_BC5,
+                vec![Sample::block(0, 0, 64), Sample::block(1, 64, 64)],
+            ),
+            TextureFormat::Bc7 => (DF_MODEL_BC7, vec![Sample::block(0, 0, 128)]),
+            TextureFormat::Rgba8 => (
+                DF_MODEL_RGBSDA,
+                [0, 1, 2, DF_CHANNEL_ALPHA]
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &channel)| Sample {
+                        channel,
+                        qualifiers: 0,
+                        bit_offset: i as u16 * 8,
+                        bit_length: 7,
+                        lower: 0,
+                        upper: 255,
+                    })
+                    .collect(),
+            ),
+            TextureFormat::Rgba16F => (
+                DF_MODEL_RGBSDA,
+                [0, 1, 2, DF_CHANNEL_ALPHA]
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &channel)| Sample {
+                        channel,
+                        qualifiers: DF_QUALIFIER_FLOAT | DF_QUALIFIER_SIGNED,
+                        bit_offset: i as u16 * 16,
+                        bit_length: 15,
+                        lower: (-1.0f32).to_bits(),
+                        upper: 1.0f32.to_bits(),
+                    })
+                    .collect(),
+            ),
+        };
+
+        let transfer = if srgb { DF_TRANSFER_SRGB } else { DF_TRANSFER_LINEAR };
+        let (block_dimension, bytes_plane0) = if format.is_compressed() {
+            (0x0000_0303u32, format.block_size() as u32)
+        } else {
+            (0, format.block_size() as u32)
+        };
+        let block_size = 24 + 16 * samples.len() as u32;
+
+        let mut dfd = Vec::with_capacity(4 + block_size as usize);
+        dfd.extend_from_slice(&(4 + block_size).to_le_bytes()); // dfdTotalSize
+        dfd.extend_from_slice(&0u32.to_le_bytes()); // vendorId = Khronos, descriptorType = basic
+        dfd.extend_from_slice(&(2 | (block_size << 16)).to_le_bytes()); // version 1.3
+        dfd.extend_from_slice(&(model | (DF_PRIMARIES_BT709 << 8) | (transfer << 16)).to_le_bytes());
+        dfd.extend_from_slice(&block_dimension.to_le_bytes());
+        dfd.extend_from_slice(&bytes_plane0.to_le_bytes()); // bytesPlane0-3
+        dfd.extend_from_slice(&0u32.to_le_bytes()); // bytesPlane4-7
+
+        for sample in samples {
+            // Alpha is never sRGB encoded
+            let mut qualifiers = sample.qualifiers;
+            if srgb && sample.channel == DF_CHANNEL_ALPHA {
+                qualifiers |= DF_QUALIFIER_LINEAR;
+            }
+            let word = sample.bit_offset as u32
+                | (sample.bit_length as u32) << 16
+                | ((sample.channel | qualifiers) as u32) << 24;
+            dfd.extend_from_slice(&word.to_le_bytes());
+            dfd.extend_from_slice(&0u32.to_le_bytes()); // samplePosition
+            dfd.extend_from_slice(&sample.lower.to_le_bytes());
+            dfd.extend_from_slice(&sample.upper.to_le_bytes());
+        }
+
+        dfd
+    }
+
+    /// Key/value data with the writer name
+    fn key_value_data() -> Vec<u8> {
+        let mut entry = Vec::new();
+        entry.extend_from_slice(b"KTXwriter\0");
+        entry.extend_from_slice(concat!("Photonic Ring ", env!("CARGO_PKG_VERSION"), "\0").as_bytes());
+
+        let mut kvd = Vec::new();
+        kvd.extend_from_slice(&(entry.len() as u32).to_le_bytes());
+        kvd.extend_from_slice(&entry);
+        kvd.resize(kvd.len().div_ceil(4) * 4, 0);
+        kvd
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn u32_at(data: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+    }
+
+    fn u64_at(data: &[u8], offset: usize) -> usize {
+        u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap()) as usize
+    }
+
+    /// Mip chain with the given level sizes; every level is filled with its level number + 1
+    fn levels(bytes_per_level: &[usize]) -> Vec<Vec<u8>> {
+        bytes_per_level
+            .iter()
+            .enumerate()
+            .map(|(index, &size)| vec![index as u8 + 1; size])
+            .collect()
+    }
+
+    /// Check the level index against the data: aligned, smallest level first, and the
+    /// last level ending at the end of the file.
+    fn check_levels(ktx: &[u8], level_sizes: &[usize], alignment: usize) {
+        let entries: Vec<(usize, usize)> = (0..level_sizes.len())
+            .map(|index| (u64_at(ktx, HEADER_SIZE + index * 24), u64_at(ktx, HEADER_SIZE + index * 24 + 8)))
+            .collect();
+        for (index, (&(offset, length), &size)) in entries.iter().zip(level_sizes).enumerate() {
+            assert_eq!(offset % alignment, 0, "level {} offset {}", index, offset);
+            assert_eq!(length, size);
+            assert_eq!(u64_at(ktx, HEADER_SIZE + index * 24 + 16), size);
+            assert!(ktx[offset..offset + length].iter().all(|&b| b == index as u8 + 1));
+            if index > 0 {
+                assert!(offset < entries[index - 1].0);
+            }
+        }
+        assert_eq!(entries[0].0 + entries[0].1, ktx.len());
+    }
+
+    #[test]
+    fn bc7_header_dfd_and_levels() {
+        let sizes = [64, 16, 16, 16];
+        let ktx = Ktx2Writer::encode(TextureFormat::Bc7, true, 8, 8, &[levels(&sizes)]);
+
+        assert_eq!(ktx[..12], IDENTIFIER);
+        assert_eq!(u32_at(&ktx, 12), 146); // VK_FORMAT_BC7_SRGB_BLOCK
+        assert_eq!(u32_at(&ktx, 16), 1); // typeSize
+        assert_eq!((u32_at(&ktx, 20), u32_at(&ktx, 24)), (8, 8));
+        assert_eq!(u32_at(&ktx, 32), 0); // layerCount
+        assert_eq!(u32_at(&ktx, 36), 1); // faceCount
+        assert_eq!(u32_at(&ktx, 40), 4); // levelCount
+
+        let dfd = u32_at(&ktx, 48) as usize;
+        assert_eq!(dfd, HEADER_SIZE + 4 * 24);
+        assert_eq!(u32_at(&ktx, 52), 44);
+        assert_eq!(u32_at(&ktx, dfd), 44); // dfdTotalSize
+        assert_eq!(u32_at(&ktx, dfd + 12), DF_MODEL_BC7 | DF_PRIMARIES_BT709 << 8 | DF_TRANSFER_SRGB << 16);
+        assert_eq!(u32_at(&ktx, dfd + 16), 0x0303); // 4x4 texel blocks
+        assert_eq!(u32_at(&ktx, dfd + 20), 16); // bytesPlane0
+        assert_eq!(u32_at(&ktx, dfd + 28) >> 16 & 0xff, 127); // one 128-bit sample
+
+        check_levels(&ktx, &sizes, 16);
+    }
+
+    #[test]
+    fn rgba16f_header_dfd_and_levels() {
+        let sizes = [512, 128, 32, 8];
+        // Half floats have no sRGB variant, so the request is ignored
+        let ktx = Ktx2Writer::encode(TextureFormat::Rgba16F, true, 8, 8, &[levels(&sizes)]);
+
+        assert_eq!(u32_at(&ktx, 12), 97); // VK_FORMAT_R16G16B16A16_SFLOAT
+        assert_eq!(u32_at(&ktx, 16), 2); // typeSize
+
+        let dfd = u32_at(&ktx, 48) as usize;
+        assert_eq!(u32_at(&ktx, 52), 92);
+        assert_eq!(u32_at(&ktx, dfd + 12), DF_MODEL_RGBSDA | DF_PRIMARIES_BT709 << 8 | DF_TRANSFER_LINEAR << 16);
+        assert_eq!(u32_at(&ktx, dfd + 16), 0);
+        assert_eq!(u32_at(&ktx, dfd + 20), 8);
+        for (index, channel) in [0, 1, 2, DF_CHANNEL_ALPHA].into_iter().enumerate() {
+            let word = u32_at(&ktx, dfd + 28 + index * 16);
+            assert_eq!(word & 0xffff, index as u32 * 16); // bitOffset
+            assert_eq!(word >> 16 & 0xff, 15); // bitLength - 1
+            assert_eq!(word >> 24, (channel | DF_QUALIFIER_FLOAT | DF_QUALIFIER_SIGNED) as u32);
+        }
+
+        check_levels(&ktx, &sizes, 8);
+    }
+
+    #[test]
+    fn array_layers_share_levels() {
+        let first = vec![vec![1u8; 64], vec![2u8; 16]];
+        let second = vec![vec![1u8; 64], vec![2u8; 16]];
+        let ktx = Ktx2Writer::encode(TextureFormat::Rgba8, false, 4, 4, &[first, second]);
+
+        assert_eq!(u32_at(&ktx, 12), 37); // VK_FORMAT_R8G8B8A8_UNORM
+        assert_eq!(u32_at(&ktx, 32), 2); // layerCount
+        check_levels(&ktx, &[128, 32], 4);
+    }
+}
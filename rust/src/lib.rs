@@ -1,9 +1,28 @@
 use godot::prelude::*;
 
+mod bc7;
 mod channel_packer;
+mod color_management;
+mod dds_reader;
+mod delight;
+mod fft;
+mod height_from_normal;
 mod height_map;
+mod image_converter;
+mod ktx2;
+mod material_segmentation;
+mod mipmap;
+mod normal_analysis;
+mod normal_blend;
 mod normal_map;
+mod openexr;
+mod output_config;
+mod packing_preset;
+mod photometric_stereo;
 mod roughness_map;
+mod specular_gloss;
+mod surface_integration;
+mod terrain3d_asset;
 mod texture_generator;
 
 struct PhotonicRingExtension;
@@ -0,0 +1,455 @@
+use image::{DynamicImage, GrayImage, ImageBuffer, Rgb, RgbImage};
+use rayon::prelude::*;
+
+use crate::color_management::TransferFunction;
+use crate::openexr::GrayF32Image;
+
+/// One material region of a segmentation.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MaterialCluster {
+    /// Mean colour, encoded like the albedo
+    pub color: [f32; 3],
+    /// Share of the texture covered, 0.0-1.0
+    pub coverage: f32,
+}
+
+/// Per-texel material labels.
+pub struct Segmentation {
+    pub width: u32,
+    pub height: u32,
+    /// Index into `materials` for every texel, row-major
+    pub labels: Vec<u8>,
+    /// Ordered from darkest to brightest
+    pub materials: Vec<MaterialCluster>,
+}
+
+/// Material regions of an albedo and per-material roughness
+/// Regions come from k-means on linear colour plus local luminance variation, or
+/// from the distinct colours of an ID mask. Both order materials from dark to bright,
+/// so a range list lines up with either source.
+pub struct MaterialSegmenter;
+
+impl MaterialSegmenter {
+    pub const MAX_MATERIALS: usize = 16;
+    /// Texels used to fit the cluster centres
+    const SAMPLE_COUNT: usize = 65536;
+    const ITERATIONS: usize = 25;
+    /// Weight of local luminance deviation against colour in the cluster features
+    const VARIATION_WEIGHT: f32 = 3.0;
+    /// Half width of a default roughness range around a material's mean detail
+    const DEFAULT_SPREAD: f32 = 0.2;
+
+    /// Split the albedo into `count` material regions.
+    pub fn cluster(albedo: &DynamicImage, transfer: TransferFunction, count: usize) -> Result<Segmentation, String> {
+        if !(1..=Self::MAX_MATERIALS).contains(&count) {
+            return Err(format!(
+                "Material count must be between 1 and {}, got {}",
+                Self::MAX_MATERIALS,
+                count
+            ));
+        }
+        let (width, height) = (albedo.width(), albedo.height());
+        if width == 0 || height == 0 {
+            return Err("Cannot segment an empty image".to_string());
+        }
+        let features = Self::features(albedo, transfer);
+
+        let stride = (features.len() / Self::SAMPLE_COUNT).max(1);
+        let samples: Vec<[f32; 4]> = features.iter().step_by(stride).copied().collect();
+        let centres = Self::fit(&samples, count.min(samples.len()));
+
+        let labels: Vec<u8> = features.par_iter().map(|feature| Self::nearest(feature, &centres) as u8).collect();
+        Ok(Self::ordered(width, height, labels, &features, transfer, centres.len()))
+    }
+
+    /// Use the distinct colours of an ID mask as material regions (resized to `width` x `height` if needed).
+    pub fn from_mask(mask: &DynamicImage, width: u32, height: u32) -> Result<Segmentation, String> {
+        let mask = if (mask.width(), mask.height()) == (width, height) {
+            mask.to_rgb8()
+        } else {
+            mask.resize_exact(width, height, image::imageops::FilterType::Nearest).to_rgb8()
+        };
+
+        let mut colours: Vec<[u8; 3]> = Vec::new();
+        let mut labels = Vec::with_capacity((width * height) as usize);
+        for pixel in mask.pixels() {
+            let index = match colours.iter().position(|colour| *colour == pixel.0) {
+                Some(index) => index,
+                None => {
+                    if colours.len() == Self::MAX_MATERIALS {
+                        return Err(format!(
+                            "ID mask has more than {} colours",
+                            Self::MAX_MATERIALS
+                        ));
+                    }
+                    colours.push(pixel.0);
+                    colours.len() - 1
+                }
+            };
+            labels.push(index as u8);
+        }
+
+        // Order by the mask colours themselves, so a preview fed back in keeps its order
+        let brightness = |[r, g, b]: [u8; 3]| 0.2126 * r as f32 + 0.7152 * g as f32 + 0.0722 * b as f32;
+        let mut order: Vec<usize> = (0..colours.len()).collect();
+        order.sort_by(|&a, &b| brightness(colours[a]).total_cmp(&brightness(colours[b])));
+        let mut rank = vec![0u8; colours.len()];
+        for (position, &index) in order.iter().enumerate() {
+            rank[index] = position as u8;
+        }
+        labels.iter_mut().for_each(|label| *label = rank[*label as usize]);
+
+        let coverage = Self::coverage(&labels, colours.len());
+        let materials = order
+            .iter()
+            .zip(coverage)
+            .map(|(&index, coverage)| MaterialCluster {
+                color: colours[index].map(|value| value as f32 / 255.0),
+                coverage,
+            })
+            .collect();
+        Ok(Segmentation { width, height, labels, materials })
+    }
+
+    /// Remap per-texel detail roughness (0.0-1.0) into each material's range.
+    /// Materials without a range get one centred on their mean detail.
+    /// `blend_radius` (pixels) feathers the transition between materials.
+    /// Returns the roughness map and the ranges used.
+    pub fn roughness(
+        segmentation: &Segmentation,
+        detail: &DynamicImage,
+        ranges: &[(f32, f32)],
+        blend_radius: f32,
+    ) -> (DynamicImage, Vec<(f32, f32)>) {
+        let (width, height) = (segmentation.width, segmentation.height);
+        let detail = detail.to_luma32f();
+        let count = segmentation.materials.len();
+
+        let mut sums = vec![(0.0f64, 0usize); count];
+        for (label, pixel) in segmentation.labels.iter().zip(detail.pixels()) {
+            sums[*label as usize].0 += pixel[0] as f64;
+            sums[*label as usize].1 += 1;
+        }
+        let ranges: Vec<(f32, f32)> = (0..count)
+            .map(|index| match ranges.get(index) {
+                Some(&(min, max)) => (min.clamp(0.0, 1.0), max.clamp(0.0, 1.0)),
+                None => {
+                    let (sum, texels) = sums[index];
+                    let mean = (sum / texels.max(1) as f64) as f32;
+                    (
+                        (mean - Self::DEFAULT_SPREAD).clamp(0.0, 1.0),
+                        (mean + Self::DEFAULT_SPREAD).clamp(0.0, 1.0),
+                    )
+                }
+            })
+            .collect();
+
+        let value_at = |index: usize, t: f32| {
+            let (min, max) = ranges[index];
+            min + (max - min) * t
+        };
+        let mut roughness: GrayImage = ImageBuffer::new(width, height);
+        if blend_radius <= 0.0 {
+            roughness.par_chunks_mut(width as usize).enumerate().for_each(|(y, row)| {
+                for (x, pixel) in row.iter_mut().enumerate() {
+                    let index = y * width as usize + x;
+                    let t = detail.as_raw()[index].clamp(0.0, 1.0);
+                    *pixel = (value_at(segmentation.labels[index] as usize, t).clamp(0.0, 1.0) * 255.0).round() as u8;
+                }
+            });
+            return (DynamicImage::ImageLuma8(roughness), ranges);
+        }
+
+        // Soft membership of every material, so ranges cross-fade at boundaries.
+        // Built from block averages and blurred at about 2 pixels per sigma to stay cheap.
+        let block = (blend_radius / 2.0).floor().max(1.0) as u32;
+        let (small_width, small_height) = (width.div_ceil(block), height.div_ceil(block));
+        let mut memberships = vec![GrayF32Image::new(small_width, small_height); count];
+        for (index, &label) in segmentation.labels.iter().enumerate() {
+            let (x, y) = (index as u32 % width, index as u32 / width);
+            memberships[label as usize].get_pixel_mut(x / block, y / block)[0] += 1.0;
+        }
+        let memberships: Vec<GrayF32Image> = memberships
+            .into_par_iter()
+            .map(|membership| imageproc::filter::gaussian_blur_f32(&membership, (blend_radius / block as f32).max(0.5)))
+            .collect();
+
+        roughness.par_chunks_mut(width as usize).enumerate().for_each(|(y, row)| {
+            let sy = ((y as f32 + 0.5) / block as f32 - 0.5).clamp(0.0, (small_height - 1) as f32);
+            let (y0, fy) = (sy.floor() as u32, sy.fract());
+            let y1 = (y0 + 1).min(small_height - 1);
+            for (x, pixel) in row.iter_mut().enumerate() {
+                let sx = ((x as f32 + 0.5) / block as f32 - 0.5).clamp(0.0, (small_width - 1) as f32);
+                let (x0, fx) = (sx.floor() as u32, sx.fract());
+                let x1 = (x0 + 1).min(small_width - 1);
+
+                let index = y * width as usize + x;
+                let t = detail.as_raw()[index].clamp(0.0, 1.0);
+                let (mut value, mut weight) = (0.0f32, 0.0f32);
+                for (material, membership) in memberships.iter().enumerate() {
+                    let at = |x: u32, y: u32| membership.get_pixel(x, y)[0];
+                    let top = at(x0, y0) + (at(x1, y0) - at(x0, y0)) * fx;
+                    let bottom = at(x0, y1) + (at(x1, y1) - at(x0, y1)) * fx;
+                    let w = (top + (bottom - top) * fy).max(0.0);
+                    value += w * value_at(material, t);
+                    weight += w;
+                }
+                let value = if weight > 1e-6 {
+                    value / weight
+                } else {
+                    value_at(segmentation.labels[index] as usize, t)
+                };
+                *pixel = (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+            }
+        });
+
+        (DynamicImage::ImageLuma8(roughness), ranges)
+    }
+
+    /// Each texel painted in its material's colour; usable as an ID mask.
+    pub fn preview(segmentation: &Segmentation) -> DynamicImage {
+        let mut colours: Vec<Rgb<u8>> = Vec::with_capacity(segmentation.materials.len());
+        for material in &segmentation.materials {
+            let mut colour = Rgb(material.color.map(|value| (value.clamp(0.0, 1.0) * 255.0).round() as u8));
+            // Materials told apart by texture alone can share a mean colour. Brightening the
+            // later one keeps the dark-to-bright order `from_mask` reads back.
+            while colours.contains(&colour) {
+                match [2, 0, 1].into_iter().find(|&channel| colour[channel] < 255) {
+                    Some(channel) => colour[channel] += 1,
+                    None => break,
+                }
+            }
+            colours.push(colour);
+        }
+        let (width, height) = (segmentation.width, segmentation.height);
+        DynamicImage::ImageRgb8(RgbImage::from_fn(width, height, |x, y| {
+            colours[segmentation.labels[(y * width + x) as usize] as usize]
+        }))
+    }
+
+    /// Linear colour and scaled local luminance deviation (7x7 window) per texel
+    fn features(albedo: &DynamicImage, transfer: TransferFunction) -> Vec<[f32; 4]> {
+        let rgb = albedo.to_rgb32f();
+        let (width, height) = (rgb.width() as usize, rgb.height() as usize);
+        let linear: Vec<[f32; 3]> = rgb.pixels().map(|p| p.0.map(|value| transfer.to_linear(value))).collect();
+        let luminance: Vec<f64> = linear
+            .iter()
+            .map(|[r, g, b]| (0.2126 * r + 0.7152 * g + 0.0722 * b) as f64)
+            .collect();
+
+        // Summed-area tables of luminance and its square
+        let stride = width + 1;
+        let mut sum = vec![0.0f64; stride * (height + 1)];
+        let mut sum_sq = sum.clone();
+        for y in 0..height {
+            for x in 0..width {
+                let value = luminance[y * width + x];
+                let i = (y + 1) * stride + x + 1;
+                sum[i] = value + sum[i - 1] + sum[i - stride] - sum[i - stride - 1];
+                sum_sq[i] = value * value + sum_sq[i - 1] + sum_sq[i - stride] - sum_sq[i - stride - 1];
+            }
+        }
+
+        const RADIUS: usize = 3;
+        (0..width * height)
+            .into_par_iter()
+            .map(|index| {
+                let (x, y) = (index % width, index / width);
+                let (x0, x1) = (x.saturating_sub(RADIUS), (x + RADIUS + 1).min(width));
+                let (y0, y1) = (y.saturating_sub(RADIUS), (y + RADIUS + 1).min(height));
+                let area = |table: &[f64]| {
+                    table[y1 * stride + x1] - table[y0 * stride + x1] - table[y1 * stride + x0] + table[y0 * stride + x0]
+                };
+                let texels = ((x1 - x0) * (y1 - y0)) as f64;
+                let mean = area(&sum) / texels;
+                let deviation = (area(&sum_sq) / texels - mean * mean).max(0.0).sqrt() as f32;
+                let [r, g, b] = linear[index];
+                [r, g, b, deviation * Self::VARIATION_WEIGHT]
+            })
+            .collect()
+    }
+
+    /// k-means++ seeding (fixed seed, so results are repeatable) and Lloyd iterations
+    fn fit(samples: &[[f32; 4]], count: usize) -> Vec<[f32; 4]> {
+        let mut state = 0x9E37_79B9_7F4A_7C15u64;
+        let mut random = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state >> 11) as f64 / (1u64 << 53) as f64
+        };
+
+        let mut centres = vec![samples[(random() * samples.len() as f64) as usize % samples.len()]];
+        while centres.len() < count {
+            let distances: Vec<f64> = samples
+                .par_iter()
+                .map(|sample| Self::distance(sample, &centres[Self::nearest(sample, &centres)]) as f64)
+                .collect();
+            let total: f64 = distances.iter().sum();
+            if total <= 0.0 {
+                break;
+            }
+            let mut target = random() * total;
+            let chosen = distances
+                .iter()
+                .position(|&distance| {
+                    target -= distance;
+                    target <= 0.0
+                })
+                .unwrap_or(samples.len() - 1);
+            centres.push(samples[chosen]);
+        }
+
+        for _ in 0..Self::ITERATIONS {
+            let labels: Vec<usize> = samples.par_iter().map(|sample| Self::nearest(sample, &centres)).collect();
+            let mut sums = vec![([0.0f64; 4], 0usize); centres.len()];
+            for (sample, &label) in samples.iter().zip(&labels) {
+                for (total, value) in sums[label].0.iter_mut().zip(sample) {
+                    *total += *value as f64;
+                }
+                sums[label].1 += 1;
+            }
+
+            let mut moved = false;
+            for (centre, (total, members)) in centres.iter_mut().zip(&sums) {
+                if *members == 0 {
+                    continue;
+                }
+                let updated = total.map(|value| (value / *members as f64) as f32);
+                moved |= Self::distance(centre, &updated) > 1e-8;
+                *centre = updated;
+            }
+            if !moved {
+                break;
+            }
+        }
+        centres
+    }
+
+    fn nearest(feature: &[f32; 4], centres: &[[f32; 4]]) -> usize {
+        centres
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| Self::distance(feature, a).total_cmp(&Self::distance(feature, b)))
+            .map_or(0, |(index, _)| index)
+    }
+
+    fn distance(a: &[f32; 4], b: &[f32; 4]) -> f32 {
+        a.iter().zip(b).map(|(a, b)| (a - b) * (a - b)).sum()
+    }
+
+    /// Materials with their mean colour, reordered from dark to bright (empty clusters dropped)
+    fn ordered(
+        width: u32,
+        height: u32,
+        labels: Vec<u8>,
+        features: &[[f32; 4]],
+        transfer: TransferFunction,
+        count: usize,
+    ) -> Segmentation {
+        let mut sums = vec![[0.0f64; 3]; count];
+        for (feature, &label) in features.iter().zip(&labels) {
+            for (total, value) in sums[label as usize].iter_mut().zip(feature) {
+                *total += *value as f64;
+            }
+        }
+        let coverage = Self::coverage(&labels, count);
+        let texels = labels.len() as f64;
+        let linear: Vec<[f32; 3]> = sums
+            .iter()
+            .zip(&coverage)
+            .map(|(total, &share)| total.map(|value| (value / (share as f64 * texels).max(1.0)) as f32))
+            .collect();
+
+        let brightness = |[r, g, b]: [f32; 3]| 0.2126 * r + 0.7152 * g + 0.0722 * b;
+        let mut order: Vec<usize> = (0..count).filter(|&i| coverage[i] > 0.0).collect();
+        order.sort_by(|&a, &b| brightness(linear[a]).total_cmp(&brightness(linear[b])));
+
+        let mut rank = vec![0u8; count];
+        for (position, &index) in order.iter().enumerate() {
+            rank[index] = position as u8;
+        }
+        Segmentation {
+            width,
+            height,
+            labels: labels.iter().map(|&label| rank[label as usize]).collect(),
+            materials: order
+                .iter()
+                .map(|&index| MaterialCluster {
+                    color: linear[index].map(|value| transfer.encode(value)),
+                    coverage: coverage[index],
+                })
+                .collect(),
+        }
+    }
+
+    /// Share of texels per label
+    fn coverage(labels: &[u8], count: usize) -> Vec<f32> {
+        let mut texels = vec![0usize; count];
+        labels.iter().for_each(|&label| texels[label as usize] += 1);
+        let total = labels.len().max(1) as f32;
+        texels.iter().map(|&count| count as f32 / total).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DARK: [u8; 3] = [30, 40, 90];
+    const BRIGHT: [u8; 3] = [200, 60, 40];
+
+    /// Bright left half, dark right half
+    fn two_colour_albedo() -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(32, 32, |x, _| Rgb(if x < 16 { BRIGHT } else { DARK })))
+    }
+
+    #[test]
+    fn two_colours_split_into_ordered_clusters() {
+        let segmentation = MaterialSegmenter::cluster(&two_colour_albedo(), TransferFunction::Srgb, 2).unwrap();
+
+        assert_eq!(segmentation.materials.len(), 2);
+        let expected = [DARK, BRIGHT];
+        for (material, colour) in segmentation.materials.iter().zip(expected) {
+            for (mean, value) in material.color.iter().zip(colour) {
+                assert!((mean * 255.0 - value as f32).abs() < 8.0, "{:?} vs {:?}", material.color, colour);
+            }
+            assert!((material.coverage - 0.5).abs() < 0.05, "coverage {}", material.coverage);
+        }
+        // Away from the edge, where the variation feature is high, every texel is labelled by colour
+        for y in 0..32 {
+            for x in (0..12).chain(20..32) {
+                let label = segmentation.labels[y * 32 + x];
+                assert_eq!(label, if x < 16 { 1 } else { 0 }, "texel {}, {}", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn preview_keeps_order_through_from_mask() {
+        let clustered = MaterialSegmenter::cluster(&two_colour_albedo(), TransferFunction::Srgb, 2).unwrap();
+        // Two materials sharing a mean colour with blue already at full intensity
+        let shared = Segmentation {
+            width: 4,
+            height: 2,
+            labels: vec![0, 1, 2, 3, 3, 2, 1, 0],
+            materials: [[0.2, 0.2, 0.2], [0.5, 0.5, 1.0], [0.5, 0.5, 1.0], [1.0, 1.0, 1.0]]
+                .into_iter()
+                .map(|color| MaterialCluster { color, coverage: 0.25 })
+                .collect(),
+        };
+
+        for segmentation in [clustered, shared] {
+            let preview = MaterialSegmenter::preview(&segmentation);
+            let mask = MaterialSegmenter::from_mask(&preview, segmentation.width, segmentation.height).unwrap();
+            assert_eq!(mask.materials.len(), segmentation.materials.len());
+            assert_eq!(mask.labels, segmentation.labels);
+        }
+    }
+
+    #[test]
+    fn empty_image_is_an_error() {
+        let empty = DynamicImage::ImageRgb8(RgbImage::new(0, 0));
+        assert!(MaterialSegmenter::cluster(&empty, TransferFunction::Srgb, 2).is_err());
+    }
+}
@@ -0,0 +1,300 @@
+use image::{Rgba, RgbaImage};
+use rayon::prelude::*;
+
+/// Downsampling filter used to build mip levels.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MipFilter {
+    /// 2x2 average - fastest, slightly blurry
+    Box,
+    /// Kaiser-windowed sinc - sharp with little ringing (NVTT default)
+    Kaiser,
+    /// Lanczos3 - sharpest, may ring on hard edges
+    Lanczos,
+}
+
+impl MipFilter {
+    /// Parse a filter name as passed from GDScript.
+    /// Returns `None` for "none" (mipmaps disabled).
+    pub fn from_name(name: &str) -> Result<Option<Self>, String> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "" | "none" => Ok(None),
+            "box" => Ok(Some(MipFilter::Box)),
+            "kaiser" => Ok(Some(MipFilter::Kaiser)),
+            "lanczos" => Ok(Some(MipFilter::Lanczos)),
+            other => Err(format!(
+                "Unknown mip filter '{}' (expected none, box, kaiser or lanczos)",
+                other
+            )),
+        }
+    }
+
+    /// Kernel support radius in destination pixels
+    fn support(self) -> f32 {
+        match self {
+            MipFilter::Box => 0.5,
+            MipFilter::Kaiser => 3.0,
+            MipFilter::Lanczos => 3.0,
+        }
+    }
+
+    fn weight(self, x: f32) -> f32 {
+        let x = x.abs();
+        match self {
+            MipFilter::Box => {
+                if x <= 0.5 {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            MipFilter::Kaiser => {
+                const ALPHA: f32 = 4.0;
+                let support = self.support();
+                if x >= support {
+                    return 0.0;
+                }
+                let t = x / support;
+                sinc(x) * bessel_i0(ALPHA * (1.0 - t * t).sqrt()) / bessel_i0(ALPHA)
+            }
+            MipFilter::Lanczos => {
+                let support = self.support();
+                if x >= support {
+                    0.0
+                } else {
+                    sinc(x) * sinc(x / support)
+                }
+            }
+        }
+    }
+}
+
+/// How the RGB channels of a texture are interpreted while filtering.
+/// Alpha is always filtered as linear data.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextureContent {
+    /// sRGB-encoded colour - filtered in linear light (albedo)
+    Color,
+    /// Tangent-space normal - vectors are renormalized after filtering
+    Normal,
+    /// Linear data (masks, height, roughness)
+    Linear,
+}
+
+/// Mip chain generator for packed textures
+pub struct MipmapGenerator;
+
+impl MipmapGenerator {
+    /// Build the full mip chain for `base`.
+    /// The returned vector starts with a copy of `base` (level 0) and ends with the 1x1 level.
+    pub fn generate_chain(
+        base: &RgbaImage,
+        filter: MipFilter,
+        content: TextureContent,
+    ) -> Vec<RgbaImage> {
+        let mut levels = vec![base.clone()];
+
+        // Filter every level from the previous one in float to avoid accumulating
+        // 8-bit rounding errors down the chain.
+        let mut width = base.width();
+        let mut height = base.height();
+        let mut current = Self::decode(base, content);
+
+        while width > 1 || height > 1 {
+            let next_width = (width / 2).max(1);
+            let next_height = (height / 2).max(1);
+
+            let horizontal = Self::resample_rows(&current, width, height, next_width, filter);
+            current = Self::resample_columns(&horizontal, next_width, height, next_height, filter);
+            width = next_width;
+            height = next_height;
+
+            levels.push(Self::encode(&current, width, height, content));
+        }
+
+        levels
+    }
+
+    /// Convert to float working space (linear light for colour, [-1, 1] for normals)
+    fn decode(image: &RgbaImage, content: TextureContent) -> Vec<[f32; 4]> {
+        image
+            .pixels()
+            .map(|p| {
+                let a = p[3] as f32 / 255.0;
+                match content {
+                    TextureContent::Color => [
+                        srgb_to_linear(p[0] as f32 / 255.0),
+                        srgb_to_linear(p[1] as f32 / 255.0),
+                        srgb_to_linear(p[2] as f32 / 255.0),
+                        a,
+                    ],
+                    TextureContent::Normal => [
+                        p[0] as f32 / 255.0 * 2.0 - 1.0,
+                        p[1] as f32 / 255.0 * 2.0 - 1.0,
+                        p[2] as f32 / 255.0 * 2.0 - 1.0,
+                        a,
+                    ],
+                    TextureContent::Linear => [
+                        p[0] as f32 / 255.0,
+                        p[1] as f32 / 255.0,
+                        p[2] as f32 / 255.0,
+                        a,
+                    ],
+                }
+            })
+            .collect()
+    }
+
+    fn encode(pixels: &[[f32; 4]], width: u32, height: u32, content: TextureContent) -> RgbaImage {
+        let to_u8 = |v: f32| (v.clamp(0.0, 1.0) * 255.0 + 0.5) as u8;
+
+        let mut output = RgbaImage::new(width, height);
+        for (pixel, value) in output.pixels_mut().zip(pixels) {
+            let [r, g, b, a] = *value;
+            let rgb = match content {
+                TextureContent::Color => [linear_to_srgb(r), linear_to_srgb(g), linear_to_srgb(b)],
+                TextureContent::Normal => {
+                    let length = (r * r + g * g + b * b).sqrt();
+                    let (x, y, z) = if length > 1e-6 {
+                        (r / length, g / length, b / length)
+                    } else {
+                        (0.0, 0.0, 1.0)
+                    };
+                    [x * 0.5 + 0.5, y * 0.5 + 0.5, z * 0.5 + 0.5]
+                }
+                TextureContent::Linear => [r, g, b],
+            };
+            *pixel = Rgba([to_u8(rgb[0]), to_u8(rgb[1]), to_u8(rgb[2]), to_u8(a)]);
+        }
+        output
+    }
+
+    /// Horizontal pass: `src_width` -> `dst_width`
+    fn resample_rows(
+        pixels: &[[f32; 4]],
+        src_width: u32,
+        height: u32,
+        dst_width: u32,
+        filter: MipFilter,
+    ) -> Vec<[f32; 4]> {
+        let taps = Self::taps(src_width, dst_width, filter);
+        let mut output = vec![[0.0f32; 4]; (dst_width * height) as usize];
+
+        output
+            .par_chunks_mut(dst_width as usize)
+            .enumerate()
+            .for_each(|(y, row)| {
+                let src_row = &pixels[y * src_width as usize..(y + 1) * src_width as usize];
+                for (x, out) in row.iter_mut().enumerate() {
+                    *out = Self::apply(&taps[x], |i| src_row[i]);
+                }
+            });
+
+        output
+    }
+
+    /// Vertical pass: `src_height` -> `dst_height`
+    fn resample_columns(
+        pixels: &[[f32; 4]],
+        width: u32,
+        src_height: u32,
+        dst_height: u32,
+        filter: MipFilter,
+    ) -> Vec<[f32; 4]> {
+        let taps = Self::taps(src_height, dst_height, filter);
+        let mut output = vec![[0.0f32; 4]; (width * dst_height) as usize];
+
+        output
+            .par_chunks_mut(width as usize)
+            .enumerate()
+            .for_each(|(y, row)| {
+                for (x, out) in row.iter_mut().enumerate() {
+                    *out = Self::apply(&taps[y], |i| pixels[i * width as usize + x]);
+                }
+            });
+
+        output
+    }
+
+    fn apply(taps: &[(usize, f32)], sample: impl Fn(usize) -> [f32; 4]) -> [f32; 4] {
+        let mut sum = [0.0f32; 4];
+        for &(index, weight) in taps {
+            let value = sample(index);
+            for c in 0..4 {
+                sum[c] += value[c] * weight;
+            }
+        }
+        sum
+    }
+
+    /// Precompute normalized (source index, weight) pairs for each destination pixel.
+    /// Indices wrap around since terrain textures tile.
+    fn taps(src_size: u32, dst_size: u32, filter: MipFilter) -> Vec<Vec<(usize, f32)>> {
+        let scale = src_size as f32 / dst_size as f32;
+        let radius = filter.support() * scale;
+
+        (0..dst_size)
+            .map(|d| {
+                let center = (d as f32 + 0.5) * scale;
+                let first = (center - radius).floor() as i64;
+                let last = (center + radius).ceil() as i64;
+
+                let mut taps: Vec<(usize, f32)> = (first..last)
+                    .filter_map(|s| {
+                        let weight = filter.weight((s as f32 + 0.5 - center) / scale);
+                        (weight != 0.0)
+                            .then(|| (s.rem_euclid(src_size as i64) as usize, weight))
+                    })
+                    .collect();
+
+                let total: f32 = taps.iter().map(|(_, w)| w).sum();
+                if total.abs() > f32::EPSILON {
+                    for tap in &mut taps {
+                        tap.1 /= total;
+                    }
+                }
+                taps
+            })
+            .collect()
+    }
+}
+
+fn sinc(x: f32) -> f32 {
+    if x.abs() < 1e-6 {
+        1.0
+    } else {
+        let px = std::f32::consts::PI * x;
+        px.sin() / px
+    }
+}
+
+/// Zeroth-order modified Bessel function of the first kind (power series)
+fn bessel_i0(x: f32) -> f32 {
+    let mut sum = 1.0f32;
+    let mut term = 1.0f32;
+    let half_x_sq = x * x / 4.0;
+    for k in 1..32 {
+        term *= half_x_sq / (k * k) as f32;
+        sum += term;
+        if term < sum * 1e-8 {
+            break;
+        }
+    }
+    sum
+}
+
+pub fn srgb_to_linear(v: f32) -> f32 {
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+pub fn linear_to_srgb(v: f32) -> f32 {
+    let v = v.max(0.0);
+    if v <= 0.0031308 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    }
+}
@@ -0,0 +1,238 @@
+use image::{DynamicImage, Rgb32FImage};
+use rayon::prelude::*;
+
+use crate::height_from_normal::NormalConvention;
+use crate::image_converter::{BitDepth, ImageConverter};
+
+/// Findings of `NormalAnalyzer::analyze`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NormalReport {
+    /// Mean length of the decoded vectors (1.0 for a clean map)
+    pub average_length: f32,
+    /// Share of texels pointing into the surface (Z below 0), 0-100
+    pub z_violation_percent: f32,
+    /// Share of texels with a length off by more than 10% or a negative Z, 0-100
+    pub invalid_percent: f32,
+    /// The blue channel is constant (two-channel maps such as unpacked BC5)
+    pub missing_z: bool,
+    /// Most texels point into the surface
+    pub inverted_z: bool,
+    /// `None` when the surface is too flat or noisy to tell
+    pub convention: Option<NormalConvention>,
+    /// 0 (no preference) to 1 (only one convention describes a real surface)
+    pub convention_confidence: f32,
+    /// Share of empty levels inside the used X/Y range (quantization or compression banding), 0-1
+    pub banding: f32,
+}
+
+/// Fixes applied by `NormalAnalyzer::repair`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RepairOptions {
+    /// Recompute Z from X and Y instead of renormalising the stored vector
+    pub reconstruct_z: bool,
+    pub flip_y: bool,
+}
+
+/// Validation and repair of tangent-space normal maps
+pub struct NormalAnalyzer;
+
+impl NormalAnalyzer {
+    /// Convention confidence below this leaves the convention undetermined
+    const MIN_CONFIDENCE: f32 = 0.1;
+
+    pub fn analyze(normal_map: &DynamicImage) -> NormalReport {
+        let rgb = normal_map.to_rgb32f();
+        let (width, height) = rgb.dimensions();
+        let count = (width * height).max(1) as f32;
+        let decode = |x: u32, y: u32| rgb.get_pixel(x, y).0.map(|value| value * 2.0 - 1.0);
+
+        let (length_sum, z_violations, invalid) = rgb
+            .as_raw()
+            .par_chunks(3)
+            .map(|pixel| {
+                let [x, y, z] = [pixel[0], pixel[1], pixel[2]].map(|value| value * 2.0 - 1.0);
+                let length = (x * x + y * y + z * z).sqrt();
+                let invalid = (length - 1.0).abs() > 0.1 || z < 0.0;
+                (length as f64, (z < 0.0) as u32, invalid as u32)
+            })
+            .reduce(|| (0.0, 0, 0), |a, b| (a.0 + b.0, a.1 + b.1, a.2 + b.2));
+
+        let first_blue = rgb.get_pixel(0, 0)[2];
+        let missing_z = rgb.pixels().all(|pixel| (pixel[2] - first_blue).abs() < 1e-4);
+
+        // Slopes of a real surface are curl-free; the wrong Y convention breaks that
+        let slopes = |x: u32, y: u32| {
+            let [nx, ny, nz] = decode(x, y);
+            let nz = if missing_z { (1.0 - nx * nx - ny * ny).max(0.0).sqrt() } else { nz.abs() };
+            let nz = nz.max(0.05);
+            (-nx / nz, ny / nz)
+        };
+        let (opengl_curl, directx_curl) = (1..height.saturating_sub(1))
+            .into_par_iter()
+            .map(|y| {
+                let mut sums = (0.0f64, 0.0f64);
+                for x in 1..width.saturating_sub(1) {
+                    let dp_dy = (slopes(x, y + 1).0 - slopes(x, y - 1).0) * 0.5;
+                    let dq_dx = (slopes(x + 1, y).1 - slopes(x - 1, y).1) * 0.5;
+                    sums.0 += (dp_dy - dq_dx).abs() as f64;
+                    sums.1 += (dp_dy + dq_dx).abs() as f64;
+                }
+                sums
+            })
+            .reduce(|| (0.0, 0.0), |a, b| (a.0 + b.0, a.1 + b.1));
+        let larger = opengl_curl.max(directx_curl);
+        let convention_confidence = if larger > 0.0 {
+            (1.0 - opengl_curl.min(directx_curl) / larger) as f32
+        } else {
+            0.0
+        };
+        let convention = (convention_confidence >= Self::MIN_CONFIDENCE).then_some(if opengl_curl <= directx_curl {
+            NormalConvention::OpenGl
+        } else {
+            NormalConvention::DirectX
+        });
+
+        NormalReport {
+            average_length: (length_sum / count as f64) as f32,
+            z_violation_percent: z_violations as f32 / count * 100.0,
+            invalid_percent: invalid as f32 / count * 100.0,
+            missing_z,
+            inverted_z: z_violations as f32 > count * 0.5 && !missing_z,
+            convention,
+            convention_confidence,
+            banding: Self::banding(&rgb),
+        }
+    }
+
+    /// Repaired copy at the source's bit depth (RGB).
+    pub fn repair(normal_map: &DynamicImage, options: &RepairOptions) -> DynamicImage {
+        let mut rgb: Rgb32FImage = normal_map.to_rgb32f();
+        rgb.par_chunks_mut(3).for_each(|pixel| {
+            let [x, y, z] = [pixel[0], pixel[1], pixel[2]].map(|value| value * 2.0 - 1.0);
+            let y = if options.flip_y { -y } else { y };
+            let [x, y, z] = if options.reconstruct_z {
+                let length = (x * x + y * y).sqrt();
+                let (x, y) = if length > 1.0 { (x / length, y / length) } else { (x, y) };
+                [x, y, (1.0 - x * x - y * y).max(0.0).sqrt()]
+            } else {
+                let length = (x * x + y * y + z * z).sqrt();
+                if length > 1e-6 {
+                    [x / length, y / length, z / length]
+                } else {
+                    [0.0, 0.0, 1.0]
+                }
+            };
+            pixel.copy_from_slice(&[x, y, z].map(|value| value * 0.5 + 0.5));
+        });
+
+        let depth = BitDepth::of(normal_map.color());
+        ImageConverter::with_depth(&DynamicImage::ImageRgb32F(rgb), depth)
+    }
+
+    /// Empty 8-bit levels between the lowest and highest used X and Y values
+    fn banding(rgb: &Rgb32FImage) -> f32 {
+        let mut histograms = [[false; 256]; 2];
+        for pixel in rgb.pixels() {
+            for (channel, histogram) in histograms.iter_mut().enumerate() {
+                histogram[(pixel[channel].clamp(0.0, 1.0) * 255.0).round() as usize] = true;
+            }
+        }
+
+        let (mut empty, mut span) = (0usize, 0usize);
+        for histogram in &histograms {
+            let first = histogram.iter().position(|&used| used);
+            let last = histogram.iter().rposition(|&used| used);
+            if let (Some(first), Some(last)) = (first, last) {
+                span += last - first + 1;
+                empty += histogram[first..=last].iter().filter(|&&used| !used).count();
+            }
+        }
+        if span == 0 {
+            0.0
+        } else {
+            empty as f32 / span as f32
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgb;
+    use std::f32::consts::TAU;
+
+    const SIZE: u32 = 64;
+
+    /// Unit normal of tileable bumps that differ along X and Y, with image y pointing down.
+    fn bump_normal(x: u32, y: u32, convention: NormalConvention) -> [f32; 3] {
+        let (u, v) = (x as f32 / SIZE as f32 * TAU, y as f32 / SIZE as f32 * TAU);
+        let scale = TAU / SIZE as f32 * 8.0;
+        let dh_dx = scale * (0.4 * (2.0 * u).cos() * v.cos() + 0.1 * (u + 3.0 * v).cos());
+        let dh_dy = scale * (-0.2 * (2.0 * u).sin() * v.sin() + 0.3 * (u + 3.0 * v).cos());
+        let ny = match convention {
+            NormalConvention::OpenGl => dh_dy,
+            NormalConvention::DirectX => -dh_dy,
+        };
+        let length = (dh_dx * dh_dx + ny * ny + 1.0).sqrt();
+        [-dh_dx / length, ny / length, 1.0 / length]
+    }
+
+    /// Bump field encoded by `encode`, which maps each unit normal to the stored vector.
+    fn bump_map(convention: NormalConvention, encode: impl Fn([f32; 3]) -> [f32; 3]) -> DynamicImage {
+        DynamicImage::ImageRgb32F(Rgb32FImage::from_fn(SIZE, SIZE, |x, y| {
+            Rgb(encode(bump_normal(x, y, convention)).map(|value| value * 0.5 + 0.5))
+        }))
+    }
+
+    fn decoded(image: &DynamicImage, x: u32, y: u32) -> [f32; 3] {
+        image.to_rgb32f().get_pixel(x, y).0.map(|value| value * 2.0 - 1.0)
+    }
+
+    fn assert_normals_close(image: &DynamicImage, convention: NormalConvention) {
+        for (x, y) in [(0, 0), (5, 17), (31, 40), (63, 63)] {
+            let (actual, expected) = (decoded(image, x, y), bump_normal(x, y, convention));
+            for (a, e) in actual.iter().zip(expected) {
+                assert!((a - e).abs() < 1e-4, "({}, {}): {:?} != {:?}", x, y, actual, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn detects_both_conventions() {
+        for convention in [NormalConvention::OpenGl, NormalConvention::DirectX] {
+            let report = NormalAnalyzer::analyze(&bump_map(convention, |n| n));
+            assert_eq!(report.convention, Some(convention));
+            assert!(report.convention_confidence > 0.5, "{:?}", report);
+            assert!((report.average_length - 1.0).abs() < 1e-3);
+            assert_eq!(report.invalid_percent, 0.0);
+            assert!(!report.missing_z && !report.inverted_z);
+        }
+    }
+
+    #[test]
+    fn flags_missing_and_inverted_z() {
+        let missing = NormalAnalyzer::analyze(&bump_map(NormalConvention::OpenGl, |[x, y, _]| [x, y, 0.0]));
+        assert!(missing.missing_z && !missing.inverted_z);
+        assert_eq!(missing.convention, Some(NormalConvention::OpenGl));
+
+        let inverted = NormalAnalyzer::analyze(&bump_map(NormalConvention::OpenGl, |[x, y, z]| [x, y, -z]));
+        assert!(inverted.inverted_z && !inverted.missing_z);
+        assert_eq!(inverted.z_violation_percent, 100.0);
+    }
+
+    #[test]
+    fn repair_renormalises_reconstructs_and_flips() {
+        let renormalise = RepairOptions { reconstruct_z: false, flip_y: false };
+        let short = bump_map(NormalConvention::OpenGl, |n| n.map(|value| value * 0.8));
+        assert_normals_close(&NormalAnalyzer::repair(&short, &renormalise), NormalConvention::OpenGl);
+
+        let reconstruct = RepairOptions { reconstruct_z: true, flip_y: false };
+        let missing = bump_map(NormalConvention::OpenGl, |[x, y, _]| [x, y, 0.0]);
+        assert_normals_close(&NormalAnalyzer::repair(&missing, &reconstruct), NormalConvention::OpenGl);
+
+        let flip = RepairOptions { reconstruct_z: false, flip_y: true };
+        let flipped = NormalAnalyzer::repair(&bump_map(NormalConvention::OpenGl, |n| n), &flip);
+        assert_normals_close(&flipped, NormalConvention::DirectX);
+        assert_eq!(NormalAnalyzer::analyze(&flipped).convention, Some(NormalConvention::DirectX));
+    }
+}
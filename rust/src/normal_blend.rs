@@ -0,0 +1,215 @@
+use image::{DynamicImage, ImageBuffer, Rgb32FImage};
+use nalgebra::Vector3;
+use rayon::prelude::*;
+
+use crate::image_converter::{BitDepth, ImageConverter};
+use crate::openexr::GrayF32Image;
+
+/// How a detail normal map is combined with a base normal map.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NormalBlendMode {
+    /// Reoriented Normal Mapping: the detail follows the base surface
+    Reoriented,
+    /// Unreal Developer Network: adds slopes, keeps the base Z (flattens steep detail)
+    Udn,
+    /// Whiteout: adds slopes and multiplies Z
+    Whiteout,
+    /// Base plus the detail's tilt away from flat, without reorienting the detail
+    Linear,
+}
+
+impl NormalBlendMode {
+    /// Parse a blend mode name as passed from GDScript ("" selects RNM).
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "" | "rnm" | "reoriented" => Ok(NormalBlendMode::Reoriented),
+            "udn" => Ok(NormalBlendMode::Udn),
+            "whiteout" => Ok(NormalBlendMode::Whiteout),
+            "linear" => Ok(NormalBlendMode::Linear),
+            other => Err(format!(
+                "Unknown normal blend mode '{}' (expected rnm, udn, whiteout or linear)",
+                other
+            )),
+        }
+    }
+}
+
+/// Settings of one normal blend.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NormalBlendOptions {
+    pub mode: NormalBlendMode,
+    /// Detail intensity (0 = base only, 1 = as authored, above 1 exaggerates)
+    pub strength: f32,
+    /// Times the detail repeats across the base (1 stretches it over the base once)
+    pub detail_tiling: f32,
+}
+
+impl Default for NormalBlendOptions {
+    fn default() -> Self {
+        Self {
+            mode: NormalBlendMode::Reoriented,
+            strength: 1.0,
+            detail_tiling: 1.0,
+        }
+    }
+}
+
+/// Combines a detail normal map with a base normal map
+/// Both maps must use the same convention; the result has the base's size and bit depth.
+pub struct NormalBlender;
+
+impl NormalBlender {
+    /// `mask` (any size, white = full detail) limits where the detail is applied.
+    pub fn blend(
+        base: &DynamicImage,
+        detail: &DynamicImage,
+        mask: Option<&DynamicImage>,
+        options: &NormalBlendOptions,
+    ) -> DynamicImage {
+        let base_rgb = base.to_rgb32f();
+        let detail_rgb = detail.to_rgb32f();
+        let mask = mask.map(|mask| mask.to_luma32f());
+        let (width, height) = base_rgb.dimensions();
+        let flat = Vector3::z();
+
+        let mut blended: Rgb32FImage = ImageBuffer::new(width, height);
+        blended
+            .par_chunks_mut(width as usize * 3)
+            .enumerate()
+            .for_each(|(y, row)| {
+                let v = (y as f32 + 0.5) / height as f32;
+                for (x, pixel) in row.chunks_mut(3).enumerate() {
+                    let u = (x as f32 + 0.5) / width as f32;
+                    let n1 = Self::decode(base_rgb.get_pixel(x as u32, y as u32).0);
+                    let n2 = Self::decode(Self::sample_rgb(
+                        &detail_rgb,
+                        u * options.detail_tiling,
+                        v * options.detail_tiling,
+                    ));
+                    let mut n2 = flat + (n2 - flat) * options.strength;
+                    n2.z = n2.z.max(0.01);
+                    let n2 = n2.normalize();
+
+                    let mut n = match options.mode {
+                        NormalBlendMode::Reoriented => {
+                            let t = n1 + flat;
+                            let u = Vector3::new(-n2.x, -n2.y, n2.z);
+                            t * t.dot(&u) / t.z - u
+                        }
+                        NormalBlendMode::Udn => Vector3::new(n1.x + n2.x, n1.y + n2.y, n1.z),
+                        NormalBlendMode::Whiteout => Vector3::new(n1.x + n2.x, n1.y + n2.y, n1.z * n2.z),
+                        NormalBlendMode::Linear => n1 + n2 - flat,
+                    }
+                    .try_normalize(1e-6)
+                    .unwrap_or(n1);
+                    if let Some(mask) = &mask {
+                        let amount = Self::sample_gray(mask, u, v).clamp(0.0, 1.0);
+                        n = (n1 + (n - n1) * amount).try_normalize(1e-6).unwrap_or(n1);
+                    }
+
+                    pixel.copy_from_slice(&[n.x * 0.5 + 0.5, n.y * 0.5 + 0.5, n.z * 0.5 + 0.5]);
+                }
+            });
+
+        ImageConverter::with_depth(&DynamicImage::ImageRgb32F(blended), BitDepth::of(base.color()))
+    }
+
+    fn decode([r, g, b]: [f32; 3]) -> Vector3<f32> {
+        Vector3::new(r * 2.0 - 1.0, g * 2.0 - 1.0, b * 2.0 - 1.0)
+            .try_normalize(1e-6)
+            .unwrap_or_else(Vector3::z)
+    }
+
+    /// Bilinear sample at UV coordinates, wrapping around the edges
+    fn sample_rgb(image: &Rgb32FImage, u: f32, v: f32) -> [f32; 3] {
+        let (x0, x1, fx) = Self::wrap_coordinates(u, image.width());
+        let (y0, y1, fy) = Self::wrap_coordinates(v, image.height());
+        let at = |x: u32, y: u32| image.get_pixel(x, y).0;
+        let lerp = |a: [f32; 3], b: [f32; 3], t: f32| [0, 1, 2].map(|c| a[c] + (b[c] - a[c]) * t);
+        lerp(lerp(at(x0, y0), at(x1, y0), fx), lerp(at(x0, y1), at(x1, y1), fx), fy)
+    }
+
+    fn sample_gray(image: &GrayF32Image, u: f32, v: f32) -> f32 {
+        let (x0, x1, fx) = Self::wrap_coordinates(u, image.width());
+        let (y0, y1, fy) = Self::wrap_coordinates(v, image.height());
+        let at = |x: u32, y: u32| image.get_pixel(x, y)[0];
+        let top = at(x0, y0) + (at(x1, y0) - at(x0, y0)) * fx;
+        let bottom = at(x0, y1) + (at(x1, y1) - at(x0, y1)) * fx;
+        top + (bottom - top) * fy
+    }
+
+    /// Neighbouring texel indices and interpolation weight for a UV coordinate
+    fn wrap_coordinates(coordinate: f32, size: u32) -> (u32, u32, f32) {
+        let position = (coordinate * size as f32 - 0.5).rem_euclid(size as f32);
+        let first = (position.floor() as u32).min(size - 1);
+        (first, (first + 1) % size, position.fract())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Luma, Rgb};
+    use std::f32::consts::TAU;
+
+    const MODES: [NormalBlendMode; 4] = [
+        NormalBlendMode::Reoriented,
+        NormalBlendMode::Udn,
+        NormalBlendMode::Whiteout,
+        NormalBlendMode::Linear,
+    ];
+
+    fn normal_map(size: u32, normal: impl Fn(u32, u32) -> Vector3<f32>) -> DynamicImage {
+        DynamicImage::ImageRgb32F(Rgb32FImage::from_fn(size, size, |x, y| {
+            let n = normal(x, y).normalize();
+            Rgb([n.x * 0.5 + 0.5, n.y * 0.5 + 0.5, n.z * 0.5 + 0.5])
+        }))
+    }
+
+    fn base() -> DynamicImage {
+        normal_map(16, |x, y| {
+            let (u, v) = (x as f32 / 16.0 * TAU, y as f32 / 16.0 * TAU);
+            Vector3::new(0.6 * u.sin(), 0.4 * (2.0 * v).cos(), 1.0)
+        })
+    }
+
+    fn detail() -> DynamicImage {
+        normal_map(8, |x, y| Vector3::new(if (x + y) % 2 == 0 { 0.5 } else { -0.3 }, 0.2, 1.0))
+    }
+
+    fn assert_same(actual: &DynamicImage, expected: &DynamicImage) {
+        for (a, e) in actual.to_rgb32f().pixels().zip(expected.to_rgb32f().pixels()) {
+            for (a, e) in a.0.iter().zip(e.0) {
+                assert!((a - e).abs() < 1e-5, "{:?} != {:?}", a, e);
+            }
+        }
+    }
+
+    #[test]
+    fn flat_detail_keeps_the_base() {
+        let flat = normal_map(4, |_, _| Vector3::z());
+        for mode in MODES {
+            let options = NormalBlendOptions { mode, ..Default::default() };
+            assert_same(&NormalBlender::blend(&base(), &flat, None, &options), &base());
+        }
+    }
+
+    #[test]
+    fn zero_strength_keeps_the_base() {
+        for mode in MODES {
+            let options = NormalBlendOptions { mode, strength: 0.0, ..Default::default() };
+            assert_same(&NormalBlender::blend(&base(), &detail(), None, &options), &base());
+        }
+    }
+
+    #[test]
+    fn black_mask_keeps_the_base() {
+        let mask = DynamicImage::ImageLuma8(ImageBuffer::from_pixel(4, 4, Luma([0])));
+        for mode in MODES {
+            let options = NormalBlendOptions { mode, ..Default::default() };
+            assert_same(&NormalBlender::blend(&base(), &detail(), Some(&mask), &options), &base());
+            let blended = NormalBlender::blend(&base(), &detail(), None, &options);
+            assert_ne!(blended.to_rgb32f().as_raw(), base().to_rgb32f().as_raw());
+        }
+    }
+}
@@ -1,13 +1,52 @@
+use image::imageops::{self, FilterType};
 use image::{DynamicImage, ImageBuffer, Rgb, RgbImage};
 use nalgebra::{Matrix2, Vector3};
 use rayon::prelude::*;
 
+use crate::openexr::GrayF32Image;
+
+/// Contribution of one pyramid level to a multi-scale normal map.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NormalLevel {
+    /// Share of the combined normal (relative to the other levels, 0 disables the level)
+    pub weight: f32,
+    /// Bumpiness of this level's normals
+    pub strength: f32,
+}
+
+/// Pyramid levels of a multi-scale normal map, from fine to very coarse.
+/// Each level works on a height map half the width and height of the previous one.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NormalDetail {
+    pub levels: [NormalLevel; 4],
+}
+
+impl NormalDetail {
+    /// Level names as used in GDScript option keys
+    pub const LEVEL_NAMES: [&'static str; 4] = ["fine", "medium", "coarse", "very_coarse"];
+}
+
+impl Default for NormalDetail {
+    fn default() -> Self {
+        let level = |weight| NormalLevel { weight, strength: 4.0 };
+        Self {
+            levels: [level(1.0), level(0.5), level(0.25), level(0.125)],
+        }
+    }
+}
+
+/// Tangent-space normal maps from height, in the DirectX convention (green down):
+/// the normal is (-dh/dx, -dh/dy, 1) with image y pointing down.
 pub struct NormalMapGenerator;
 
 impl NormalMapGenerator {
     /// Generate a normal map using structure tensor (state-of-the-art 2026)
     pub fn generate(height_map: &DynamicImage) -> DynamicImage {
-        let height_gray = height_map.to_luma8();
+        Self::generate_from_f32(&height_map.to_luma32f())
+    }
+
+    /// Generate a normal map from a float height field (0.0-1.0), without quantizing heights
+    pub fn generate_from_f32(height_gray: &GrayF32Image) -> DynamicImage {
         let width = height_gray.width();
         let height = height_gray.height();
 
@@ -22,10 +61,10 @@ impl NormalMapGenerator {
             .par_bridge()
             .for_each(|(x, y, pixel)| {
                 // Compute structure tensor for this pixel
-                let _tensor = Self::compute_structure_tensor(&height_gray, x, y, 1.5);
+                let _tensor = Self::compute_structure_tensor(height_gray, x, y, 1.5);
 
                 // Get gradients from neighboring pixels
-                let (dx, dy) = Self::compute_adaptive_gradients(&height_gray, x, y, strength);
+                let (dx, dy) = Self::compute_adaptive_gradients(height_gray, x, y, strength);
 
                 // Construct normal vector
                 let mut normal = Vector3::new(-dx, -dy, 1.0);
@@ -42,9 +81,76 @@ impl NormalMapGenerator {
         DynamicImage::ImageRgb8(normal_map)
     }
 
+    /// Generate a normal map that combines the normals of several pyramid levels,
+    /// so large forms show up next to pixel-level detail.
+    pub fn generate_multi_scale(height_gray: &GrayF32Image, detail: &NormalDetail) -> DynamicImage {
+        let (width, height) = height_gray.dimensions();
+        let mut combined = vec![Vector3::<f32>::zeros(); (width * height) as usize];
+
+        for (index, level) in detail.levels.iter().enumerate() {
+            if level.weight <= 0.0 {
+                continue;
+            }
+            let factor = 2u32.pow(index as u32);
+            let level_width = (width / factor).max(width.min(4));
+            let level_height = (height / factor).max(height.min(4));
+            let downscaled;
+            let source = if (level_width, level_height) == (width, height) {
+                height_gray
+            } else {
+                downscaled = imageops::resize(height_gray, level_width, level_height, FilterType::Triangle);
+                &downscaled
+            };
+
+            // Slopes per level pixel, so coarse levels turn gentle large forms into visible normals
+            let slopes: Vec<(f32, f32)> = (0..level_width * level_height)
+                .into_par_iter()
+                .map(|i| {
+                    let (x, y) = (i % level_width, i / level_width);
+                    Self::compute_adaptive_gradients(source, x, y, level.strength)
+                })
+                .collect();
+            let scale_x = level_width as f32 / width as f32;
+            let scale_y = level_height as f32 / height as f32;
+
+            // Bilinear upsampling by hand: image resizing clamps the negative slopes
+            combined
+                .par_chunks_mut(width as usize)
+                .enumerate()
+                .for_each(|(y, row)| {
+                    let sy = ((y as f32 + 0.5) * scale_y - 0.5).clamp(0.0, (level_height - 1) as f32);
+                    let (y0, fy) = (sy.floor() as u32, sy.fract());
+                    let y1 = (y0 + 1).min(level_height - 1);
+                    for (x, sum) in row.iter_mut().enumerate() {
+                        let sx = ((x as f32 + 0.5) * scale_x - 0.5).clamp(0.0, (level_width - 1) as f32);
+                        let (x0, fx) = (sx.floor() as u32, sx.fract());
+                        let x1 = (x0 + 1).min(level_width - 1);
+                        let at = |x: u32, y: u32| slopes[(y * level_width + x) as usize];
+                        let lerp = |a: (f32, f32), b: (f32, f32), t: f32| {
+                            (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t)
+                        };
+                        let (dx, dy) = lerp(lerp(at(x0, y0), at(x1, y0), fx), lerp(at(x0, y1), at(x1, y1), fx), fy);
+                        *sum += Vector3::new(-dx, -dy, 1.0).normalize() * level.weight;
+                    }
+                });
+        }
+
+        let normal_map = RgbImage::from_fn(width, height, |x, y| {
+            let normal = combined[(y * width + x) as usize]
+                .try_normalize(1e-6)
+                .unwrap_or_else(Vector3::z);
+            Rgb([
+                ((normal.x * 0.5 + 0.5) * 255.0) as u8,
+                ((normal.y * 0.5 + 0.5) * 255.0) as u8,
+                ((normal.z * 0.5 + 0.5) * 255.0) as u8,
+            ])
+        });
+        DynamicImage::ImageRgb8(normal_map)
+    }
+
     /// Compute structure tensor for better gradient estimation
     fn compute_structure_tensor(
-        image: &image::GrayImage,
+        image: &GrayF32Image,
         x: u32,
         y: u32,
         sigma: f32,
@@ -88,14 +194,14 @@ impl NormalMapGenerator {
     }
 
     /// Scharr operator - more accurate than Sobel
-    fn scharr_x(image: &image::GrayImage, x: u32, y: u32) -> f32 {
+    fn scharr_x(image: &GrayF32Image, x: u32, y: u32) -> f32 {
         let width = image.width();
         let height = image.height();
 
         let get = |dx: i32, dy: i32| {
             let nx = (x as i32 + dx).clamp(0, width as i32 - 1) as u32;
             let ny = (y as i32 + dy).clamp(0, height as i32 - 1) as u32;
-            image.get_pixel(nx, ny)[0] as f32 / 255.0
+            image.get_pixel(nx, ny)[0]
         };
 
         // Scharr X kernel
@@ -106,14 +212,14 @@ impl NormalMapGenerator {
         gx / 16.0
     }
 
-    fn scharr_y(image: &image::GrayImage, x: u32, y: u32) -> f32 {
+    fn scharr_y(image: &GrayF32Image, x: u32, y: u32) -> f32 {
         let width = image.width();
         let height = image.height();
 
         let get = |dx: i32, dy: i32| {
             let nx = (x as i32 + dx).clamp(0, width as i32 - 1) as u32;
             let ny = (y as i32 + dy).clamp(0, height as i32 - 1) as u32;
-            image.get_pixel(nx, ny)[0] as f32 / 255.0
+            image.get_pixel(nx, ny)[0]
         };
 
         // Scharr Y kernel
@@ -127,7 +233,7 @@ impl NormalMapGenerator {
 
     /// Compute adaptive gradients
     fn compute_adaptive_gradients(
-        image: &image::GrayImage,
+        image: &GrayF32Image,
         x: u32,
         y: u32,
         strength: f32,
@@ -139,3 +245,48 @@ impl NormalMapGenerator {
         (dx, dy)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Luma;
+    use crate::height_from_normal::{HeightFromNormalGenerator, NormalConvention};
+    use crate::normal_analysis::NormalAnalyzer;
+    use std::f32::consts::TAU;
+
+    /// Tileable bumps that differ along X and Y, so only one Y convention integrates.
+    fn bumpy_height(size: u32) -> GrayF32Image {
+        ImageBuffer::from_fn(size, size, |x, y| {
+            let (u, v) = (x as f32 / size as f32 * TAU, y as f32 / size as f32 * TAU);
+            Luma([0.5 + 0.2 * (2.0 * u).sin() * v.cos() + 0.1 * (u + 3.0 * v).sin()])
+        })
+    }
+
+    fn correlation(a: &GrayF32Image, b: &GrayF32Image) -> f32 {
+        let mean = |image: &GrayF32Image| image.pixels().map(|p| p[0]).sum::<f32>() / image.len() as f32;
+        let (mean_a, mean_b) = (mean(a), mean(b));
+        let (mut ab, mut aa, mut bb) = (0.0, 0.0, 0.0);
+        for (pa, pb) in a.pixels().zip(b.pixels()) {
+            let (da, db) = (pa[0] - mean_a, pb[0] - mean_b);
+            ab += da * db;
+            aa += da * da;
+            bb += db * db;
+        }
+        ab / (aa * bb).sqrt()
+    }
+
+    #[test]
+    fn generated_normals_are_directx() {
+        let source = bumpy_height(64);
+        let normals = NormalMapGenerator::generate_from_f32(&source);
+
+        let report = NormalAnalyzer::analyze(&normals);
+        assert_eq!(report.convention, Some(NormalConvention::DirectX));
+
+        let directx = HeightFromNormalGenerator::generate(&normals, NormalConvention::DirectX, true);
+        let opengl = HeightFromNormalGenerator::generate(&normals, NormalConvention::OpenGl, true);
+        let (directx, opengl) = (correlation(&source, &directx.height), correlation(&source, &opengl.height));
+        assert!(directx > 0.95, "DirectX height correlation {}", directx);
+        assert!(directx > opengl + 0.1, "DirectX {} vs OpenGL {}", directx, opengl);
+    }
+}
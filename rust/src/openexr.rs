@@ -0,0 +1,112 @@
+use exr::prelude::{
+    read_first_flat_layer_from_file, AnyChannel, AnyChannels, Encoding, FlatSamples, Image, Layer,
+    LayerAttributes, SmallVec, Vec2, WritableImage,
+};
+use image::{DynamicImage, ImageBuffer, Luma, Rgba32FImage};
+use std::path::Path;
+
+/// Single-channel float image (heights, displacement)
+pub type GrayF32Image = ImageBuffer<Luma<f32>, Vec<f32>>;
+
+/// OpenEXR reader/writer
+/// `image::open` only reads RGB(A) files; this also reads single-channel
+/// (luminance / height) files and writes single-channel float output.
+pub struct OpenExr;
+
+impl OpenExr {
+    /// Load the first layer at full 32-bit precision as `ImageRgba32F`.
+    /// RGB(A) channels are used when present, otherwise the first channel
+    /// (e.g. `Y` or `Z`) is replicated to RGB. Missing alpha is 1.
+    pub fn load(path: &Path) -> Result<DynamicImage, String> {
+        let image = read_first_flat_layer_from_file(path)
+            .map_err(|e| format!("Failed to read EXR file: {}", e))?;
+        let layer = image.layer_data;
+        let (width, height) = (layer.size.width(), layer.size.height());
+        let channels = &layer.channel_data.list;
+
+        // Layered files name channels "layer.R", so only the last segment counts
+        let find = |names: &[&str]| {
+            channels.iter().find(|channel| {
+                let name = channel.name.to_string();
+                let base = name.rsplit('.').next().unwrap_or_default();
+                names.iter().any(|n| base.eq_ignore_ascii_case(n))
+            })
+        };
+        if let Some(channel) = channels.iter().find(|c| c.sampling != Vec2(1, 1)) {
+            return Err(format!("Subsampled EXR channel '{}' is not supported", channel.name));
+        }
+
+        let red = find(&["R", "red"]);
+        let green = find(&["G", "green"]);
+        let blue = find(&["B", "blue"]);
+        let (red, green, blue) = match (red, green, blue) {
+            (Some(r), Some(g), Some(b)) => (r, g, b),
+            _ => {
+                let gray = find(&["Y", "L", "Z", "height"])
+                    .or_else(|| channels.first())
+                    .ok_or("EXR layer has no channels")?;
+                (gray, gray, gray)
+            }
+        };
+        let alpha = find(&["A", "alpha"]);
+
+        let mut pixels = vec![1.0f32; width * height * 4];
+        for (offset, channel) in [red, green, blue].into_iter().chain(alpha).enumerate() {
+            for (pixel, value) in pixels
+                .chunks_exact_mut(4)
+                .zip(channel.sample_data.values_as_f32())
+            {
+                pixel[offset] = value;
+            }
+        }
+
+        Rgba32FImage::from_raw(width as u32, height as u32, pixels)
+            .map(DynamicImage::ImageRgba32F)
+            .ok_or_else(|| "EXR buffer has the wrong size".to_string())
+    }
+
+    /// Save a single-channel float image as a `Y` channel (ZIP compressed).
+    pub fn save_gray(image: &GrayF32Image, path: &Path) -> Result<(), String> {
+        let channel = AnyChannel::new("Y", FlatSamples::F32(image.as_raw().clone()));
+        let layer = Layer::new(
+            (image.width() as usize, image.height() as usize),
+            LayerAttributes::default(),
+            Encoding::SMALL_LOSSLESS,
+            AnyChannels::sort(SmallVec::from_vec(vec![channel])),
+        );
+
+        Image::from_layer(layer)
+            .write()
+            .to_file(path)
+            .map_err(|e| format!("Failed to write EXR: {}", e))
+    }
+
+    /// Save any image as 32-bit float RGB(A) (alpha only if the source has one).
+    pub fn save(image: &DynamicImage, path: &Path) -> Result<(), String> {
+        let rgba = image.to_rgba32f();
+        let mut names = vec!["R", "G", "B"];
+        if image.color().has_alpha() {
+            names.push("A");
+        }
+
+        let channels = names
+            .iter()
+            .enumerate()
+            .map(|(offset, &name)| {
+                let samples = rgba.pixels().map(|pixel| pixel[offset]).collect();
+                AnyChannel::new(name, FlatSamples::F32(samples))
+            })
+            .collect();
+        let layer = Layer::new(
+            (rgba.width() as usize, rgba.height() as usize),
+            LayerAttributes::default(),
+            Encoding::SMALL_LOSSLESS,
+            AnyChannels::sort(SmallVec::from_vec(channels)),
+        );
+
+        Image::from_layer(layer)
+            .write()
+            .to_file(path)
+            .map_err(|e| format!("Failed to write EXR: {}", e))
+    }
+}
@@ -0,0 +1,286 @@
+use image::{DynamicImage, ImageBuffer, Luma};
+use std::path::{Path, PathBuf};
+
+use crate::channel_packer::ColorSpace;
+use crate::color_management::ColorManagement;
+use crate::image_converter::{BitDepth, ImageConverter};
+use crate::openexr::{GrayF32Image, OpenExr};
+
+/// A generated map, either regular pixels or a 32-bit float height field.
+pub enum MapImage {
+    Image(DynamicImage),
+    Float(GrayF32Image),
+}
+
+impl MapImage {
+    pub fn dimensions(&self) -> (u32, u32) {
+        match self {
+            MapImage::Image(image) => (image.width(), image.height()),
+            MapImage::Float(image) => image.dimensions(),
+        }
+    }
+
+    /// Pixels for integer formats (float heights become 16-bit)
+    pub fn to_dynamic(&self) -> DynamicImage {
+        match self {
+            MapImage::Image(image) => image.clone(),
+            MapImage::Float(image) => {
+                let quantized = ImageBuffer::from_fn(image.width(), image.height(), |x, y| {
+                    Luma([(image.get_pixel(x, y)[0].clamp(0.0, 1.0) * 65535.0).round() as u16])
+                });
+                DynamicImage::ImageLuma16(quantized)
+            }
+        }
+    }
+}
+
+/// File format for a generated map.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MapFormat {
+    /// 8-bit PNG
+    Png8,
+    /// 16-bit PNG
+    Png16,
+    /// 8-bit TGA
+    Tga,
+    /// 16-bit TIFF
+    Tiff,
+    /// Lossless 8-bit WebP
+    WebpLossless,
+    /// 32-bit float OpenEXR
+    Exr,
+}
+
+impl MapFormat {
+    /// Parse a format name as passed from GDScript.
+    /// "" or "auto" returns `None` (EXR for float heights, 16-bit PNG for 16-bit maps,
+    /// 8-bit PNG otherwise).
+    pub fn from_name(name: &str) -> Result<Option<Self>, String> {
+        match name.trim().trim_start_matches('.').to_ascii_lowercase().as_str() {
+            "" | "auto" => Ok(None),
+            "png" | "png8" => Ok(Some(MapFormat::Png8)),
+            "png16" => Ok(Some(MapFormat::Png16)),
+            "tga" => Ok(Some(MapFormat::Tga)),
+            "tif" | "tiff" => Ok(Some(MapFormat::Tiff)),
+            "webp" => Ok(Some(MapFormat::WebpLossless)),
+            "exr" => Ok(Some(MapFormat::Exr)),
+            other => Err(format!(
+                "Unknown map format '{}' (expected auto, png, png16, tga, tiff, webp or exr)",
+                other
+            )),
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            MapFormat::Png8 | MapFormat::Png16 => "png",
+            MapFormat::Tga => "tga",
+            MapFormat::Tiff => "tiff",
+            MapFormat::WebpLossless => "webp",
+            MapFormat::Exr => "exr",
+        }
+    }
+
+    /// Save `map`; PNG files carry the `color_space` tag (EXR is always linear).
+    pub fn save(self, map: &MapImage, path: &Path, color_space: ColorSpace) -> Result<(), String> {
+        match (self, map) {
+            (MapFormat::Exr, MapImage::Float(image)) => OpenExr::save_gray(image, path),
+            (MapFormat::Exr, MapImage::Image(image)) if image.color().channel_count() == 1 => {
+                OpenExr::save_gray(&image.to_luma32f(), path)
+            }
+            (MapFormat::Exr, MapImage::Image(image)) => OpenExr::save(image, path),
+            (MapFormat::Png8 | MapFormat::Png16, _) => {
+                let depth = if self == MapFormat::Png16 { BitDepth::Sixteen } else { BitDepth::Eight };
+                ColorManagement::save_png(&ImageConverter::with_depth(&map.to_dynamic(), depth), path, color_space)
+            }
+            _ => {
+                let depth = if self == MapFormat::Tiff { BitDepth::Sixteen } else { BitDepth::Eight };
+                ImageConverter::with_depth(&map.to_dynamic(), depth)
+                    .save(path)
+                    .map_err(|e| format!("Failed to save {}: {}", path.display(), e))
+            }
+        }
+    }
+}
+
+/// What to do when an output file already exists.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExistingFilePolicy {
+    Overwrite,
+    /// Keep the existing file and report the map as skipped
+    Skip,
+    /// Write `name_v2`, `name_v3`, ... next to the existing file
+    Version,
+}
+
+impl ExistingFilePolicy {
+    /// Parse a policy name as passed from GDScript ("" selects overwrite).
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "" | "overwrite" => Ok(ExistingFilePolicy::Overwrite),
+            "skip" => Ok(ExistingFilePolicy::Skip),
+            "version" | "increment" => Ok(ExistingFilePolicy::Version),
+            other => Err(format!(
+                "Unknown existing file policy '{}' (expected overwrite, skip or version)",
+                other
+            )),
+        }
+    }
+
+    /// Path to write to, or `None` if the file exists and should be kept.
+    pub fn resolve(self, path: &Path) -> Option<PathBuf> {
+        if !path.exists() {
+            return Some(path.to_path_buf());
+        }
+        match self {
+            ExistingFilePolicy::Overwrite => Some(path.to_path_buf()),
+            ExistingFilePolicy::Skip => None,
+            ExistingFilePolicy::Version => {
+                let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+                let extension = path
+                    .extension()
+                    .map(|ext| format!(".{}", ext.to_string_lossy()))
+                    .unwrap_or_default();
+                (2..)
+                    .map(|version| path.with_file_name(format!("{}_v{}{}", stem, version, extension)))
+                    .find(|candidate| !candidate.exists())
+            }
+        }
+    }
+}
+
+/// Where a map ended up.
+pub struct SavedMap {
+    pub path: PathBuf,
+    /// The file already existed and was left untouched
+    pub skipped: bool,
+}
+
+/// Naming and format settings for generated maps.
+pub struct OutputConfig {
+    /// File name without extension. Tokens: `{stem}` (source name), `{map}`
+    /// ("height", "normal", "roughness", "albedo"), `{map_short}` ("H", "N", "R", "A"),
+    /// `{res}` (larger side in pixels), `{width}`, `{height}`.
+    /// May contain `/` to write into subdirectories.
+    pub template: String,
+    /// Format for maps without an entry in `map_formats` (`None` = automatic)
+    pub default_format: Option<MapFormat>,
+    /// Per-map overrides, keyed by map name
+    pub map_formats: Vec<(String, MapFormat)>,
+    pub existing: ExistingFilePolicy,
+    /// Tag written into PNG maps (`Unspecified` for legacy untagged output)
+    pub color_space: ColorSpace,
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        Self {
+            template: "{stem}_{map}".to_string(),
+            default_format: None,
+            map_formats: Vec::new(),
+            existing: ExistingFilePolicy::Overwrite,
+            color_space: ColorSpace::Linear,
+        }
+    }
+}
+
+impl OutputConfig {
+    /// Format of `map`: its override, the default format, or the automatic choice.
+    pub fn format_for(&self, map: &str, image: &MapImage) -> MapFormat {
+        self.map_formats
+            .iter()
+            .find(|(name, _)| name == map)
+            .map(|&(_, format)| format)
+            .or(self.default_format)
+            .unwrap_or(match image {
+                MapImage::Float(_) => MapFormat::Exr,
+                MapImage::Image(image) if BitDepth::of(image.color()) == BitDepth::Sixteen => MapFormat::Png16,
+                MapImage::Image(_) => MapFormat::Png8,
+            })
+    }
+
+    /// Expand the template for one map.
+    pub fn file_name(&self, stem: &str, map: &str, width: u32, height: u32) -> Result<String, String> {
+        let map_short = map.chars().next().map(|c| c.to_ascii_uppercase().to_string()).unwrap_or_default();
+        let tokens = [
+            ("stem", stem.to_string()),
+            ("map", map.to_string()),
+            ("map_short", map_short),
+            ("res", width.max(height).to_string()),
+            ("width", width.to_string()),
+            ("height", height.to_string()),
+        ];
+
+        let mut name = String::new();
+        let mut rest = self.template.as_str();
+        while let Some(start) = rest.find('{') {
+            name.push_str(&rest[..start]);
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| format!("Unclosed '{{' in naming template '{}'", self.template))?;
+            let token = &rest[start + 1..start + end];
+            let value = tokens
+                .iter()
+                .find(|(key, _)| *key == token)
+                .map(|(_, value)| value)
+                .ok_or_else(|| format!("Unknown token '{{{}}}' in naming template", token))?;
+            name.push_str(value);
+            rest = &rest[start + end + 1..];
+        }
+        name.push_str(rest);
+
+        if name.trim().is_empty() {
+            return Err("Naming template produced an empty file name".to_string());
+        }
+        Ok(name)
+    }
+
+    /// Name, place and write one map according to the template, format and existing file policy.
+    pub fn save_map(
+        &self,
+        output_dir: &Path,
+        stem: &str,
+        map: &str,
+        image: &MapImage,
+    ) -> Result<SavedMap, String> {
+        let format = self.format_for(map, image);
+        let (width, height) = image.dimensions();
+        let name = self.file_name(stem, map, width, height)?;
+        let target = output_dir.join(format!("{}.{}", name, format.extension()));
+        let Some(path) = self.existing.resolve(&target) else {
+            return Ok(SavedMap { path: target, skipped: true });
+        };
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        format.save(image, &path, self.color_space_for(map))?;
+        Ok(SavedMap { path, skipped: false })
+    }
+
+    /// Albedo is colour data and tagged sRGB; every other map is tagged `color_space`.
+    pub fn color_space_for(&self, map: &str) -> ColorSpace {
+        match (map, self.color_space) {
+            ("albedo", ColorSpace::Linear) => ColorSpace::Srgb,
+            (_, color_space) => color_space,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn auto_format_keeps_16_bit_heights() {
+        let config = OutputConfig::default();
+        let float = MapImage::Float(GrayF32Image::new(2, 2));
+        let height = MapImage::Image(MapImage::Float(GrayF32Image::new(2, 2)).to_dynamic());
+        let normal = MapImage::Image(DynamicImage::new_rgb8(2, 2));
+
+        assert_eq!(config.format_for("height", &float), MapFormat::Exr);
+        assert_eq!(config.format_for("height", &height), MapFormat::Png16);
+        assert_eq!(config.format_for("normal", &normal), MapFormat::Png8);
+    }
+}
@@ -0,0 +1,107 @@
+use crate::channel_packer::{ChannelMapping, ChannelSource};
+
+/// Engine-specific channel layouts for single-texture material masks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PackingPreset {
+    /// glTF / Godot ORM: R = AO, G = Roughness, B = Metallic
+    Orm,
+    /// Unity HDRP mask map: R = Metallic, G = AO, B = Detail mask, A = Smoothness
+    HdrpMask,
+    /// Unreal ARM: R = AO, G = Roughness, B = Metallic
+    Arm,
+}
+
+/// One output channel of a preset: candidate inputs in order of preference
+/// (name, inverted), and the constant used when none of them is provided.
+struct PresetChannel {
+    candidates: &'static [(&'static str, bool)],
+    default: f32,
+}
+
+const AO: PresetChannel = PresetChannel {
+    candidates: &[("ao", false)],
+    default: 1.0,
+};
+const ROUGHNESS: PresetChannel = PresetChannel {
+    candidates: &[("roughness", false), ("smoothness", true)],
+    default: 1.0,
+};
+const SMOOTHNESS: PresetChannel = PresetChannel {
+    candidates: &[("smoothness", false), ("roughness", true)],
+    default: 0.0,
+};
+const METALLIC: PresetChannel = PresetChannel {
+    candidates: &[("metallic", false)],
+    default: 0.0,
+};
+const DETAIL_MASK: PresetChannel = PresetChannel {
+    candidates: &[("detail", false)],
+    default: 1.0,
+};
+const OPAQUE: PresetChannel = PresetChannel {
+    candidates: &[],
+    default: 1.0,
+};
+
+impl PackingPreset {
+    /// Parse a preset name as passed from GDScript.
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "orm" | "gltf" | "godot" => Ok(PackingPreset::Orm),
+            "hdrp" | "hdrp_mask" | "mask_map" => Ok(PackingPreset::HdrpMask),
+            "arm" | "unreal" => Ok(PackingPreset::Arm),
+            other => Err(format!(
+                "Unknown packing preset '{}' (expected orm, hdrp_mask or arm)",
+                other
+            )),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PackingPreset::Orm => "orm",
+            PackingPreset::HdrpMask => "hdrp_mask",
+            PackingPreset::Arm => "arm",
+        }
+    }
+
+    /// File name suffix for the packed texture
+    pub fn suffix(self) -> &'static str {
+        match self {
+            PackingPreset::Orm => "orm",
+            PackingPreset::HdrpMask => "mask",
+            PackingPreset::Arm => "arm",
+        }
+    }
+
+    /// Input names the preset can use ("ao", "roughness", "smoothness", "metallic", "detail")
+    pub fn input_names() -> [&'static str; 5] {
+        ["ao", "roughness", "smoothness", "metallic", "detail"]
+    }
+
+    fn layout(self) -> [PresetChannel; 4] {
+        match self {
+            PackingPreset::Orm | PackingPreset::Arm => [AO, ROUGHNESS, METALLIC, OPAQUE],
+            PackingPreset::HdrpMask => [METALLIC, AO, DETAIL_MASK, SMOOTHNESS],
+        }
+    }
+
+    /// Channel mapping for the given input names (in `pack_channels` order).
+    /// Channels without a matching input are filled with the preset default.
+    pub fn mapping(self, inputs: &[String]) -> ChannelMapping {
+        let channels = self.layout().map(|channel| {
+            channel
+                .candidates
+                .iter()
+                .find_map(|&(name, invert)| {
+                    inputs.iter().position(|n| n == name).map(|input| ChannelSource::Input {
+                        input,
+                        channel: 0,
+                        invert,
+                    })
+                })
+                .unwrap_or(ChannelSource::Constant(channel.default))
+        });
+        ChannelMapping { channels }
+    }
+}
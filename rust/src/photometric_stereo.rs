@@ -0,0 +1,133 @@
+use image::{DynamicImage, Rgb, RgbImage};
+use nalgebra::{Matrix3, Vector3};
+use rayon::prelude::*;
+
+use crate::color_management::TransferFunction;
+use crate::height_from_normal::NormalConvention;
+
+/// Albedo and normals recovered from several lit captures.
+pub struct PhotometricResult {
+    /// Colour albedo, sRGB encoded
+    pub albedo: DynamicImage,
+    /// Tangent-space normal map
+    pub normals: DynamicImage,
+    /// RMS difference between the captures and the fitted Lambertian model (0.0-1.0)
+    pub fit_error: f32,
+}
+
+/// Lambertian photometric stereo
+/// Each capture sees the same surface under a known light. Per pixel the scaled
+/// normal `g = albedo * n` is the least-squares solution of `I_i = l_i · g`.
+/// With five or more captures the darkest (shadowed) and brightest (specular)
+/// observation of each pixel are ignored.
+pub struct PhotometricStereo;
+
+impl PhotometricStereo {
+    /// `lights` are directions towards each light (x right, y up, z towards the
+    /// camera); their length is the light's relative intensity.
+    pub fn solve(
+        captures: &[DynamicImage],
+        lights: &[Vector3<f32>],
+        transfer: TransferFunction,
+        convention: NormalConvention,
+    ) -> Result<PhotometricResult, String> {
+        if captures.len() < 3 {
+            return Err(format!("Photometric stereo needs at least 3 captures, got {}", captures.len()));
+        }
+        if captures.len() != lights.len() {
+            return Err(format!(
+                "Got {} captures but {} light directions",
+                captures.len(),
+                lights.len()
+            ));
+        }
+        let (width, height) = (captures[0].width(), captures[0].height());
+        for (index, capture) in captures.iter().enumerate() {
+            if (capture.width(), capture.height()) != (width, height) {
+                return Err(format!(
+                    "Capture {} is {}x{}, expected {}x{}",
+                    index,
+                    capture.width(),
+                    capture.height(),
+                    width,
+                    height
+                ));
+            }
+        }
+        let rank = lights.iter().fold(Matrix3::zeros(), |sum, l| sum + l * l.transpose());
+        if rank.determinant().abs() < 1e-6 {
+            return Err("Light directions must not all lie in one plane".to_string());
+        }
+
+        let linear: Vec<Vec<[f32; 3]>> = captures
+            .par_iter()
+            .map(|capture| {
+                capture
+                    .to_rgb32f()
+                    .pixels()
+                    .map(|p| p.0.map(|value| transfer.to_linear(value)))
+                    .collect()
+            })
+            .collect();
+
+        let drop_extremes = captures.len() >= 5;
+        let pixels: Vec<([f32; 3], [f32; 3], f32)> = (0..(width * height) as usize)
+            .into_par_iter()
+            .map(|index| {
+                let colours: Vec<[f32; 3]> = linear.iter().map(|capture| capture[index]).collect();
+                let intensity: Vec<f32> = colours
+                    .iter()
+                    .map(|[r, g, b]| 0.2126 * r + 0.7152 * g + 0.0722 * b)
+                    .collect();
+
+                let mut used: Vec<usize> = (0..colours.len()).collect();
+                if drop_extremes {
+                    used.sort_by(|&a, &b| intensity[a].total_cmp(&intensity[b]));
+                    used = used[1..used.len() - 1].to_vec();
+                }
+
+                let (a, b) = used.iter().fold((Matrix3::zeros(), Vector3::zeros()), |(a, b), &i| {
+                    (a + lights[i] * lights[i].transpose(), b + lights[i] * intensity[i])
+                });
+                let g = a.try_inverse().map(|inverse| inverse * b).unwrap_or_else(Vector3::z);
+                let normal = if g.norm() > 1e-6 && g.z > 0.0 { g.normalize() } else { Vector3::z() };
+
+                // Colour albedo for the fitted normal
+                let shading: Vec<f32> = used.iter().map(|&i| lights[i].dot(&normal).max(0.0)).collect();
+                let energy: f32 = shading.iter().map(|s| s * s).sum();
+                let mut albedo = [0.0f32; 3];
+                if energy > 1e-6 {
+                    for (channel, value) in albedo.iter_mut().enumerate() {
+                        let fit: f32 = used.iter().zip(&shading).map(|(&i, s)| colours[i][channel] * s).sum();
+                        *value = fit / energy;
+                    }
+                }
+
+                let error = used
+                    .iter()
+                    .map(|&i| (intensity[i] - lights[i].dot(&g)).powi(2))
+                    .sum::<f32>()
+                    / used.len() as f32;
+                ([normal.x, normal.y, normal.z], albedo, error)
+            })
+            .collect();
+
+        let encode_normal = |value: f32| ((value * 0.5 + 0.5).clamp(0.0, 1.0) * 255.0).round() as u8;
+        let normals = RgbImage::from_fn(width, height, |x, y| {
+            let ([nx, ny, nz], _, _) = pixels[(y * width + x) as usize];
+            let ny = if convention == NormalConvention::DirectX { -ny } else { ny };
+            Rgb([encode_normal(nx), encode_normal(ny), encode_normal(nz)])
+        });
+        let albedo = RgbImage::from_fn(width, height, |x, y| {
+            let (_, albedo, _) = pixels[(y * width + x) as usize];
+            Rgb(albedo.map(|value| (TransferFunction::Srgb.encode(value.clamp(0.0, 1.0)) * 255.0).round() as u8))
+        });
+        let fit_error = (pixels.iter().map(|(_, _, error)| *error as f64).sum::<f64>() / pixels.len() as f64).sqrt() as f32;
+
+        Ok(PhotometricResult {
+            albedo: DynamicImage::ImageRgb8(albedo),
+            normals: DynamicImage::ImageRgb8(normals),
+            fit_error,
+        })
+    }
+}
@@ -1,6 +1,9 @@
 use image::{DynamicImage, GrayImage, ImageBuffer, Luma};
 use rayon::prelude::*;
 
+use crate::color_management::{ColorManagement, ColorProcessing, TransferFunction};
+use crate::openexr::GrayF32Image;
+
 pub struct RoughnessMapGenerator;
 
 impl RoughnessMapGenerator {
@@ -12,9 +15,14 @@ impl RoughnessMapGenerator {
     /// 3. Enhanced material classification
     /// 4. Better roughness priors based on real materials
     pub fn generate(albedo: &DynamicImage) -> DynamicImage {
-        let rgba = albedo.to_rgba8();
-        let width = rgba.width();
-        let height = rgba.height();
+        let luminance = ColorManagement::luminance(albedo, TransferFunction::Srgb, ColorProcessing::Legacy);
+        Self::generate_from_luminance(&luminance)
+    }
+
+    /// Same as `generate`, starting from a luminance image (see `ColorManagement::luminance`)
+    pub fn generate_from_luminance(luminance: &GrayF32Image) -> DynamicImage {
+        let width = luminance.width();
+        let height = luminance.height();
 
         let mut roughness_map: GrayImage = ImageBuffer::new(width, height);
 
@@ -27,7 +35,7 @@ impl RoughnessMapGenerator {
             .par_bridge()
             .for_each(|(x, y, pixel)| {
                 // Measure local "busyness"
-                let variance = Self::compute_texture_variance(&rgba, x, y);
+                let variance = Self::compute_texture_variance(luminance, x, y);
                 
                 // Base roughness on luminance (often darker things are smoother, but not always)
                 // Let's assume mid-roughness base
@@ -62,7 +70,7 @@ impl RoughnessMapGenerator {
     }
 
     /// Texture variance (unchanged - already good)
-    fn compute_texture_variance(image: &image::RgbaImage, x: u32, y: u32) -> f32 {
+    fn compute_texture_variance(image: &GrayF32Image, x: u32, y: u32) -> f32 {
         let width = image.width();
         let height = image.height();
         let window_size = 3;
@@ -74,8 +82,7 @@ impl RoughnessMapGenerator {
                 let nx = (x as i32 + dx).clamp(0, width as i32 - 1) as u32;
                 let ny = (y as i32 + dy).clamp(0, height as i32 - 1) as u32;
 
-                let lum = Self::get_luminance(image, nx, ny);
-                values.push(lum);
+                values.push(image.get_pixel(nx, ny)[0]);
             }
         }
 
@@ -89,15 +96,5 @@ impl RoughnessMapGenerator {
         (variance * 10.0).clamp(0.0, 1.0)
     }
 
-    /// Get luminance
-    fn get_luminance(image: &image::RgbaImage, x: u32, y: u32) -> f32 {
-        let pixel = image.get_pixel(x, y);
-        let r = pixel[0] as f32 / 255.0;
-        let g = pixel[1] as f32 / 255.0;
-        let b = pixel[2] as f32 / 255.0;
-
-        0.2126 * r + 0.7152 * g + 0.0722 * b
-    }
-
 
 }
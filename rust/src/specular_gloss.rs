@@ -0,0 +1,289 @@
+use image::{DynamicImage, ImageBuffer, Luma, Rgb, Rgb32FImage};
+
+use crate::color_management::TransferFunction;
+use crate::image_converter::{BitDepth, ImageConverter};
+use crate::openexr::GrayF32Image;
+use crate::output_config::MapImage;
+
+/// How glossiness relates to Godot's (perceptual) roughness.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GlossRemap {
+    /// roughness = 1 - gloss (gloss authored as smoothness)
+    Linear,
+    /// roughness = sqrt(1 - gloss) (gloss authored as 1 - alpha, alpha = roughness²)
+    Perceptual,
+}
+
+impl GlossRemap {
+    /// Parse a remap name as passed from GDScript ("" selects linear).
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "" | "linear" => Ok(GlossRemap::Linear),
+            "perceptual" => Ok(GlossRemap::Perceptual),
+            other => Err(format!("Unknown gloss remap '{}' (expected linear or perceptual)", other)),
+        }
+    }
+
+    pub fn roughness(self, gloss: f32) -> f32 {
+        let gloss = gloss.clamp(0.0, 1.0);
+        match self {
+            GlossRemap::Linear => 1.0 - gloss,
+            GlossRemap::Perceptual => (1.0 - gloss).sqrt(),
+        }
+    }
+
+    pub fn gloss(self, roughness: f32) -> f32 {
+        let roughness = roughness.clamp(0.0, 1.0);
+        match self {
+            GlossRemap::Linear => 1.0 - roughness,
+            GlossRemap::Perceptual => 1.0 - roughness * roughness,
+        }
+    }
+}
+
+/// Metallic-roughness colour maps.
+pub struct MetallicMaps {
+    /// Base colour, encoded like the source
+    pub albedo: DynamicImage,
+    pub metallic: DynamicImage,
+}
+
+/// Specular-gloss colour maps.
+pub struct SpecularMaps {
+    /// Diffuse colour, encoded like the source
+    pub diffuse: DynamicImage,
+    /// Specular colour (F0), encoded like the source
+    pub specular: DynamicImage,
+}
+
+/// Conversion between the specular-gloss and metallic-roughness workflows
+/// Colour math runs in linear light and follows the glTF specular-glossiness
+/// converter: dielectrics reflect 4%, brighter specular is solved as metal.
+pub struct SpecularGlossConverter;
+
+impl SpecularGlossConverter {
+    /// Specular reflectance of dielectrics
+    const DIELECTRIC_F0: f32 = 0.04;
+
+    /// Glossiness (channel 0-3 = R, G, B, A, `None` = grey) to roughness, at the source's depth.
+    pub fn gloss_to_roughness(gloss: &DynamicImage, channel: Option<usize>, remap: GlossRemap) -> DynamicImage {
+        Self::remap_gray(gloss, channel, |value| remap.roughness(value))
+    }
+
+    /// Roughness (grey) to glossiness, at the source's depth.
+    pub fn roughness_to_gloss(roughness: &DynamicImage, remap: GlossRemap) -> DynamicImage {
+        Self::remap_gray(roughness, None, |value| remap.gloss(value))
+    }
+
+    /// Diffuse + specular colour to base colour + metallic. Both maps must be the same size.
+    pub fn to_metallic(
+        diffuse: &DynamicImage,
+        specular: &DynamicImage,
+        transfer: TransferFunction,
+    ) -> Result<MetallicMaps, String> {
+        Self::check_sizes(diffuse, specular, "Specular")?;
+        let diffuse_rgb = Self::linear_rgb(diffuse, transfer);
+        let specular_rgb = Self::linear_rgb(specular, transfer);
+        let (width, height) = diffuse_rgb.dimensions();
+
+        let mut albedo: Rgb32FImage = ImageBuffer::new(width, height);
+        let mut metallic: GrayF32Image = ImageBuffer::new(width, height);
+        for (x, y, pixel) in albedo.enumerate_pixels_mut() {
+            let diffuse = diffuse_rgb.get_pixel(x, y).0;
+            let specular = specular_rgb.get_pixel(x, y).0;
+            let one_minus_specular = 1.0 - specular.iter().fold(0.0f32, |a, &b| a.max(b));
+            let metal = Self::solve_metallic(
+                Self::brightness(diffuse),
+                Self::brightness(specular),
+                one_minus_specular,
+            );
+
+            let a = Self::DIELECTRIC_F0;
+            let from_diffuse =
+                diffuse.map(|d| d * one_minus_specular / (1.0 - a) / (1.0 - metal).max(1e-4));
+            let from_specular = specular.map(|s| (s - a * (1.0 - metal)) / metal.max(1e-4));
+            let blend = metal * metal;
+            let base = [0, 1, 2].map(|c| {
+                let value = from_diffuse[c] + (from_specular[c] - from_diffuse[c]) * blend;
+                transfer.encode(value.clamp(0.0, 1.0))
+            });
+
+            *pixel = Rgb(base);
+            metallic.put_pixel(x, y, Luma([metal]));
+        }
+
+        let depth = BitDepth::of(diffuse.color());
+        Ok(MetallicMaps {
+            albedo: ImageConverter::with_depth(&DynamicImage::ImageRgb32F(albedo), depth),
+            metallic: ImageConverter::with_depth(&MapImage::Float(metallic).to_dynamic(), depth),
+        })
+    }
+
+    /// Base colour + metallic (grey) to diffuse + specular colour, the inverse of `to_metallic`.
+    /// Both maps must be the same size.
+    pub fn to_specular(
+        albedo: &DynamicImage,
+        metallic: &DynamicImage,
+        transfer: TransferFunction,
+    ) -> Result<SpecularMaps, String> {
+        Self::check_sizes(albedo, metallic, "Metallic")?;
+        let albedo_rgb = Self::linear_rgb(albedo, transfer);
+        let metallic_gray = metallic.to_luma32f();
+        let (width, height) = albedo_rgb.dimensions();
+
+        let a = Self::DIELECTRIC_F0;
+        let mut diffuse: Rgb32FImage = ImageBuffer::new(width, height);
+        let mut specular: Rgb32FImage = ImageBuffer::new(width, height);
+        for (x, y, base) in albedo_rgb.enumerate_pixels() {
+            let metal = metallic_gray.get_pixel(x, y)[0].clamp(0.0, 1.0);
+            let f0 = base.0.map(|c| a + (c - a) * metal);
+            // Spec-gloss shading scales diffuse by 1 - max(specular); undo it as `to_metallic` does
+            let one_minus_specular = (1.0 - f0.iter().fold(0.0f32, |m, &s| m.max(s))).max(1e-4);
+            let scale = (1.0 - metal) * (1.0 - a) / one_minus_specular;
+            diffuse.put_pixel(x, y, Rgb(base.0.map(|c| transfer.encode((c * scale).clamp(0.0, 1.0)))));
+            specular.put_pixel(x, y, Rgb(f0.map(|c| transfer.encode(c))));
+        }
+
+        let depth = BitDepth::of(albedo.color());
+        Ok(SpecularMaps {
+            diffuse: ImageConverter::with_depth(&DynamicImage::ImageRgb32F(diffuse), depth),
+            specular: ImageConverter::with_depth(&DynamicImage::ImageRgb32F(specular), depth),
+        })
+    }
+
+    /// Metalness that reproduces the specular brightness (root of a quadratic in `metallic`)
+    fn solve_metallic(diffuse: f32, specular: f32, one_minus_specular: f32) -> f32 {
+        let a = Self::DIELECTRIC_F0;
+        if specular < a {
+            return 0.0;
+        }
+        let b = diffuse * one_minus_specular / (1.0 - a) + specular - 2.0 * a;
+        let c = a - specular;
+        let discriminant = (b * b - 4.0 * a * c).max(0.0);
+        ((-b + discriminant.sqrt()) / (2.0 * a)).clamp(0.0, 1.0)
+    }
+
+    /// Perceived brightness of a linear colour
+    fn brightness([r, g, b]: [f32; 3]) -> f32 {
+        (0.299 * r * r + 0.587 * g * g + 0.114 * b * b).sqrt()
+    }
+
+    fn linear_rgb(image: &DynamicImage, transfer: TransferFunction) -> Rgb32FImage {
+        let mut rgb = image.to_rgb32f();
+        rgb.pixels_mut().for_each(|p| p.0 = p.0.map(|value| transfer.to_linear(value)));
+        rgb
+    }
+
+    fn remap_gray(image: &DynamicImage, channel: Option<usize>, remap: impl Fn(f32) -> f32) -> DynamicImage {
+        let values: GrayF32Image = match channel {
+            Some(channel) => {
+                let rgba = image.to_rgba32f();
+                ImageBuffer::from_fn(rgba.width(), rgba.height(), |x, y| {
+                    Luma([remap(rgba.get_pixel(x, y)[channel.min(3)])])
+                })
+            }
+            None => {
+                let mut gray = image.to_luma32f();
+                gray.pixels_mut().for_each(|p| p[0] = remap(p[0]));
+                gray
+            }
+        };
+        ImageConverter::with_depth(&MapImage::Float(values).to_dynamic(), BitDepth::of(image.color()))
+    }
+
+    fn check_sizes(first: &DynamicImage, second: &DynamicImage, label: &str) -> Result<(), String> {
+        if (first.width(), first.height()) != (second.width(), second.height()) {
+            return Err(format!(
+                "{} map is {}x{}, expected {}x{}",
+                label,
+                second.width(),
+                second.height(),
+                first.width(),
+                first.height()
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rgb(pixels: &[[f32; 3]]) -> DynamicImage {
+        DynamicImage::ImageRgb32F(Rgb32FImage::from_fn(pixels.len() as u32, 1, |x, _| Rgb(pixels[x as usize])))
+    }
+
+    fn gray(values: &[f32]) -> DynamicImage {
+        DynamicImage::ImageLuma16(ImageBuffer::from_fn(values.len() as u32, 1, |x, _| {
+            Luma([(values[x as usize] * 65535.0).round() as u16])
+        }))
+    }
+
+    fn assert_close(actual: &DynamicImage, expected: &DynamicImage, tolerance: f32) {
+        for (a, e) in actual.to_rgb32f().pixels().zip(expected.to_rgb32f().pixels()) {
+            for (a, e) in a.0.iter().zip(e.0) {
+                assert!((a - e).abs() < tolerance, "{:?} != {:?}", a, e);
+            }
+        }
+    }
+
+    #[test]
+    fn gloss_round_trips() {
+        for remap in [GlossRemap::Linear, GlossRemap::Perceptual] {
+            for gloss in [0.0, 0.25, 0.5, 0.9, 1.0] {
+                let back = remap.gloss(remap.roughness(gloss));
+                assert!((back - gloss).abs() < 1e-5, "{:?}: {} -> {}", remap, gloss, back);
+            }
+        }
+        assert_eq!(GlossRemap::Linear.roughness(0.75), 0.25);
+        assert_eq!(GlossRemap::Perceptual.roughness(0.75), 0.5);
+
+        let roughness = gray(&[0.0, 0.3, 0.7, 1.0]);
+        let gloss = SpecularGlossConverter::roughness_to_gloss(&roughness, GlossRemap::Perceptual);
+        let back = SpecularGlossConverter::gloss_to_roughness(&gloss, None, GlossRemap::Perceptual);
+        assert_close(&back, &roughness, 1e-3);
+    }
+
+    #[test]
+    fn dielectric_has_no_metal() {
+        let diffuse = rgb(&[[0.5, 0.3, 0.2]]);
+        let specular = rgb(&[[0.04; 3]]);
+        let maps = SpecularGlossConverter::to_metallic(&diffuse, &specular, TransferFunction::Linear).unwrap();
+        assert_close(&maps.metallic, &gray(&[0.0]), 1e-3);
+        assert_close(&maps.albedo, &diffuse, 1e-4);
+    }
+
+    #[test]
+    fn metal_takes_the_specular_colour() {
+        let diffuse = rgb(&[[0.0; 3]]);
+        let specular = rgb(&[[0.9, 0.6, 0.3]]);
+        let maps = SpecularGlossConverter::to_metallic(&diffuse, &specular, TransferFunction::Linear).unwrap();
+        assert_close(&maps.metallic, &gray(&[1.0]), 1e-3);
+        assert_close(&maps.albedo, &specular, 1e-3);
+    }
+
+    fn round_trip(albedo: &[[f32; 3]], metallic: &[f32], tolerance: f32) {
+        let (albedo, metallic) = (rgb(albedo), gray(metallic));
+        for transfer in [TransferFunction::Linear, TransferFunction::Srgb] {
+            let input = SpecularGlossConverter::to_specular(&albedo, &metallic, transfer).unwrap();
+            let maps = SpecularGlossConverter::to_metallic(&input.diffuse, &input.specular, transfer).unwrap();
+            let output = SpecularGlossConverter::to_specular(&maps.albedo, &maps.metallic, transfer).unwrap();
+            assert_close(&output.diffuse, &input.diffuse, tolerance);
+            assert_close(&output.specular, &input.specular, tolerance);
+        }
+    }
+
+    #[test]
+    fn specular_round_trips_through_metallic() {
+        round_trip(&[[0.5, 0.3, 0.2], [0.9, 0.6, 0.3], [0.8, 0.8, 0.8]], &[0.0, 1.0, 0.5], 1e-3);
+        // Coloured partial metals are solved from their brightness, so only close
+        round_trip(&[[0.2, 0.5, 0.7], [0.9, 0.6, 0.3]], &[0.25, 0.6], 1e-2);
+    }
+
+    #[test]
+    fn mismatched_sizes_are_an_error() {
+        let result = SpecularGlossConverter::to_metallic(&rgb(&[[0.5; 3]]), &rgb(&[[0.04; 3]; 2]), TransferFunction::Linear);
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,126 @@
+use image::{ImageBuffer, Luma};
+use std::f32::consts::PI;
+
+use crate::fft::{fft_2d, Complex32};
+use crate::openexr::GrayF32Image;
+
+/// Height from a gradient field
+/// Frankot-Chellappa: the least-squares surface whose gradient best matches the
+/// given slopes, solved in the Fourier domain (a Poisson equation).
+pub struct SurfaceIntegrator;
+
+impl SurfaceIntegrator {
+    /// Integrate `dh_dx` and `dh_dy` (image y pointing down) into a zero-mean height.
+    /// `wrap` treats the texture as tiling; otherwise the slopes are mirrored so the
+    /// opposite edges do not influence each other (at twice the memory per side).
+    pub fn integrate(dh_dx: &GrayF32Image, dh_dy: &GrayF32Image, wrap: bool) -> GrayF32Image {
+        let (width, height) = dh_dx.dimensions();
+        let (width, height) = (width as usize, height as usize);
+        let (grid_width, grid_height) = if wrap { (width, height) } else { (width * 2, height * 2) };
+
+        // Mirrored copies flip the sign of the slope across the mirror axis
+        let sample = |image: &GrayF32Image, x: usize, y: usize, flip_x: bool, flip_y: bool| {
+            let (sx, mirrored_x) = if x < width { (x, false) } else { (grid_width - 1 - x, true) };
+            let (sy, mirrored_y) = if y < height { (y, false) } else { (grid_height - 1 - y, true) };
+            let value = image.get_pixel(sx as u32, sy as u32)[0];
+            if (flip_x && mirrored_x) || (flip_y && mirrored_y) {
+                -value
+            } else {
+                value
+            }
+        };
+
+        let mut p = vec![Complex32::new(0.0, 0.0); grid_width * grid_height];
+        let mut q = p.clone();
+        for y in 0..grid_height {
+            for x in 0..grid_width {
+                p[y * grid_width + x].re = sample(dh_dx, x, y, true, false);
+                q[y * grid_width + x].re = sample(dh_dy, x, y, false, true);
+            }
+        }
+        fft_2d(&mut p, grid_width, grid_height, false);
+        fft_2d(&mut q, grid_width, grid_height, false);
+
+        let frequency = |k: usize, n: usize| {
+            let k = if k > n / 2 { k as f32 - n as f32 } else { k as f32 };
+            2.0 * PI * k / n as f32
+        };
+        for y in 0..grid_height {
+            let v = frequency(y, grid_height);
+            for x in 0..grid_width {
+                let u = frequency(x, grid_width);
+                let index = y * grid_width + x;
+                let denominator = u * u + v * v;
+                p[index] = if denominator > 0.0 {
+                    // H = -i(u P + v Q) / (u² + v²)
+                    let sum = p[index] * u + q[index] * v;
+                    Complex32::new(sum.im, -sum.re) / denominator
+                } else {
+                    Complex32::new(0.0, 0.0)
+                };
+            }
+        }
+        fft_2d(&mut p, grid_width, grid_height, true);
+
+        ImageBuffer::from_fn(width as u32, height as u32, |x, y| {
+            Luma([p[y as usize * grid_width + x as usize].re])
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Integrate the analytic gradient of `surface` (returning height, dh/dx, dh/dy) and
+    /// return the RMS difference to the zero-mean height, relative to its range.
+    fn relative_error(width: u32, height: u32, wrap: bool, surface: impl Fn(f32, f32) -> (f32, f32, f32)) -> f32 {
+        let dh_dx = ImageBuffer::from_fn(width, height, |x, y| Luma([surface(x as f32, y as f32).1]));
+        let dh_dy = ImageBuffer::from_fn(width, height, |x, y| Luma([surface(x as f32, y as f32).2]));
+        let integrated = SurfaceIntegrator::integrate(&dh_dx, &dh_dy, wrap);
+
+        let expected: Vec<f32> = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| surface(x as f32, y as f32).0)
+            .collect();
+        let mean = expected.iter().sum::<f32>() / expected.len() as f32;
+        let range = expected.iter().cloned().fold(f32::MIN, f32::max) - expected.iter().cloned().fold(f32::MAX, f32::min);
+        let squared: f32 = expected
+            .iter()
+            .zip(integrated.pixels())
+            .map(|(expected, actual)| (expected - mean - actual[0]).powi(2))
+            .sum();
+        (squared / expected.len() as f32).sqrt() / range
+    }
+
+    #[test]
+    fn integrates_tiling_surface() {
+        let (width, height) = (64.0, 32.0);
+        // One period across the texture in x, two in y
+        let surface = |x: f32, y: f32| {
+            let (ax, ay) = (2.0 * PI * x / width, 4.0 * PI * y / height);
+            (
+                3.0 * ax.sin() + 2.0 * ay.cos(),
+                3.0 * ax.cos() * 2.0 * PI / width,
+                -2.0 * ay.sin() * 4.0 * PI / height,
+            )
+        };
+        let error = relative_error(64, 32, true, surface);
+        assert!(error < 1e-3, "error {}", error);
+    }
+
+    #[test]
+    fn integrates_non_tiling_surface() {
+        // A tilted bowl: neither the slope nor the height repeats across the edges
+        let surface = |x: f32, y: f32| {
+            let (cx, cy) = (x - 20.0, y - 12.0);
+            (
+                0.5 * x + 0.02 * (cx * cx + cy * cy),
+                0.5 + 0.04 * cx,
+                0.04 * cy,
+            )
+        };
+        let error = relative_error(48, 40, false, surface);
+        assert!(error < 0.02, "error {}", error);
+    }
+}
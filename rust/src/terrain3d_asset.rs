@@ -0,0 +1,295 @@
+use std::path::Path;
+
+/// Properties written into a `Terrain3DTextureAsset` resource.
+///
+/// Defaults match the values Terrain3D uses for a freshly created texture slot.
+pub struct TextureAssetSettings {
+    pub name: String,
+    pub uv_scale: f32,
+    pub detiling_rotation: f32,
+    pub detiling_shift: f32,
+}
+
+impl Default for TextureAssetSettings {
+    fn default() -> Self {
+        Self {
+            name: String::from("texture"),
+            uv_scale: 0.1,
+            detiling_rotation: 0.0,
+            detiling_shift: 0.0,
+        }
+    }
+}
+
+/// Terrain3D resource writer
+/// Creates `Terrain3DTextureAsset` (.tres) files pointing at packed albedo_h / normal_r
+/// textures and registers them in an existing `Terrain3DAssets` resource.
+pub struct Terrain3DAssetWriter;
+
+impl Terrain3DAssetWriter {
+    /// Properties owned by the writer. Everything else found in an existing asset
+    /// (albedo_color, normal_depth, roughness, ...) is preserved on update.
+    const MANAGED_KEYS: [&'static str; 7] = [
+        "name",
+        "id",
+        "albedo_texture",
+        "normal_texture",
+        "uv_scale",
+        "detiling_rotation",
+        "detiling_shift",
+    ];
+
+    /// Create or update a texture asset resource at `asset_path`.
+    /// If `id` is `None`, an id already stored in the existing resource is kept.
+    pub fn write_texture_asset(
+        asset_path: &Path,
+        albedo_h_path: &Path,
+        normal_r_path: &Path,
+        settings: &TextureAssetSettings,
+        id: Option<i32>,
+    ) -> Result<(), String> {
+        // Keep user tweaks from a previous version of the asset
+        let existing = if asset_path.exists() {
+            let text = std::fs::read_to_string(asset_path)
+                .map_err(|e| format!("Failed to read texture asset: {}", e))?;
+            Self::resource_properties(&text)
+        } else {
+            Vec::new()
+        };
+
+        let id = id.or_else(|| {
+            existing
+                .iter()
+                .find(|(key, _)| key == "id")
+                .and_then(|(_, value)| value.parse().ok())
+        });
+
+        let mut tres = String::new();
+        tres.push_str("[gd_resource type=\"Terrain3DTextureAsset\" load_steps=3 format=3]\n\n");
+        tres.push_str(&format!(
+            "[ext_resource type=\"Texture2D\" path=\"{}\" id=\"1_albedo_h\"]\n",
+            Self::to_resource_path(albedo_h_path)
+        ));
+        tres.push_str(&format!(
+            "[ext_resource type=\"Texture2D\" path=\"{}\" id=\"2_normal_r\"]\n\n",
+            Self::to_resource_path(normal_r_path)
+        ));
+
+        tres.push_str("[resource]\n");
+        tres.push_str(&format!("name = \"{}\"\n", settings.name.replace('"', "\\\"")));
+        if let Some(id) = id {
+            tres.push_str(&format!("id = {}\n", id));
+        }
+        tres.push_str("albedo_texture = ExtResource(\"1_albedo_h\")\n");
+        tres.push_str("normal_texture = ExtResource(\"2_normal_r\")\n");
+        tres.push_str(&format!("uv_scale = {:?}\n", settings.uv_scale));
+        tres.push_str(&format!("detiling_rotation = {:?}\n", settings.detiling_rotation));
+        tres.push_str(&format!("detiling_shift = {:?}\n", settings.detiling_shift));
+
+        for (key, value) in &existing {
+            if Self::MANAGED_KEYS.contains(&key.as_str()) || value.contains("ExtResource(") {
+                continue;
+            }
+            tres.push_str(&format!("{} = {}\n", key, value));
+        }
+
+        std::fs::write(asset_path, tres)
+            .map_err(|e| format!("Failed to write texture asset: {}", e))
+    }
+
+    /// Return the slot `asset_path` occupies (or would occupy once appended) in the
+    /// `texture_list` of the Terrain3DAssets resource at `assets_path`.
+    pub fn texture_slot(assets_path: &Path, asset_path: &Path) -> Result<i32, String> {
+        let text = Self::read_assets(assets_path)?;
+        let entries = Self::texture_list_entries(&text);
+
+        if let Some(ext_id) = Self::find_ext_resource(&text, &Self::to_resource_path(asset_path)) {
+            let reference = format!("ExtResource(\"{}\")", ext_id);
+            if let Some(index) = entries.iter().position(|e| *e == reference) {
+                return Ok(index as i32);
+            }
+        }
+
+        Ok(entries.len() as i32)
+    }
+
+    /// Append `asset_path` to the `texture_list` of the Terrain3DAssets resource at
+    /// `assets_path`. Does nothing if the asset is already registered.
+    /// Returns the texture slot of the asset.
+    pub fn append_to_assets(assets_path: &Path, asset_path: &Path) -> Result<i32, String> {
+        let text = Self::read_assets(assets_path)?;
+        let resource_path = Self::to_resource_path(asset_path);
+
+        let (ext_id, mut lines) = match Self::find_ext_resource(&text, &resource_path) {
+            Some(ext_id) => (ext_id, text.lines().map(String::from).collect::<Vec<_>>()),
+            None => Self::insert_ext_resource(&text, &resource_path),
+        };
+
+        let reference = format!("ExtResource(\"{}\")", ext_id);
+        let mut entries = Self::texture_list_entries(&text);
+        if let Some(index) = entries.iter().position(|e| *e == reference) {
+            return Ok(index as i32);
+        }
+        entries.push(reference);
+
+        let list_line = format!(
+            "texture_list = Array[Terrain3DTextureAsset]([{}])",
+            entries.join(", ")
+        );
+
+        // Replace the existing texture_list, or add one to the [resource] section
+        let resource_start = lines
+            .iter()
+            .position(|l| l.trim() == "[resource]")
+            .ok_or("Terrain3DAssets resource has no [resource] section")?;
+        match lines
+            .iter()
+            .skip(resource_start)
+            .position(|l| l.starts_with("texture_list ="))
+        {
+            Some(offset) => lines[resource_start + offset] = list_line,
+            None => lines.insert(resource_start + 1, list_line),
+        }
+
+        let mut output = lines.join("\n");
+        output.push('\n');
+        std::fs::write(assets_path, output)
+            .map_err(|e| format!("Failed to write Terrain3DAssets: {}", e))?;
+
+        Ok(entries.len() as i32 - 1)
+    }
+
+    /// Convert a filesystem path to a Godot resource path.
+    /// Paths inside the project directory become `res://` paths, anything else
+    /// stays absolute.
+    pub fn to_resource_path(path: &Path) -> String {
+        let path_str = path.to_string_lossy().replace('\\', "/");
+        if path_str.starts_with("res://") {
+            return path_str;
+        }
+
+        if let Ok(project_path) = std::env::current_dir() {
+            if let Ok(relative) = path.strip_prefix(&project_path) {
+                return format!("res://{}", relative.to_string_lossy().replace('\\', "/"));
+            }
+        }
+
+        path_str
+    }
+
+    fn read_assets(assets_path: &Path) -> Result<String, String> {
+        let text = std::fs::read_to_string(assets_path)
+            .map_err(|e| format!("Failed to read Terrain3DAssets: {}", e))?;
+        if !text.contains("type=\"Terrain3DAssets\"") {
+            return Err(format!(
+                "'{}' is not a Terrain3DAssets resource",
+                assets_path.display()
+            ));
+        }
+        Ok(text)
+    }
+
+    /// `key = value` pairs of the `[resource]` section.
+    fn resource_properties(text: &str) -> Vec<(String, String)> {
+        text.lines()
+            .skip_while(|l| l.trim() != "[resource]")
+            .skip(1)
+            .take_while(|l| !l.starts_with('['))
+            .filter_map(|l| l.split_once(" = "))
+            .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+            .collect()
+    }
+
+    /// Entries of the `texture_list` array, e.g. `ExtResource("3_abc")`.
+    fn texture_list_entries(text: &str) -> Vec<String> {
+        let Some(line) = text.lines().find(|l| l.starts_with("texture_list =")) else {
+            return Vec::new();
+        };
+
+        let inner = line
+            .split_once("([")
+            .and_then(|(_, rest)| rest.rsplit_once("])"))
+            .map(|(inner, _)| inner)
+            .unwrap_or("");
+
+        let mut entries = Vec::new();
+        let mut depth = 0;
+        let mut current = String::new();
+        for c in inner.chars() {
+            match c {
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                ',' if depth == 0 => {
+                    entries.push(current.trim().to_string());
+                    current.clear();
+                    continue;
+                }
+                _ => {}
+            }
+            current.push(c);
+        }
+        if !current.trim().is_empty() {
+            entries.push(current.trim().to_string());
+        }
+
+        entries
+    }
+
+    /// Id of the ext_resource pointing at `resource_path`, if any.
+    fn find_ext_resource(text: &str, resource_path: &str) -> Option<String> {
+        let path_attr = format!("path=\"{}\"", resource_path);
+        text.lines()
+            .filter(|l| l.starts_with("[ext_resource") && l.contains(&path_attr))
+            .find_map(|l| Self::attribute(l, "id"))
+    }
+
+    /// Add an ext_resource line for `resource_path` and bump `load_steps`.
+    /// Returns the new resource id together with the updated lines.
+    fn insert_ext_resource(text: &str, resource_path: &str) -> (String, Vec<String>) {
+        let mut lines: Vec<String> = text.lines().map(String::from).collect();
+
+        let ext_count = lines.iter().filter(|l| l.starts_with("[ext_resource")).count();
+        let mut n = ext_count + 1;
+        while text.contains(&format!("id=\"{}_photonic\"", n)) {
+            n += 1;
+        }
+        let ext_id = format!("{}_photonic", n);
+        let ext_line = format!(
+            "[ext_resource type=\"Terrain3DTextureAsset\" path=\"{}\" id=\"{}\"]",
+            resource_path, ext_id
+        );
+
+        match lines.iter().rposition(|l| l.starts_with("[ext_resource")) {
+            Some(last) => lines.insert(last + 1, ext_line),
+            None => {
+                lines.insert(1, String::new());
+                lines.insert(2, ext_line);
+            }
+        }
+
+        if let Some(header) = lines.first_mut() {
+            if let Some(steps) = Self::attribute(header, "load_steps").and_then(|s| s.parse::<u32>().ok()) {
+                *header = header.replace(
+                    &format!("load_steps={}", steps),
+                    &format!("load_steps={}", steps + 1),
+                );
+            } else {
+                *header = header.replacen(" format=", &format!(" load_steps={} format=", 2), 1);
+            }
+        }
+
+        (ext_id, lines)
+    }
+
+    /// Read `key=value` or `key="value"` from a section header line.
+    fn attribute(line: &str, key: &str) -> Option<String> {
+        let start = line.find(&format!(" {}=", key))? + key.len() + 2;
+        let rest = &line[start..];
+        let value = if let Some(quoted) = rest.strip_prefix('"') {
+            quoted.split('"').next()?
+        } else {
+            rest.split([' ', ']']).next()?
+        };
+        Some(value.to_string())
+    }
+}
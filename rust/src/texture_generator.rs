@@ -2,9 +2,75 @@ use godot::prelude::*;
 use image::DynamicImage;
 use std::path::{Path, PathBuf};
 
-use crate::height_map::HeightMapGenerator;
-use crate::normal_map::NormalMapGenerator;
+use crate::channel_packer::{
+    ChannelMapping, ChannelPacker, ChannelSource, ColorSpace, ColorWeights, CompressionQuality,
+    Container, TextureOptions, NormalEncoding, ResizePolicy, TextureFormat,
+};
+use crate::color_management::{ColorManagement, ColorProcessing, TransferFunction};
+use crate::dds_reader::DdsReader;
+use crate::delight::{AlbedoDelighter, DelightOptions};
+use crate::height_from_normal::{HeightFromNormalGenerator, NormalConvention};
+use crate::height_map::{HeightMapGenerator, HeightMode, LightDirection};
+use crate::image_converter::{BitDepth, ColorConversion, ConvertFormat, ConvertOptions, ImageConverter};
+use crate::material_segmentation::{MaterialSegmenter, Segmentation};
+use crate::mipmap::{MipFilter, TextureContent};
+use crate::normal_analysis::{NormalAnalyzer, NormalReport, RepairOptions};
+use crate::normal_blend::{NormalBlendMode, NormalBlendOptions, NormalBlender};
+use crate::normal_map::{NormalDetail, NormalMapGenerator};
+use crate::openexr::OpenExr;
+use crate::output_config::{ExistingFilePolicy, MapFormat, MapImage, OutputConfig};
+use crate::packing_preset::PackingPreset;
+use crate::photometric_stereo::PhotometricStereo;
 use crate::roughness_map::RoughnessMapGenerator;
+use crate::specular_gloss::{GlossRemap, SpecularGlossConverter};
+use crate::terrain3d_asset::{Terrain3DAssetWriter, TextureAssetSettings};
+
+/// Inputs of `pack_terrain_3d_manual` / `pack_terrain_3d_array`, in packing order
+const MANUAL_PACK_INPUTS: [&str; 4] = ["albedo", "height", "normal", "roughness"];
+
+/// Extensions `convert_image` picks up when converting a directory
+const CONVERTIBLE_EXTENSIONS: [&str; 11] = [
+    "png", "jpg", "jpeg", "tga", "tif", "tiff", "webp", "bmp", "exr", "hdr", "dds",
+];
+
+/// Parsed `generate_maps_with_config` dictionary
+struct GenerationSettings {
+    output: OutputConfig,
+    processing: ColorProcessing,
+    /// `None` detects the encoding from the file
+    source_transfer: Option<TransferFunction>,
+    delight: Option<DelightOptions>,
+    height_mode: HeightMode,
+    /// `None` estimates the light from the albedo
+    light: Option<LightDirection>,
+    /// Treat the texture as tiling when integrating slopes
+    wrap: bool,
+    /// `None` keeps the single-scale normal map
+    normal_detail: Option<NormalDetail>,
+    /// Baked normal map the generated normals are blended onto
+    normal_blend: Option<NormalBlendLayer>,
+    /// `None` keeps the single variance-based roughness
+    materials: Option<MaterialSettings>,
+}
+
+/// Segmentation-based roughness settings.
+struct MaterialSettings {
+    /// Number of k-means clusters (ignored with a mask)
+    count: usize,
+    /// ID mask replacing the clustering
+    mask_path: Option<String>,
+    /// Roughness range per material, darkest material first
+    ranges: Vec<(f32, f32)>,
+    /// Feathering between materials, in pixels
+    blend_radius: f32,
+}
+
+/// Base layer for blending generated normals in the pipeline.
+struct NormalBlendLayer {
+    base_path: String,
+    mask_path: Option<String>,
+    options: NormalBlendOptions,
+}
 
 #[derive(GodotClass)]
 #[class(base=RefCounted)]
@@ -21,7 +87,10 @@ impl IRefCounted for TextureGenerator {
 
 #[godot_api]
 impl TextureGenerator {
-    /// Generate all maps (height, normal, roughness) from an albedo texture
+    /// Generate all maps (height, normal, roughness) from an albedo texture.
+    /// Float sources (EXR, HDR) keep 32-bit precision for height and normal generation
+    /// and write the height map as a float EXR. Normal maps use the DirectX convention
+    /// (green down); pass "directx" when analysing, repairing or integrating them.
     ///
     /// # Arguments
     /// * `albedo_path` - Path to the albedo texture
@@ -31,11 +100,88 @@ impl TextureGenerator {
     /// Dictionary with keys: success (bool), error (string), height_path, normal_path, roughness_path
     #[func]
     fn generate_maps(&mut self, albedo_path: GString, output_dir: GString) -> Dictionary {
+        self.generate_maps_with_config(albedo_path, output_dir, Dictionary::new())
+    }
+
+    /// `generate_maps` with custom file naming and formats.
+    ///
+    /// # Arguments
+    /// * `albedo_path` - Path to the albedo texture
+    /// * `output_dir` - Optional output directory (if empty, saves next to source)
+    /// * `config` - Dictionary, every key optional:
+    ///   - "template": file name without extension (default `"{stem}_{map}"`). Tokens:
+    ///     `{stem}`, `{map}` (height/normal/roughness), `{map_short}` (H/N/R), `{res}`
+    ///     (larger side), `{width}`, `{height}`; e.g. `"T_{stem}_{map_short}"`
+    ///   - "format": format of all maps: "auto" (EXR for float heights, PNG otherwise),
+    ///     "png", "png16", "tga", "tiff" (16-bit), "webp" (lossless) or "exr"
+    ///   - "height_format", "normal_format", "roughness_format": per-map overrides
+    ///   - "existing": "overwrite" (default), "skip" or "version" (`_v2`, `_v3`, ...)
+    ///   - "color_processing": "linear" (default) decodes the albedo to linear light before
+    ///     computing luminance, and tags PNG maps as linear; "legacy" reproduces the
+    ///     untagged output of earlier versions
+    ///   - "source_color_space": "auto" (default: EXR/HDR linear, PNG sRGB/gAMA/iCCP chunks
+    ///     and embedded ICC profiles, otherwise sRGB), "srgb", "linear" or a gamma ("2.2")
+    ///   - "delight": remove baked lighting before height generation (see `delight_albedo`);
+    ///     "delight_strength", "delight_radius", "delight_occlusion", "delight_highlights"
+    ///   - "height_mode": "luminance" (default, brightness is height) or "shading"
+    ///     (shape from shading: slopes facing the light are bright)
+    ///   - "light_azimuth", "light_elevation": light direction in degrees for "shading"
+    ///     (azimuth 90 = from the top of the image; omit both to estimate the azimuth)
+    ///   - "tiling": keep the shading height tileable (default true)
+    ///   - "normal_mode": "single" (default, pixel-level detail) or "multiscale" (normals of
+    ///     four pyramid levels combined, so large forms show up too)
+    ///   - "normal_fine", "normal_medium", "normal_coarse", "normal_very_coarse": level weights
+    ///     for "multiscale" (default 1.0, 0.5, 0.25, 0.125; 0 disables a level);
+    ///     "normal_fine_strength" etc. set each level's bumpiness (default 4.0)
+    ///   - "blend_normal": path of a baked normal map in the DirectX convention (green down,
+    ///     like the generated normals); the generated normals are blended on top of it as
+    ///     detail (see `blend_normals`), with "blend_mode", "blend_strength",
+    ///     "blend_detail_tiling" and "blend_mask"
+    ///   - "roughness_mode": "variance" (default, one global formula) or "segmented"
+    ///     (per-material ranges, see `segment_materials`)
+    ///   - "material_count": materials to find for "segmented" (default 4, at most 16)
+    ///   - "material_mask": ID mask (one flat colour per material) used instead of clustering
+    ///   - "material_ranges": roughness range per material, darkest material first, as an
+    ///     array of `Vector2(min, max)`; missing entries centre on the material's own detail
+    ///   - "material_blend": feathering between materials in pixels (default 4)
+    ///
+    /// # Returns
+    /// Dictionary with keys: success, error, height_path, normal_path, roughness_path,
+    /// skipped (names of maps whose files already existed), source_color_space,
+    /// maps_color_space ("linear" or "untagged"), illumination_range (when de-lighting),
+    /// light_azimuth and light_elevation (shading mode), material_colors, material_coverage
+    /// and material_ranges (segmented roughness)
+    #[func]
+    fn generate_maps_with_config(
+        &mut self,
+        albedo_path: GString,
+        output_dir: GString,
+        config: Dictionary,
+    ) -> Dictionary {
         let mut result = Dictionary::new();
         let _ = result.insert("success", false);
         let _ = result.insert("error", "");
         let _ = result.insert("progress", 0);
 
+        let GenerationSettings {
+            output: config,
+            processing,
+            source_transfer,
+            delight,
+            height_mode,
+            light,
+            wrap,
+            normal_detail,
+            normal_blend,
+            materials,
+        } = match Self::generation_settings(&config) {
+            Ok(settings) => settings,
+            Err(e) => {
+                let _ = result.insert("error", e);
+                return result;
+            }
+        };
+
         // Convert GString to String
         let path_str = albedo_path.to_string();
         let output_str = output_dir.to_string();
@@ -58,14 +204,7 @@ impl TextureGenerator {
         let _ = result.insert("progress", 10);
 
         // Determine output directory
-        let output_path = if output_str.is_empty() {
-            PathBuf::from(&path_str)
-                .parent()
-                .unwrap_or(Path::new(""))
-                .to_path_buf()
-        } else {
-            PathBuf::from(&output_str)
-        };
+        let output_path = Self::output_directory(Path::new(&path_str), &output_str);
 
         let stem = PathBuf::from(&path_str)
             .file_stem()
@@ -76,249 +215,2414 @@ impl TextureGenerator {
         // Generate maps - height and normal can be parallelized
         godot_print!("🚀 Generating maps (using multi-threading)...");
 
-        let albedo_clone = albedo_image.clone();
-
-        // Generate height and normal in parallel using rayon::join
-        let (height_map, normal_map) = rayon::join(
-            || {
-                godot_print!("  ⛰️  Generating height map...");
-                HeightMapGenerator::generate(&albedo_image)
-            },
-            || {
-                // Normal map needs height map, so generate it here
-                let height = HeightMapGenerator::generate(&albedo_clone);
-                godot_print!("  🌊 Generating normal map...");
-                NormalMapGenerator::generate(&height)
-            },
+        let is_float = matches!(
+            albedo_image,
+            DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_)
+        );
+        let transfer = source_transfer.unwrap_or_else(|| {
+            Self::resolve_path(&path_str)
+                .map(|path| TransferFunction::detect(&path))
+                .unwrap_or(TransferFunction::Srgb)
+        });
+        godot_print!(
+            "  🎨 Source colour space: {} ({} processing)",
+            transfer.name(),
+            if processing == ColorProcessing::Legacy { "legacy" } else { "linear" }
         );
 
-        // Generate roughness map sequentially (uses albedo)
-        godot_print!("  ✨ Generating roughness map...");
-        let roughness_map = RoughnessMapGenerator::generate(&albedo_image);
+        // De-lit albedo feeds the height (and normal) map; roughness keeps the original
+        let delit = delight.map(|options| {
+            godot_print!("  💡 Removing baked lighting...");
+            let report = AlbedoDelighter::delight(&albedo_image, transfer, &options);
+            let _ = result.insert("illumination_range", report.illumination_range);
+            report.image
+        });
+        let height_source = delit.as_ref().unwrap_or(&albedo_image);
 
-        let _ = result.insert("progress", 70);
+        let use_float_pipeline =
+            is_float || processing == ColorProcessing::Linear || height_mode == HeightMode::ShapeFromShading;
+        let (height_map, normal_map, roughness_map) = if use_float_pipeline {
+            // Float height is generated once and feeds the normal map unquantized
+            let luminance = ColorManagement::luminance(&albedo_image, transfer, processing);
+            let delit_luminance = delit
+                .as_ref()
+                .map(|image| ColorManagement::luminance(image, transfer, processing));
+            let height_luminance = delit_luminance.as_ref().unwrap_or(&luminance);
 
-        // Build output paths
-        let height_path = output_path.join(format!("{}_height.png", stem));
-        let normal_path = output_path.join(format!("{}_normal.png", stem));
-        let roughness_path = output_path.join(format!("{}_roughness.png", stem));
+            let light = (height_mode == HeightMode::ShapeFromShading).then(|| {
+                let light = light.unwrap_or_else(|| LightDirection::estimate(height_luminance));
+                godot_print!(
+                    "  🔦 Shape from shading, light azimuth {:.0}°, elevation {:.0}°",
+                    light.azimuth,
+                    light.elevation
+                );
+                let _ = result.insert("light_azimuth", light.azimuth);
+                let _ = result.insert("light_elevation", light.elevation);
+                light
+            });
 
-        // Save all images
-        godot_print!("💾 Saving generated maps...");
+            godot_print!("  ⛰️  Generating height and roughness maps...");
+            let (height, roughness) = rayon::join(
+                || match light {
+                    Some(light) => HeightMapGenerator::generate_from_shading(height_luminance, light, wrap),
+                    None => HeightMapGenerator::generate_from_luminance(height_luminance),
+                },
+                || RoughnessMapGenerator::generate_from_luminance(&luminance),
+            );
+            godot_print!("  🌊 Generating normal map...");
+            let normal = match &normal_detail {
+                Some(detail) => NormalMapGenerator::generate_multi_scale(&height, detail),
+                None => NormalMapGenerator::generate_from_f32(&height),
+            };
 
-        if let Err(e) = height_map.save(&height_path) {
-            let _ = result.insert("error", format!("Failed to save height map: {}", e));
-            return result;
-        }
-        godot_print!("  ✓ Height map: {}", height_path.display());
+            // Integer sources get a 16-bit height, which "auto" writes as a 16-bit PNG
+            let height = MapImage::Float(height);
+            let height = if is_float { height } else { MapImage::Image(height.to_dynamic()) };
+            (height, normal, roughness)
+        } else {
+            let albedo_clone = height_source.clone();
 
-        if let Err(e) = normal_map.save(&normal_path) {
-            let _ = result.insert("error", format!("Failed to save normal map: {}", e));
-            return result;
-        }
-        godot_print!("  ✓ Normal map: {}", normal_path.display());
+            // Generate height and normal in parallel using rayon::join
+            let (height, normal) = rayon::join(
+                || {
+                    godot_print!("  ⛰️  Generating height map...");
+                    HeightMapGenerator::generate(height_source)
+                },
+                || {
+                    // Normal map needs height map, so generate it here
+                    let height = HeightMapGenerator::generate(&albedo_clone);
+                    godot_print!("  🌊 Generating normal map...");
+                    match &normal_detail {
+                        Some(detail) => NormalMapGenerator::generate_multi_scale(&height.to_luma32f(), detail),
+                        None => NormalMapGenerator::generate(&height),
+                    }
+                },
+            );
 
-        if let Err(e) = roughness_map.save(&roughness_path) {
-            let _ = result.insert("error", format!("Failed to save roughness map: {}", e));
-            return result;
+            // Generate roughness map sequentially (uses albedo)
+            godot_print!("  ✨ Generating roughness map...");
+            let roughness = RoughnessMapGenerator::generate(&albedo_image);
+            (MapImage::Image(height), normal, roughness)
+        };
+
+        // Per-material ranges replace the single global roughness scale
+        let roughness_map = match materials {
+            Some(materials) => {
+                godot_print!("  🧱 Segmenting materials...");
+                let segmentation = match self.segmentation(&albedo_image, transfer, &materials) {
+                    Ok(segmentation) => segmentation,
+                    Err(e) => {
+                        let _ = result.insert("error", e);
+                        return result;
+                    }
+                };
+                let (roughness, ranges) = MaterialSegmenter::roughness(
+                    &segmentation,
+                    &roughness_map,
+                    &materials.ranges,
+                    materials.blend_radius,
+                );
+                Self::insert_materials(&mut result, &segmentation);
+                let _ = result.insert(
+                    "material_ranges",
+                    ranges.iter().map(|&(min, max)| Vector2::new(min, max)).collect::<PackedVector2Array>(),
+                );
+                roughness
+            }
+            None => roughness_map,
+        };
+
+        // Generated normals become the detail layer on top of a baked base
+        let normal_map = match normal_blend {
+            Some(layer) => {
+                godot_print!("  🧩 Blending normals onto {}...", layer.base_path);
+                let base = match self.load_image(&layer.base_path) {
+                    Ok(image) => image,
+                    Err(e) => {
+                        let _ = result.insert("error", format!("Failed to load blend base: {}", e));
+                        return result;
+                    }
+                };
+                let mask = match layer.mask_path.as_deref().map(|path| self.load_image(path)).transpose() {
+                    Ok(mask) => mask,
+                    Err(e) => {
+                        let _ = result.insert("error", format!("Failed to load blend mask: {}", e));
+                        return result;
+                    }
+                };
+                NormalBlender::blend(&base, &normal_map, mask.as_ref(), &layer.options)
+            }
+            None => normal_map,
+        };
+
+        let _ = result.insert("progress", 70);
+
+        // Save all images
+        godot_print!("💾 Saving generated maps...");
+
+        let mut skipped = PackedStringArray::new();
+        for (map, label, image) in [
+            ("height", "Height", height_map),
+            ("normal", "Normal", MapImage::Image(normal_map)),
+            ("roughness", "Roughness", MapImage::Image(roughness_map)),
+        ] {
+            match config.save_map(&output_path, &stem, map, &image) {
+                Ok(saved) => {
+                    if saved.skipped {
+                        godot_print!("  ⏭️ {} map exists, skipped: {}", label, saved.path.display());
+                        skipped.push(map.into());
+                    } else {
+                        godot_print!("  ✓ {} map: {}", label, saved.path.display());
+                    }
+                    let _ = result.insert(format!("{}_path", map), saved.path.to_string_lossy().to_string());
+                }
+                Err(e) => {
+                    let _ = result.insert("error", format!("Failed to save {} map: {}", map, e));
+                    return result;
+                }
+            }
         }
-        godot_print!("  ✓ Roughness map: {}", roughness_path.display());
 
         // Success!
         let _ = result.insert("success", true);
         let _ = result.insert("progress", 100);
-        let _ = result.insert("height_path", height_path.to_string_lossy().to_string());
-        let _ = result.insert("normal_path", normal_path.to_string_lossy().to_string());
+        let _ = result.insert("skipped", skipped);
+        let _ = result.insert("source_color_space", transfer.name());
         let _ = result.insert(
-            "roughness_path",
-            roughness_path.to_string_lossy().to_string(),
+            "maps_color_space",
+            if config.color_space == ColorSpace::Linear { "linear" } else { "untagged" },
         );
 
         godot_print!("🎉 All maps generated successfully!");
         result
     }
 
-    /// Pack 4 individual textures into 2 Terrain3D-optimized DDS files (BC3/DXT5).
+    /// Remove baked lighting (shading, ambient occlusion, highlights) from a photo-sourced albedo.
+    /// Writes `{stem}_delit.png` (EXR for float sources) in the source's colour space.
     ///
     /// # Arguments
-    /// * `albedo_path` - Path to the Albedo texture (RGB source for File 1)
-    /// * `height_path` - Path to the Height texture (Alpha for File 1)
-    /// * `normal_path` - Path to the Normal texture (RGB source for File 2)
-    /// * `roughness_path` - Path to the Roughness texture (Alpha for File 2)
-    /// * `output_dir` - Output directory for packed DDS files
+    /// * `albedo_path` - Path to the albedo texture
+    /// * `output_dir` - Optional output directory (if empty, saves next to source)
+    /// * `options` - Dictionary, every key optional:
+    ///   - "strength": how much large-scale lighting is flattened (0.0-1.0, default 1.0)
+    ///   - "radius": size of the lighting estimate in pixels (default 0 = 1/8 of the image)
+    ///   - "occlusion": how much ambient occlusion is lifted (0.0-1.0, default 0.5)
+    ///   - "highlights": how much specular highlights are compressed (0.0-1.0, default 0.5)
+    ///   - "source_color_space": as in `generate_maps_with_config`
+    ///   - "existing": "overwrite" (default), "skip" or "version"
     ///
     /// # Returns
-    /// Dictionary with keys: success, error, albedo_h_path, normal_r_path
+    /// Dictionary with keys: success, error, output_path, skipped (["delit"] if the file
+    /// already existed), source_color_space, illumination_range (brightest / darkest removed lighting)
     #[func]
-    fn pack_terrain_3d_manual(
-        &mut self,
-        albedo_path: GString,
-        height_path: GString,
-        normal_path: GString,
-        roughness_path: GString,
-        output_dir: GString,
-    ) -> Dictionary {
+    fn delight_albedo(&mut self, albedo_path: GString, output_dir: GString, options: Dictionary) -> Dictionary {
         let mut result = Dictionary::new();
         let _ = result.insert("success", false);
         let _ = result.insert("error", "");
 
-        // Convert paths
-        let albedo_str = albedo_path.to_string();
-        let height_str = height_path.to_string();
-        let normal_str = normal_path.to_string();
-        let roughness_str = roughness_path.to_string();
-        let output_str = output_dir.to_string();
-
-        godot_print!("📦 Starting Terrain3D Channel Packing...");
-
-        // Load images
-        let albedo = match self.load_image(&albedo_str) {
-            Ok(img) => img,
+        let get = |key: &str| options.get(key).map(|value| value.stringify().to_string());
+        let settings = Self::delight_options(&options, "").and_then(|delight| {
+            let transfer = TransferFunction::from_name(&get("source_color_space").unwrap_or_default())?;
+            let existing = ExistingFilePolicy::from_name(&get("existing").unwrap_or_default())?;
+            Ok((delight, transfer, existing))
+        });
+        let (delight, transfer, existing) = match settings {
+            Ok(settings) => settings,
             Err(e) => {
-                let _ = result.insert("error", format!("Failed to load Albedo: {}", e));
+                let _ = result.insert("error", e);
                 return result;
             }
         };
-        let height = match self.load_image(&height_str) {
-            Ok(img) => img,
+
+        let path_str = albedo_path.to_string();
+        let albedo = match self.load_image(&path_str) {
+            Ok(image) => image,
             Err(e) => {
-                let _ = result.insert("error", format!("Failed to load Height: {}", e));
+                let _ = result.insert("error", format!("Failed to load image: {}", e));
                 return result;
             }
         };
-        let normal = match self.load_image(&normal_str) {
-            Ok(img) => img,
+        let source_path = Self::resolve_path(&path_str).unwrap_or_else(|_| PathBuf::from(&path_str));
+        let transfer = transfer.unwrap_or_else(|| TransferFunction::detect(&source_path));
+
+        let directory = Self::output_directory(&source_path, &output_dir.to_string());
+        let stem = source_path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+
+        godot_print!("💡 De-lighting {} ({})...", path_str, transfer.name());
+        let _ = result.insert("source_color_space", transfer.name());
+        let report = AlbedoDelighter::delight(&albedo, transfer, &delight);
+        let color_space = match transfer {
+            TransferFunction::Srgb => ColorSpace::Srgb,
+            TransferFunction::Linear => ColorSpace::Linear,
+            TransferFunction::Gamma(_) => ColorSpace::Unspecified,
+        };
+        if Self::insert_converted_output(&mut result, ("delit", &report.image, color_space), &directory, &stem, existing) {
+            godot_print!("  💡 Removed lighting range {:.2}x", report.illumination_range);
+            let _ = result.insert("illumination_range", report.illumination_range);
+        }
+        result
+    }
+
+    /// Reconstruct a 16-bit height map from a tangent-space normal map.
+    /// Writes `{stem}_height.png` (a trailing `_normal`/`_n` is dropped from the stem).
+    ///
+    /// # Arguments
+    /// * `normal_path` - Path to the normal map
+    /// * `output_dir` - Optional output directory (if empty, saves next to source)
+    /// * `options` - Dictionary, every key optional:
+    ///   - "convention": "opengl" (default, green up, as Godot) or "directx" (green down,
+    ///     as written by `generate_maps`)
+    ///   - "tiling": treat the texture as tileable (default true); false mirrors the
+    ///     edges instead, for non-repeating textures
+    ///   - "existing": "overwrite" (default), "skip" or "version"
+    ///
+    /// # Returns
+    /// Dictionary with keys: success, error, output_path, skipped (["height"] if the file
+    /// already existed), relief (height difference in pixels between the lowest and highest point)
+    #[func]
+    fn height_from_normal(&mut self, normal_path: GString, output_dir: GString, options: Dictionary) -> Dictionary {
+        let mut result = Dictionary::new();
+        let _ = result.insert("success", false);
+        let _ = result.insert("error", "");
+
+        let get = |key: &str| options.get(key).map(|value| value.stringify().to_string());
+        let settings = NormalConvention::from_name(&get("convention").unwrap_or_default()).and_then(|convention| {
+            let existing = ExistingFilePolicy::from_name(&get("existing").unwrap_or_default())?;
+            Ok((convention, existing))
+        });
+        let (convention, existing) = match settings {
+            Ok(settings) => settings,
             Err(e) => {
-                let _ = result.insert("error", format!("Failed to load Normal: {}", e));
+                let _ = result.insert("error", e);
                 return result;
             }
         };
-        let roughness = match self.load_image(&roughness_str) {
-            Ok(img) => img,
+        let wrap = get("tiling").is_none_or(|value| !value.eq_ignore_ascii_case("false"));
+
+        let path_str = normal_path.to_string();
+        let normal_map = match self.load_image(&path_str) {
+            Ok(image) => image,
             Err(e) => {
-                let _ = result.insert("error", format!("Failed to load Roughness: {}", e));
+                let _ = result.insert("error", format!("Failed to load normal map: {}", e));
                 return result;
             }
         };
 
-        // Determine output paths
-        let output_path = if output_str.is_empty() {
-            PathBuf::from(&albedo_str)
-                .parent()
-                .unwrap_or(Path::new(""))
-                .to_path_buf()
-        } else {
-            PathBuf::from(&output_str)
-        };
-
-        let stem = PathBuf::from(&albedo_str)
-            .file_stem()
-            .unwrap_or(std::ffi::OsStr::new("terrain"))
-            .to_string_lossy()
-            .to_string();
-
-        let albedo_h_path = output_path.join(format!("{}_albedo_h.dds", stem));
-        let normal_r_path = output_path.join(format!("{}_normal_r.dds", stem));
+        let source_path = Self::resolve_path(&path_str).unwrap_or_else(|_| PathBuf::from(&path_str));
+        let directory = Self::output_directory(&source_path, &output_dir.to_string());
+        let stem = Self::material_stem(&source_path, &["_normal", "_Normal", "_nrm", "_n", "_N"]);
 
-        // Pack File 1: Albedo (RGB) + Height (A)
-        godot_print!("  📁 Packing Albedo + Height...");
-        if let Err(e) = crate::channel_packer::ChannelPacker::pack_and_save_dds(
-            &albedo,
-            &height,
-            &albedo_h_path,
-        ) {
-            let _ = result.insert("error", format!("Failed to pack Albedo+Height: {}", e));
-            return result;
+        godot_print!("⛰️ Integrating normals from {}...", path_str);
+        let reconstructed = HeightFromNormalGenerator::generate(&normal_map, convention, wrap);
+        let height = MapImage::Float(reconstructed.height).to_dynamic();
+        if Self::insert_converted_output(&mut result, ("height", &height, ColorSpace::Linear), &directory, &stem, existing) {
+            godot_print!("  ⛰️ Relief {:.1} px", reconstructed.relief);
+            let _ = result.insert("relief", reconstructed.relief);
         }
-        godot_print!("  ✓ {}", albedo_h_path.display());
+        result
+    }
 
-        // Pack File 2: Normal (RGB) + Roughness (A)
-        godot_print!("  📁 Packing Normal + Roughness...");
-        if let Err(e) = crate::channel_packer::ChannelPacker::pack_and_save_dds(
-            &normal,
-            &roughness,
-            &normal_r_path,
-        ) {
-            let _ = result.insert("error", format!("Failed to pack Normal+Roughness: {}", e));
-            return result;
-        }
-        godot_print!("  ✓ {}", normal_r_path.display());
+    /// Check a normal map for non-unit vectors, Z problems, the likely Y convention and banding.
+    ///
+    /// # Returns
+    /// Dictionary with keys: success, error, average_length, z_violation_percent,
+    /// invalid_percent, missing_z, inverted_z, convention ("opengl", "directx" or "unknown"),
+    /// convention_confidence (0.0-1.0), banding (share of empty levels, 0.0-1.0)
+    #[func]
+    fn analyze_normal_map(&mut self, normal_path: GString) -> Dictionary {
+        let mut result = Dictionary::new();
+        let _ = result.insert("success", false);
+        let _ = result.insert("error", "");
 
-        // Success
+        let normal_map = match self.load_image(&normal_path.to_string()) {
+            Ok(image) => image,
+            Err(e) => {
+                let _ = result.insert("error", format!("Failed to load normal map: {}", e));
+                return result;
+            }
+        };
+        let report = NormalAnalyzer::analyze(&normal_map);
+        Self::insert_normal_report(&mut result, &report);
         let _ = result.insert("success", true);
-        let _ = result.insert("albedo_h_path", albedo_h_path.to_string_lossy().to_string());
-        let _ = result.insert("normal_r_path", normal_r_path.to_string_lossy().to_string());
-
-        godot_print!("🎉 Terrain3D packing complete!");
         result
     }
 
-    /// Convert an image (jpg/jpeg) to PNG
+    /// Fix a normal map: renormalise or reconstruct Z from X/Y and flip Y to the wanted
+    /// convention. Writes `{stem}_repaired.png` at the source's bit depth (EXR for float sources).
     ///
     /// # Arguments
-    /// * `input_path` - Path to the source image
-    /// * `output_dir` - Optional output directory (if empty, saves next to source)
+    /// * `normal_path` - Normal map to repair
+    /// * `output_dir` - Optional output directory (if empty, saves next to the source)
+    /// * `options` - Dictionary, every key optional:
+    ///   - "convention": wanted convention, "opengl" (default) or "directx"; `generate_maps`
+    ///     writes "directx", so the default flips its output to OpenGL
+    ///   - "flip_y": "auto" (default, flip when the detected convention differs), "true" or "false"
+    ///   - "reconstruct_z": "auto" (default, when Z is missing, inverted or invalid texels
+    ///     exceed 1%), "true" or "false" (renormalise only)
+    ///   - "existing": "overwrite" (default), "skip" or "version"
     ///
     /// # Returns
-    /// Dictionary with keys: success, error, output_path
+    /// Dictionary with keys: success, error, output_path, skipped (["repaired"] if the file
+    /// already existed), flipped_y, reconstructed_z,
+    /// and the `analyze_normal_map` keys describing the source
     #[func]
-    fn convert_to_png(&mut self, input_path: GString, output_dir: GString) -> Dictionary {
+    fn repair_normal_map(&mut self, normal_path: GString, output_dir: GString, options: Dictionary) -> Dictionary {
         let mut result = Dictionary::new();
         let _ = result.insert("success", false);
         let _ = result.insert("error", "");
 
-        let input_str = input_path.to_string();
-        let output_dir_str = output_dir.to_string();
-
-        godot_print!("♻️ Converting image to PNG: {}", input_str);
+        let get = |key: &str| options.get(key).map(|value| value.stringify().to_string());
+        let tri_state = |key: &str| -> Result<Option<bool>, String> {
+            match get(key).unwrap_or_default().trim().to_ascii_lowercase().as_str() {
+                "" | "auto" => Ok(None),
+                "true" => Ok(Some(true)),
+                "false" => Ok(Some(false)),
+                other => Err(format!("Invalid {} '{}' (expected auto, true or false)", key, other)),
+            }
+        };
+        let settings = NormalConvention::from_name(&get("convention").unwrap_or_default()).and_then(|convention| {
+            let existing = ExistingFilePolicy::from_name(&get("existing").unwrap_or_default())?;
+            Ok((convention, tri_state("flip_y")?, tri_state("reconstruct_z")?, existing))
+        });
+        let (convention, flip_y, reconstruct_z, existing) = match settings {
+            Ok(settings) => settings,
+            Err(e) => {
+                let _ = result.insert("error", e);
+                return result;
+            }
+        };
 
-        // Load source image
-        let image = match self.load_image(&input_str) {
-            Ok(img) => img,
+        let path_str = normal_path.to_string();
+        let normal_map = match self.load_image(&path_str) {
+            Ok(image) => image,
             Err(e) => {
-                let _ = result.insert("error", format!("Failed to load image: {}", e));
+                let _ = result.insert("error", format!("Failed to load normal map: {}", e));
                 return result;
             }
         };
+        let report = NormalAnalyzer::analyze(&normal_map);
+        Self::insert_normal_report(&mut result, &report);
+        let repair = RepairOptions {
+            reconstruct_z: reconstruct_z
+                .unwrap_or(report.missing_z || report.inverted_z || report.invalid_percent > 1.0),
+            flip_y: flip_y.unwrap_or(report.convention.is_some_and(|detected| detected != convention)),
+        };
 
-        // Determine output path
-        let input_path_buf = PathBuf::from(&input_str);
-        let stem = input_path_buf
-            .file_stem()
-            .unwrap_or(std::ffi::OsStr::new("image"))
-            .to_string_lossy()
-            .to_string();
+        let source_path = Self::resolve_path(&path_str).unwrap_or_else(|_| PathBuf::from(&path_str));
+        let directory = Self::output_directory(&source_path, &output_dir.to_string());
+        let stem = source_path.file_stem().unwrap_or_default().to_string_lossy().to_string();
 
-        let output_path = if output_dir_str.is_empty() {
-            input_path_buf.parent().unwrap_or(Path::new("")).join(format!("{}.png", stem))
-        } else {
-            PathBuf::from(&output_dir_str).join(format!("{}.png", stem))
+        godot_print!(
+            "🩹 Repairing {} ({:.1}% invalid texels{}{})...",
+            path_str,
+            report.invalid_percent,
+            if repair.reconstruct_z { ", reconstructing Z" } else { "" },
+            if repair.flip_y { ", flipping Y" } else { "" }
+        );
+        let repaired = NormalAnalyzer::repair(&normal_map, &repair);
+        if Self::insert_converted_output(&mut result, ("repaired", &repaired, ColorSpace::Linear), &directory, &stem, existing) {
+            let _ = result.insert("flipped_y", repair.flip_y);
+            let _ = result.insert("reconstructed_z", repair.reconstruct_z);
+        }
+        result
+    }
+
+    /// Blend a detail normal map onto a base normal map (e.g. generated detail onto a baked
+    /// macro normal). Writes `{base_stem}_blended.png` at the base's size and bit depth
+    /// (EXR for float bases).
+    ///
+    /// # Arguments
+    /// * `base_path` - Base normal map
+    /// * `detail_path` - Detail normal map (same convention as the base)
+    /// * `output_dir` - Optional output directory (if empty, saves next to the base)
+    /// * `options` - Dictionary, every key optional:
+    ///   - "mode": "rnm" (default, Reoriented Normal Mapping), "udn", "whiteout" or "linear"
+    ///   - "strength": detail intensity (default 1.0; 0 = base only)
+    ///   - "detail_tiling": times the detail repeats across the base (default 1.0 = resized
+    ///     to the base)
+    ///   - "mask": path of a grayscale mask, white = full detail
+    ///   - "existing": "overwrite" (default), "skip" or "version"
+    ///
+    /// # Returns
+    /// Dictionary with keys: success, error, output_path, skipped
+    #[func]
+    fn blend_normals(
+        &mut self,
+        base_path: GString,
+        detail_path: GString,
+        output_dir: GString,
+        options: Dictionary,
+    ) -> Dictionary {
+        let mut result = Dictionary::new();
+        let _ = result.insert("success", false);
+        let _ = result.insert("error", "");
+
+        let get = |key: &str| options.get(key).map(|value| value.stringify().to_string());
+        let settings = Self::normal_blend_options(&options, "").and_then(|blend| {
+            let existing = ExistingFilePolicy::from_name(&get("existing").unwrap_or_default())?;
+            Ok((blend, existing))
+        });
+        let (blend, existing) = match settings {
+            Ok(settings) => settings,
+            Err(e) => {
+                let _ = result.insert("error", e);
+                return result;
+            }
         };
 
-        // Save as PNG
-        if let Err(e) = image.save(&output_path) {
-            let _ = result.insert("error", format!("Failed to save PNG: {}", e));
-            return result;
-        }
+        let base_str = base_path.to_string();
+        let loaded = self.load_image(&base_str).map_err(|e| format!("Failed to load base: {}", e)).and_then(|base| {
+            let detail = self
+                .load_image(&detail_path.to_string())
+                .map_err(|e| format!("Failed to load detail: {}", e))?;
+            let mask = get("mask")
+                .filter(|path| !path.trim().is_empty())
+                .map(|path| self.load_image(&path))
+                .transpose()
+                .map_err(|e| format!("Failed to load mask: {}", e))?;
+            Ok((base, detail, mask))
+        });
+        let (base, detail, mask) = match loaded {
+            Ok(layers) => layers,
+            Err(e) => {
+                let _ = result.insert("error", e);
+                return result;
+            }
+        };
 
-        godot_print!("  ✓ Saved PNG: {}", output_path.display());
+        let source_path = Self::resolve_path(&base_str).unwrap_or_else(|_| PathBuf::from(&base_str));
+        let directory = Self::output_directory(&source_path, &output_dir.to_string());
+        let stem = source_path.file_stem().unwrap_or_default().to_string_lossy().to_string();
 
-        // Success
-        let _ = result.insert("success", true);
-        let _ = result.insert("output_path", output_path.to_string_lossy().to_string());
+        godot_print!("🧩 Blending normals ({:?})...", blend.mode);
+        let blended = NormalBlender::blend(&base, &detail, mask.as_ref(), &blend);
+        Self::insert_converted_output(&mut result, ("blended", &blended, ColorSpace::Linear), &directory, &stem, existing);
         result
     }
 
-    /// Load an image from a Godot resource path or filesystem path
-    fn load_image(&self, path: &str) -> Result<DynamicImage, String> {
-        let absolute_path = if path.starts_with("res://") {
-            let relative = path.strip_prefix("res://").unwrap_or(path);
-            let project_path = std::env::current_dir()
-                .map_err(|e| format!("Failed to get current directory: {}", e))?;
-            project_path.join(relative)
-        } else {
-            PathBuf::from(path)
+    /// Convert a glossiness map to roughness. Writes `{stem}_roughness.png` at the source's
+    /// bit depth (a trailing `_gloss`/`_glossiness` is dropped from the name).
+    ///
+    /// # Arguments
+    /// * `gloss_path` - Glossiness map
+    /// * `output_dir` - Optional output directory (if empty, saves next to the source)
+    /// * `options` - Dictionary, every key optional:
+    ///   - "remap": "linear" (default, roughness = 1 - gloss) or "perceptual"
+    ///     (roughness = sqrt(1 - gloss), for gloss authored in GGX alpha space)
+    ///   - "channel": "r", "g", "b" or "a" (default: grey), e.g. "a" for gloss stored in
+    ///     the specular map's alpha
+    ///   - "existing": "overwrite" (default), "skip" or "version"
+    ///
+    /// # Returns
+    /// Dictionary with keys: success, error, output_path, skipped
+    #[func]
+    fn convert_gloss_to_roughness(&mut self, gloss_path: GString, output_dir: GString, options: Dictionary) -> Dictionary {
+        let mut result = Dictionary::new();
+        let _ = result.insert("success", false);
+        let _ = result.insert("error", "");
+
+        let get = |key: &str| options.get(key).map(|value| value.stringify().to_string());
+        let settings = GlossRemap::from_name(&get("remap").unwrap_or_default()).and_then(|remap| {
+            let channel = match get("channel").unwrap_or_default().trim().to_ascii_lowercase().as_str() {
+                "" | "gray" | "grey" => None,
+                "r" => Some(0),
+                "g" => Some(1),
+                "b" => Some(2),
+                "a" => Some(3),
+                other => return Err(format!("Unknown channel '{}' (expected r, g, b or a)", other)),
+            };
+            let existing = ExistingFilePolicy::from_name(&get("existing").unwrap_or_default())?;
+            Ok((remap, channel, existing))
+        });
+        let (remap, channel, existing) = match settings {
+            Ok(settings) => settings,
+            Err(e) => {
+                let _ = result.insert("error", e);
+                return result;
+            }
+        };
+
+        let path_str = gloss_path.to_string();
+        let gloss = match self.load_image(&path_str) {
+            Ok(image) => image,
+            Err(e) => {
+                let _ = result.insert("error", format!("Failed to load gloss map: {}", e));
+                return result;
+            }
         };
+        let source_path = Self::resolve_path(&path_str).unwrap_or_else(|_| PathBuf::from(&path_str));
+        let directory = Self::output_directory(&source_path, &output_dir.to_string());
+        let stem = Self::material_stem(&source_path, &["_glossiness", "_Glossiness", "_gloss", "_Gloss"]);
+
+        godot_print!("✨ Converting gloss to roughness: {}", path_str);
+        let roughness = SpecularGlossConverter::gloss_to_roughness(&gloss, channel, remap);
+        Self::insert_converted_output(&mut result, ("roughness", &roughness, ColorSpace::Linear), &directory, &stem, existing);
+        result
+    }
+
+    /// Convert specular-gloss maps to Godot's metallic-roughness workflow.
+    /// Writes `{stem}_albedo.png`, `{stem}_metallic.png` and, with a gloss map,
+    /// `{stem}_roughness.png` (a trailing `_diffuse`/`_diff` is dropped from the name).
+    /// Colour maps of float sources are written as EXR.
+    ///
+    /// # Arguments
+    /// * `diffuse_path` - Diffuse colour map
+    /// * `specular_path` - Specular colour map of the same size
+    /// * `output_dir` - Optional output directory (if empty, saves next to the diffuse map)
+    /// * `options` - Dictionary, every key optional:
+    ///   - "gloss": path of the glossiness map, or "alpha" to read it from the specular alpha
+    ///   - "remap": gloss remapping as in `convert_gloss_to_roughness`
+    ///   - "source_color_space": encoding of the colour maps (default auto)
+    ///   - "existing": "overwrite" (default), "skip" or "version"
+    ///
+    /// # Returns
+    /// Dictionary with keys: success, error, albedo_path, metallic_path, roughness_path
+    /// (with a gloss map), skipped (names of maps whose files already existed)
+    #[func]
+    fn convert_specular_to_metallic(
+        &mut self,
+        diffuse_path: GString,
+        specular_path: GString,
+        output_dir: GString,
+        options: Dictionary,
+    ) -> Dictionary {
+        let mut result = Dictionary::new();
+        let _ = result.insert("success", false);
+        let _ = result.insert("error", "");
+
+        let get = |key: &str| options.get(key).map(|value| value.stringify().to_string());
+        let settings = GlossRemap::from_name(&get("remap").unwrap_or_default()).and_then(|remap| {
+            let transfer = TransferFunction::from_name(&get("source_color_space").unwrap_or_default())?;
+            let existing = ExistingFilePolicy::from_name(&get("existing").unwrap_or_default())?;
+            Ok((remap, transfer, existing))
+        });
+        let (remap, transfer, existing) = match settings {
+            Ok(settings) => settings,
+            Err(e) => {
+                let _ = result.insert("error", e);
+                return result;
+            }
+        };
+
+        let diffuse_str = diffuse_path.to_string();
+        let gloss_option = get("gloss").filter(|gloss| !gloss.trim().is_empty());
+        let loaded = self.load_image(&diffuse_str).map_err(|e| format!("Failed to load diffuse map: {}", e)).and_then(|diffuse| {
+            let specular = self
+                .load_image(&specular_path.to_string())
+                .map_err(|e| format!("Failed to load specular map: {}", e))?;
+            let gloss = match gloss_option.as_deref() {
+                Some(gloss) if gloss.eq_ignore_ascii_case("alpha") => Some((specular.clone(), Some(3))),
+                Some(path) => Some((
+                    self.load_image(path).map_err(|e| format!("Failed to load gloss map: {}", e))?,
+                    None,
+                )),
+                None => None,
+            };
+            Ok((diffuse, specular, gloss))
+        });
+        let (diffuse, specular, gloss) = match loaded {
+            Ok(maps) => maps,
+            Err(e) => {
+                let _ = result.insert("error", e);
+                return result;
+            }
+        };
+
+        let source_path = Self::resolve_path(&diffuse_str).unwrap_or_else(|_| PathBuf::from(&diffuse_str));
+        let transfer = transfer.unwrap_or_else(|| TransferFunction::detect(&source_path));
+        let directory = Self::output_directory(&source_path, &output_dir.to_string());
+        let stem = Self::material_stem(&source_path, &["_diffuse", "_Diffuse", "_diff", "_Diff"]);
+
+        godot_print!("🔁 Converting specular-gloss to metallic-roughness: {}", diffuse_str);
+        let maps = match SpecularGlossConverter::to_metallic(&diffuse, &specular, transfer) {
+            Ok(maps) => maps,
+            Err(e) => {
+                let _ = result.insert("error", e);
+                return result;
+            }
+        };
+        let roughness = gloss.map(|(gloss, channel)| SpecularGlossConverter::gloss_to_roughness(&gloss, channel, remap));
+
+        let mut outputs = vec![
+            ("albedo", &maps.albedo, ColorSpace::Srgb),
+            ("metallic", &maps.metallic, ColorSpace::Linear),
+        ];
+        if let Some(roughness) = &roughness {
+            outputs.push(("roughness", roughness, ColorSpace::Linear));
+        }
+        Self::insert_converted(&mut result, &outputs, &directory, &stem, existing);
+        result
+    }
+
+    /// Convert metallic-roughness maps to the specular-gloss workflow.
+    /// Writes `{stem}_diffuse.png`, `{stem}_specular.png` and, with a roughness map,
+    /// `{stem}_gloss.png` (a trailing `_albedo`/`_basecolor` is dropped from the name).
+    /// Colour maps of float sources are written as EXR.
+    ///
+    /// # Arguments
+    /// * `albedo_path` - Base colour map
+    /// * `metallic_path` - Metallic map of the same size
+    /// * `output_dir` - Optional output directory (if empty, saves next to the albedo)
+    /// * `options` - Dictionary, every key optional:
+    ///   - "roughness": path of the roughness map (e.g. a `generate_maps` output)
+    ///   - "remap": gloss remapping as in `convert_gloss_to_roughness`
+    ///   - "source_color_space": encoding of the albedo (default auto)
+    ///   - "existing": "overwrite" (default), "skip" or "version"
+    ///
+    /// # Returns
+    /// Dictionary with keys: success, error, diffuse_path, specular_path, gloss_path
+    /// (with a roughness map), skipped
+    #[func]
+    fn convert_metallic_to_specular(
+        &mut self,
+        albedo_path: GString,
+        metallic_path: GString,
+        output_dir: GString,
+        options: Dictionary,
+    ) -> Dictionary {
+        let mut result = Dictionary::new();
+        let _ = result.insert("success", false);
+        let _ = result.insert("error", "");
+
+        let get = |key: &str| options.get(key).map(|value| value.stringify().to_string());
+        let settings = GlossRemap::from_name(&get("remap").unwrap_or_default()).and_then(|remap| {
+            let transfer = TransferFunction::from_name(&get("source_color_space").unwrap_or_default())?;
+            let existing = ExistingFilePolicy::from_name(&get("existing").unwrap_or_default())?;
+            Ok((remap, transfer, existing))
+        });
+        let (remap, transfer, existing) = match settings {
+            Ok(settings) => settings,
+            Err(e) => {
+                let _ = result.insert("error", e);
+                return result;
+            }
+        };
+
+        let albedo_str = albedo_path.to_string();
+        let roughness_path = get("roughness").filter(|path| !path.trim().is_empty());
+        let loaded = self.load_image(&albedo_str).map_err(|e| format!("Failed to load albedo: {}", e)).and_then(|albedo| {
+            let metallic = self
+                .load_image(&metallic_path.to_string())
+                .map_err(|e| format!("Failed to load metallic map: {}", e))?;
+            let roughness = roughness_path
+                .map(|path| self.load_image(&path))
+                .transpose()
+                .map_err(|e| format!("Failed to load roughness map: {}", e))?;
+            Ok((albedo, metallic, roughness))
+        });
+        let (albedo, metallic, roughness) = match loaded {
+            Ok(maps) => maps,
+            Err(e) => {
+                let _ = result.insert("error", e);
+                return result;
+            }
+        };
+
+        let source_path = Self::resolve_path(&albedo_str).unwrap_or_else(|_| PathBuf::from(&albedo_str));
+        let transfer = transfer.unwrap_or_else(|| TransferFunction::detect(&source_path));
+        let directory = Self::output_directory(&source_path, &output_dir.to_string());
+        let stem = Self::material_stem(&source_path, &["_albedo", "_Albedo", "_basecolor", "_BaseColor"]);
+
+        godot_print!("🔁 Converting metallic-roughness to specular-gloss: {}", albedo_str);
+        let maps = match SpecularGlossConverter::to_specular(&albedo, &metallic, transfer) {
+            Ok(maps) => maps,
+            Err(e) => {
+                let _ = result.insert("error", e);
+                return result;
+            }
+        };
+        let gloss = roughness.map(|roughness| SpecularGlossConverter::roughness_to_gloss(&roughness, remap));
+
+        let mut outputs = vec![
+            ("diffuse", &maps.diffuse, ColorSpace::Srgb),
+            ("specular", &maps.specular, ColorSpace::Srgb),
+        ];
+        if let Some(gloss) = &gloss {
+            outputs.push(("gloss", gloss, ColorSpace::Linear));
+        }
+        Self::insert_converted(&mut result, &outputs, &directory, &stem, existing);
+        result
+    }
+
+    /// Find the material regions `generate_maps_with_config` uses for segmented roughness,
+    /// and write them as `{stem}_materials.png` (one flat colour per material, darkest first;
+    /// can be edited and passed back as "material_mask").
+    ///
+    /// # Arguments
+    /// * `albedo_path` - Path to the albedo texture
+    /// * `output_dir` - Optional output directory (if empty, saves next to the source)
+    /// * `options` - Dictionary, every key optional:
+    ///   - "material_count", "material_mask": as in `generate_maps_with_config`
+    ///   - "source_color_space": as in `generate_maps_with_config`
+    ///   - "existing": "overwrite" (default), "skip" or "version"
+    ///
+    /// # Returns
+    /// Dictionary with keys: success, error, output_path, skipped, material_colors
+    /// (darkest first), material_coverage (0.0-1.0 each)
+    #[func]
+    fn segment_materials(&mut self, albedo_path: GString, output_dir: GString, options: Dictionary) -> Dictionary {
+        let mut result = Dictionary::new();
+        let _ = result.insert("success", false);
+        let _ = result.insert("error", "");
+
+        let get = |key: &str| options.get(key).map(|value| value.stringify().to_string());
+        let settings = Self::material_options(&options).and_then(|materials| {
+            let transfer = TransferFunction::from_name(&get("source_color_space").unwrap_or_default())?;
+            let existing = ExistingFilePolicy::from_name(&get("existing").unwrap_or_default())?;
+            Ok((materials, transfer, existing))
+        });
+        let (materials, transfer, existing) = match settings {
+            Ok(settings) => settings,
+            Err(e) => {
+                let _ = result.insert("error", e);
+                return result;
+            }
+        };
+
+        let path_str = albedo_path.to_string();
+        let albedo = match self.load_image(&path_str) {
+            Ok(image) => image,
+            Err(e) => {
+                let _ = result.insert("error", format!("Failed to load image: {}", e));
+                return result;
+            }
+        };
+        let source_path = Self::resolve_path(&path_str).unwrap_or_else(|_| PathBuf::from(&path_str));
+        let transfer = transfer.unwrap_or_else(|| TransferFunction::detect(&source_path));
+        let directory = Self::output_directory(&source_path, &output_dir.to_string());
+        let stem = source_path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+
+        godot_print!("🧱 Segmenting materials: {}", path_str);
+        let segmentation = match self.segmentation(&albedo, transfer, &materials) {
+            Ok(segmentation) => segmentation,
+            Err(e) => {
+                let _ = result.insert("error", e);
+                return result;
+            }
+        };
+        Self::insert_materials(&mut result, &segmentation);
+
+        let preview = MaterialSegmenter::preview(&segmentation);
+        Self::insert_converted_output(&mut result, ("materials", &preview, ColorSpace::Srgb), &directory, &stem, existing);
+        result
+    }
+
+    /// Recover albedo and normals from photos of one surface under different known lights
+    /// (photometric stereo), then derive height from the normals and roughness from the albedo.
+    /// The height is 16-bit, so the default format writes it as a 16-bit PNG.
+    ///
+    /// # Arguments
+    /// * `image_paths` - At least 3 captures of the same size, taken from a fixed camera
+    /// * `light_directions` - One direction towards the light per capture (x right, y up,
+    ///   z towards the camera); the length is the light's relative intensity
+    /// * `output_dir` - Optional output directory (if empty, saves next to the first capture)
+    /// * `config` - Dictionary, every key optional:
+    ///   - "name": `{stem}` of the output files (default: first capture's name)
+    ///   - "convention": normal map convention, "opengl" (default) or "directx"
+    ///   - "template", "format", "*_format", "existing", "color_processing",
+    ///     "source_color_space", "tiling": as in `generate_maps_with_config`
+    ///
+    /// # Returns
+    /// Dictionary with keys: success, error, albedo_path, normal_path, height_path,
+    /// roughness_path, skipped, fit_error (RMS deviation from a matte surface, 0.0-1.0),
+    /// relief (height range in pixels)
+    #[func]
+    fn photometric_stereo(
+        &mut self,
+        image_paths: PackedStringArray,
+        light_directions: PackedVector3Array,
+        output_dir: GString,
+        config: Dictionary,
+    ) -> Dictionary {
+        let mut result = Dictionary::new();
+        let _ = result.insert("success", false);
+        let _ = result.insert("error", "");
+
+        let get = |key: &str| config.get(key).map(|value| value.stringify().to_string());
+        let settings = Self::generation_settings(&config).and_then(|settings| {
+            let convention = NormalConvention::from_name(&get("convention").unwrap_or_default())?;
+            Ok((settings, convention))
+        });
+        let (settings, convention) = match settings {
+            Ok(settings) => settings,
+            Err(e) => {
+                let _ = result.insert("error", e);
+                return result;
+            }
+        };
+
+        let paths: Vec<String> = image_paths.as_slice().iter().map(|path| path.to_string()).collect();
+        let Some(first_path) = paths.first() else {
+            let _ = result.insert("error", "No captures given");
+            return result;
+        };
+        let mut captures = Vec::with_capacity(paths.len());
+        for path in &paths {
+            match self.load_image(path) {
+                Ok(image) => captures.push(image),
+                Err(e) => {
+                    let _ = result.insert("error", format!("Failed to load capture {}: {}", path, e));
+                    return result;
+                }
+            }
+        }
+        let lights: Vec<nalgebra::Vector3<f32>> = light_directions
+            .as_slice()
+            .iter()
+            .map(|light| nalgebra::Vector3::new(light.x, light.y, light.z))
+            .collect();
+
+        let transfer = settings.source_transfer.unwrap_or_else(|| {
+            Self::resolve_path(first_path)
+                .map(|path| TransferFunction::detect(&path))
+                .unwrap_or(TransferFunction::Srgb)
+        });
+        godot_print!("📸 Solving photometric stereo from {} captures...", captures.len());
+        let solved = match PhotometricStereo::solve(&captures, &lights, transfer, convention) {
+            Ok(solved) => solved,
+            Err(e) => {
+                let _ = result.insert("error", e);
+                return result;
+            }
+        };
+        drop(captures);
+        godot_print!("  ✓ Fit error {:.3}", solved.fit_error);
+
+        godot_print!("  ⛰️  Integrating height and generating roughness...");
+        let (reconstructed, roughness) = rayon::join(
+            || HeightFromNormalGenerator::generate(&solved.normals, convention, settings.wrap),
+            || {
+                let luminance = ColorManagement::luminance(&solved.albedo, TransferFunction::Srgb, settings.processing);
+                RoughnessMapGenerator::generate_from_luminance(&luminance)
+            },
+        );
+
+        let source_path = Self::resolve_path(first_path).unwrap_or_else(|_| PathBuf::from(first_path));
+        let directory = Self::output_directory(&source_path, &output_dir.to_string());
+        let stem = get("name")
+            .filter(|name| !name.trim().is_empty())
+            .unwrap_or_else(|| source_path.file_stem().unwrap_or_default().to_string_lossy().to_string());
+
+        // 16-bit height, written as a 16-bit PNG unless a format is chosen
+        let mut skipped = PackedStringArray::new();
+        for (map, label, image) in [
+            ("albedo", "Albedo", MapImage::Image(solved.albedo)),
+            ("normal", "Normal", MapImage::Image(solved.normals)),
+            ("height", "Height", MapImage::Image(MapImage::Float(reconstructed.height).to_dynamic())),
+            ("roughness", "Roughness", MapImage::Image(roughness)),
+        ] {
+            match settings.output.save_map(&directory, &stem, map, &image) {
+                Ok(saved) => {
+                    if saved.skipped {
+                        godot_print!("  ⏭️ {} map exists, skipped: {}", label, saved.path.display());
+                        skipped.push(map.into());
+                    } else {
+                        godot_print!("  ✓ {} map: {}", label, saved.path.display());
+                    }
+                    let _ = result.insert(format!("{}_path", map), saved.path.to_string_lossy().to_string());
+                }
+                Err(e) => {
+                    let _ = result.insert("error", format!("Failed to save {} map: {}", map, e));
+                    return result;
+                }
+            }
+        }
+
+        let _ = result.insert("success", true);
+        let _ = result.insert("skipped", skipped);
+        let _ = result.insert("fit_error", solved.fit_error);
+        let _ = result.insert("relief", reconstructed.relief);
+        result
+    }
+
+    /// Pack 4 individual textures into 2 Terrain3D-optimized DDS or KTX2 files.
+    ///
+    /// # Arguments
+    /// * `albedo_path` - Path to the Albedo texture (RGB source for File 1)
+    /// * `height_path` - Path to the Height texture (Alpha for File 1)
+    /// * `normal_path` - Path to the Normal texture (RGB source for File 2)
+    /// * `roughness_path` - Path to the Roughness texture (Alpha for File 2)
+    /// * `output_dir` - Output directory for packed files
+    /// * `options` - Dictionary, every key optional:
+    ///   - "container": "dds" (default) or "ktx2"
+    ///   - "albedo_h_format": format for File 1: "bc1", "bc3" (default), "bc4", "bc5",
+    ///     "bc7", "rgba8" or "rgba16f"
+    ///   - "normal_r_format": format for File 2 (same choices)
+    ///   - "mip_filter": "kaiser" (default), "lanczos", "box" or "none"
+    ///   - "resize_policy": output size: "pad" (default, wrap-pad to a multiple of 4),
+    ///     "match" (resize the other inputs to the albedo's size, then pad), "pot" (nearest
+    ///     power of two), "fit" (downscale to "max_resolution") or "error". Inputs of
+    ///     different sizes are an error unless the policy is "match".
+    ///   - "max_resolution": largest side for the "fit" policy
+    ///   - "quality": block compression effort: "fast" (range fit), "normal" (default,
+    ///     cluster fit) or "high" (iterative cluster fit)
+    ///   - "normal_encoding": "raw" (default), "renormalize" (unit vectors before encoding)
+    ///     or "xy" (renormalized XY in RG, B cleared, Z reconstructed in the shader)
+    ///
+    /// # Returns
+    /// Dictionary with keys: success, error, albedo_h_path, normal_r_path,
+    /// resize_policy, resized_inputs (names of inputs resized by "match"), width, height,
+    /// normal_mean_angle_error, normal_max_angle_error (degrees between source and compressed normals)
+    #[func]
+    fn pack_terrain_3d_manual(
+        &mut self,
+        albedo_path: GString,
+        height_path: GString,
+        normal_path: GString,
+        roughness_path: GString,
+        output_dir: GString,
+        options: Dictionary,
+    ) -> Dictionary {
+        let mut result = Dictionary::new();
+        let _ = result.insert("success", false);
+        let _ = result.insert("error", "");
+
+        // Convert paths
+        let albedo_str = albedo_path.to_string();
+        let height_str = height_path.to_string();
+        let normal_str = normal_path.to_string();
+        let roughness_str = roughness_path.to_string();
+        let output_str = output_dir.to_string();
+
+        let (albedo_h_options, normal_r_options) = match Self::terrain_pack_options(&options) {
+            Ok(options) => options,
+            Err(e) => {
+                let _ = result.insert("error", e);
+                return result;
+            }
+        };
+        let albedo_h_format = albedo_h_options.format;
+        let normal_r_format = normal_r_options.format;
+        let resize = albedo_h_options.resize;
+        let container = albedo_h_options.container;
+
+        godot_print!("📦 Starting Terrain3D Channel Packing...");
+        for (label, format) in [
+            ("Albedo+Height", albedo_h_format),
+            ("Normal+Roughness", normal_r_format),
+        ] {
+            if !format.has_alpha() {
+                godot_warn!("{} packed as {} discards the alpha channel", label, format.name());
+            }
+        }
+
+        // Load images
+        let albedo = match self.load_image(&albedo_str) {
+            Ok(img) => img,
+            Err(e) => {
+                let _ = result.insert("error", format!("Failed to load Albedo: {}", e));
+                return result;
+            }
+        };
+        let height = match self.load_image(&height_str) {
+            Ok(img) => img,
+            Err(e) => {
+                let _ = result.insert("error", format!("Failed to load Height: {}", e));
+                return result;
+            }
+        };
+        let normal = match self.load_image(&normal_str) {
+            Ok(img) => img,
+            Err(e) => {
+                let _ = result.insert("error", format!("Failed to load Normal: {}", e));
+                return result;
+            }
+        };
+        let roughness = match self.load_image(&roughness_str) {
+            Ok(img) => img,
+            Err(e) => {
+                let _ = result.insert("error", format!("Failed to load Roughness: {}", e));
+                return result;
+            }
+        };
+
+        // Bring all four maps to one size so both files match
+        let block_aligned = albedo_h_format.is_compressed() || normal_r_format.is_compressed();
+        let resized_inputs = Self::resized_inputs(&[&albedo, &height, &normal, &roughness], &MANUAL_PACK_INPUTS);
+        let (albedo, height, normal, roughness) = match ChannelPacker::conform_inputs(
+            &[&albedo, &height, &normal, &roughness],
+            resize,
+            block_aligned,
+        ) {
+            Ok(inputs) => {
+                let [albedo, height, normal, roughness]: [DynamicImage; 4] =
+                    inputs.try_into().expect("one output per input");
+                (albedo, height, normal, roughness)
+            }
+            Err(e) => {
+                let _ = result.insert("error", format!("Failed to resize inputs: {}", e));
+                return result;
+            }
+        };
+
+        // Determine output paths
+        let output_path = Self::output_directory(Path::new(&albedo_str), &output_str);
+
+        let stem = PathBuf::from(&albedo_str)
+            .file_stem()
+            .unwrap_or(std::ffi::OsStr::new("terrain"))
+            .to_string_lossy()
+            .to_string();
+
+        let albedo_h_path = output_path.join(format!("{}_albedo_h.{}", stem, container.extension()));
+        let normal_r_path = output_path.join(format!("{}_normal_r.{}", stem, container.extension()));
+
+        // Pack File 1: Albedo (RGB) + Height (A)
+        godot_print!("  📁 Packing Albedo + Height ({})...", albedo_h_format.name());
+        let albedo_h_report = match crate::channel_packer::ChannelPacker::pack_and_save(
+            &albedo,
+            &height,
+            &albedo_h_path,
+            &albedo_h_options,
+        ) {
+            Ok(report) => report,
+            Err(e) => {
+                let _ = result.insert("error", format!("Failed to pack Albedo+Height: {}", e));
+                return result;
+            }
+        };
+        godot_print!("  ✓ {}", albedo_h_path.display());
+
+        // Pack File 2: Normal (RGB) + Roughness (A)
+        godot_print!("  📁 Packing Normal + Roughness ({})...", normal_r_format.name());
+        let normal_r_report = match crate::channel_packer::ChannelPacker::pack_and_save(
+            &normal,
+            &roughness,
+            &normal_r_path,
+            &normal_r_options,
+        ) {
+            Ok(report) => report,
+            Err(e) => {
+                let _ = result.insert("error", format!("Failed to pack Normal+Roughness: {}", e));
+                return result;
+            }
+        };
+        godot_print!("  ✓ {}", normal_r_path.display());
+        if let Some(error) = normal_r_report.normal_error {
+            godot_print!(
+                "  📐 Normal error: {:.2}° mean, {:.2}° max",
+                error.mean_degrees,
+                error.max_degrees
+            );
+            let _ = result.insert("normal_mean_angle_error", error.mean_degrees);
+            let _ = result.insert("normal_max_angle_error", error.max_degrees);
+        }
+
+        // Success
+        let _ = result.insert("success", true);
+        let _ = result.insert("albedo_h_path", albedo_h_path.to_string_lossy().to_string());
+        let _ = result.insert("normal_r_path", normal_r_path.to_string_lossy().to_string());
+        let _ = result.insert("resize_policy", resize.name());
+        let _ = result.insert("resized_inputs", resized_inputs);
+        let _ = result.insert("width", albedo_h_report.width);
+        let _ = result.insert("height", albedo_h_report.height);
+
+        godot_print!(
+            "🎉 Terrain3D packing complete ({}x{}, resize: {})!",
+            albedo_h_report.width,
+            albedo_h_report.height,
+            resize.name()
+        );
+        result
+    }
+
+    /// Pack a whole material set into 2 DDS or KTX2 texture arrays (albedo_h and normal_r).
+    ///
+    /// # Arguments
+    /// * `albedo_paths` - Albedo texture of every layer (RGB of the albedo_h array)
+    /// * `height_paths` - Height texture of every layer (same order and count)
+    /// * `normal_paths` - Normal texture of every layer
+    /// * `roughness_paths` - Roughness texture of every layer
+    /// * `output_dir` - Output directory (if empty, saves next to the first albedo)
+    /// * `name` - Base file name, e.g. "desert" -> `desert_albedo_h_array.dds`
+    ///   (if empty, uses the first albedo's name)
+    /// * `options` - Same keys as `pack_terrain_3d_manual`. The resize policy is applied
+    ///   per layer; all layers must end up the same size.
+    ///
+    /// # Returns
+    /// Dictionary with keys: success, error, albedo_h_path, normal_r_path, layer_count,
+    /// resize_policy, resized_inputs ("height 2" = layer 2's height), width, height,
+    /// normal_mean_angle_error, normal_max_angle_error (worst layer)
+    #[func]
+    #[allow(clippy::too_many_arguments)]
+    fn pack_terrain_3d_array(
+        &mut self,
+        albedo_paths: PackedStringArray,
+        height_paths: PackedStringArray,
+        normal_paths: PackedStringArray,
+        roughness_paths: PackedStringArray,
+        output_dir: GString,
+        name: GString,
+        options: Dictionary,
+    ) -> Dictionary {
+        let mut result = Dictionary::new();
+        let _ = result.insert("success", false);
+        let _ = result.insert("error", "");
+
+        let layer_count = albedo_paths.len();
+        if layer_count == 0 {
+            let _ = result.insert("error", "No layers to pack");
+            return result;
+        }
+        if [&height_paths, &normal_paths, &roughness_paths]
+            .iter()
+            .any(|paths| paths.len() != layer_count)
+        {
+            let _ = result.insert(
+                "error",
+                "Albedo, height, normal and roughness lists must have the same length",
+            );
+            return result;
+        }
+
+        let (albedo_h_options, normal_r_options) = match Self::terrain_pack_options(&options) {
+            Ok(options) => options,
+            Err(e) => {
+                let _ = result.insert("error", e);
+                return result;
+            }
+        };
+        let resize = albedo_h_options.resize;
+        let extension = albedo_h_options.container.extension();
+        let block_aligned = albedo_h_options.format.is_compressed() || normal_r_options.format.is_compressed();
+
+        godot_print!("📦 Packing {} Terrain3D layers into texture arrays...", layer_count);
+
+        let mut albedo_h_layers = Vec::with_capacity(layer_count);
+        let mut normal_r_layers = Vec::with_capacity(layer_count);
+        let mut resized_inputs = PackedStringArray::new();
+        for layer in 0..layer_count {
+            let mut maps = Vec::with_capacity(4);
+            for (label, paths) in [
+                ("Albedo", &albedo_paths),
+                ("Height", &height_paths),
+                ("Normal", &normal_paths),
+                ("Roughness", &roughness_paths),
+            ] {
+                match self.load_image(&paths.get(layer).unwrap_or_default().to_string()) {
+                    Ok(img) => maps.push(img),
+                    Err(e) => {
+                        let _ = result.insert("error", format!("Layer {}: failed to load {}: {}", layer, label, e));
+                        return result;
+                    }
+                }
+            }
+
+            let maps = [&maps[0], &maps[1], &maps[2], &maps[3]];
+            let names = MANUAL_PACK_INPUTS.map(|name| format!("{} {}", name, layer));
+            resized_inputs.extend_array(&Self::resized_inputs(&maps, &names));
+            let packed = ChannelPacker::conform_inputs(&maps, resize, block_aligned).and_then(|maps| {
+                Ok((
+                    ChannelPacker::pack_rgba(&maps[0], &maps[1])?,
+                    ChannelPacker::pack_rgba(&maps[2], &maps[3])?,
+                ))
+            });
+            match packed {
+                Ok((albedo_h, normal_r)) => {
+                    albedo_h_layers.push(albedo_h);
+                    normal_r_layers.push(normal_r);
+                }
+                Err(e) => {
+                    let _ = result.insert("error", format!("Layer {}: failed to resize inputs: {}", layer, e));
+                    return result;
+                }
+            }
+            godot_print!("  ✓ Layer {}: {}", layer, albedo_paths.get(layer).unwrap_or_default());
+        }
+
+        let first_albedo = PathBuf::from(albedo_paths.get(0).unwrap_or_default().to_string());
+        let output_path = Self::output_directory(&first_albedo, &output_dir.to_string());
+        let name = if name.is_empty() {
+            first_albedo
+                .file_stem()
+                .unwrap_or(std::ffi::OsStr::new("terrain"))
+                .to_string_lossy()
+                .to_string()
+        } else {
+            name.to_string()
+        };
+        let albedo_h_path = output_path.join(format!("{}_albedo_h_array.{}", name, extension));
+        let normal_r_path = output_path.join(format!("{}_normal_r_array.{}", name, extension));
+
+        godot_print!("  📁 Writing Albedo + Height array ({})...", albedo_h_options.format.name());
+        let albedo_h_report = match ChannelPacker::save_texture_array(&albedo_h_layers, &albedo_h_path, &albedo_h_options) {
+            Ok(report) => report,
+            Err(e) => {
+                let _ = result.insert("error", format!("Failed to pack Albedo+Height array: {}", e));
+                return result;
+            }
+        };
+        godot_print!("  ✓ {}", albedo_h_path.display());
+
+        godot_print!("  📁 Writing Normal + Roughness array ({})...", normal_r_options.format.name());
+        let normal_r_report = match ChannelPacker::save_texture_array(&normal_r_layers, &normal_r_path, &normal_r_options) {
+            Ok(report) => report,
+            Err(e) => {
+                let _ = result.insert("error", format!("Failed to pack Normal+Roughness array: {}", e));
+                return result;
+            }
+        };
+        godot_print!("  ✓ {}", normal_r_path.display());
+        if let Some(error) = normal_r_report.normal_error {
+            let _ = result.insert("normal_mean_angle_error", error.mean_degrees);
+            let _ = result.insert("normal_max_angle_error", error.max_degrees);
+        }
+
+        let _ = result.insert("success", true);
+        let _ = result.insert("albedo_h_path", albedo_h_path.to_string_lossy().to_string());
+        let _ = result.insert("normal_r_path", normal_r_path.to_string_lossy().to_string());
+        let _ = result.insert("layer_count", layer_count as i64);
+        let _ = result.insert("resize_policy", resize.name());
+        let _ = result.insert("resized_inputs", resized_inputs);
+        let _ = result.insert("width", albedo_h_report.width);
+        let _ = result.insert("height", albedo_h_report.height);
+
+        godot_print!(
+            "🎉 Texture arrays complete ({} layers, {}x{})!",
+            layer_count,
+            albedo_h_report.width,
+            albedo_h_report.height
+        );
+        result
+    }
+
+    /// Pack arbitrary channels of several images into one RGBA texture.
+    ///
+    /// # Arguments
+    /// * `inputs` - Dictionary of input name -> image path, e.g. `{"ao": "res://ao.png"}`
+    /// * `mapping` - Dictionary with keys "r", "g", "b", "a". Each value is
+    ///   `"name.channel"` (e.g. `"ao.r"`), `"1-name.channel"` for an inverted channel,
+    ///   or a constant between 0 and 1. Missing keys default to 0 (RGB) and 1 (A).
+    /// * `output_path` - Output file; `.dds` and `.ktx2` are block compressed, anything
+    ///   else is saved by extension (png, tga, exr, ...)
+    /// * `options` - Dictionary, every key optional:
+    ///   - "format": DDS/KTX2 format (see `pack_terrain_3d_manual`), ignored for other files
+    ///   - "mip_filter", "quality": DDS/KTX2 mipmap filter and compression effort
+    ///   - "resize_policy", "max_resolution": see `pack_terrain_3d_manual`
+    ///
+    /// # Returns
+    /// Dictionary with keys: success, error, output_path, resize_policy, resized_inputs, width, height
+    #[func]
+    fn pack_channels(
+        &mut self,
+        inputs: Dictionary,
+        mapping: Dictionary,
+        output_path: GString,
+        options: Dictionary,
+    ) -> Dictionary {
+        let mut result = Dictionary::new();
+        let _ = result.insert("success", false);
+        let _ = result.insert("error", "");
+
+        let output_buf = PathBuf::from(output_path.to_string());
+        let container = output_buf
+            .extension()
+            .and_then(|ext| Container::from_name(&ext.to_string_lossy()).ok());
+
+        let texture_options = match Self::texture_options(&options, "format") {
+            Ok(texture_options) => texture_options,
+            Err(e) => {
+                let _ = result.insert("error", e);
+                return result;
+            }
+        };
+        let resize = texture_options.resize;
+        let options = container.map(|container| TextureOptions {
+            container,
+            ..texture_options
+        });
+        let block_aligned = options.as_ref().is_some_and(|o| o.format.is_compressed());
+
+        godot_print!("📦 Packing channels into {}", output_buf.display());
+
+        // Load inputs in dictionary order
+        let mut names = Vec::new();
+        let mut images = Vec::new();
+        for (name, path) in inputs.iter_shared() {
+            let name = name.stringify().to_string();
+            match self.load_image(&path.stringify().to_string()) {
+                Ok(img) => images.push(img),
+                Err(e) => {
+                    let _ = result.insert("error", format!("Failed to load '{}': {}", name, e));
+                    return result;
+                }
+            }
+            names.push(name);
+        }
+
+        let mut channels = [
+            ChannelSource::Constant(0.0),
+            ChannelSource::Constant(0.0),
+            ChannelSource::Constant(0.0),
+            ChannelSource::Constant(1.0),
+        ];
+        for (channel, key) in channels.iter_mut().zip(["r", "g", "b", "a"]) {
+            let Some(expression) = mapping.get(key) else {
+                continue;
+            };
+            match ChannelSource::parse(&expression.stringify().to_string(), &names) {
+                Ok(source) => *channel = source,
+                Err(e) => {
+                    let _ = result.insert("error", format!("Invalid mapping for '{}': {}", key, e));
+                    return result;
+                }
+            }
+        }
+
+        let mapping = ChannelMapping { channels };
+        let resized_inputs = Self::resized_inputs(&images.iter().collect::<Vec<_>>(), &names);
+        let packed = match ChannelPacker::pack_channels(&images, &mapping, resize, block_aligned) {
+            Ok(img) => img,
+            Err(e) => {
+                let _ = result.insert("error", e);
+                return result;
+            }
+        };
+
+        let saved = match &options {
+            Some(options) => ChannelPacker::save_texture(&packed, &output_buf, options).map(|_| ()),
+            None if output_buf
+                .extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("exr")) =>
+            {
+                OpenExr::save(&DynamicImage::ImageRgba8(packed.clone()), &output_buf)
+            }
+            None => packed
+                .save(&output_buf)
+                .map_err(|e| format!("Failed to save image: {}", e)),
+        };
+        if let Err(e) = saved {
+            let _ = result.insert("error", e);
+            return result;
+        }
+
+        godot_print!("  ✓ {}", output_buf.display());
+
+        let _ = result.insert("success", true);
+        let _ = result.insert("output_path", output_buf.to_string_lossy().to_string());
+        let _ = result.insert("resize_policy", resize.name());
+        let _ = result.insert("resized_inputs", resized_inputs);
+        let _ = result.insert("width", packed.width());
+        let _ = result.insert("height", packed.height());
+        result
+    }
+
+    /// Pack material masks using an engine preset.
+    ///
+    /// # Arguments
+    /// * `preset` - "orm" (glTF/Godot), "hdrp_mask" (Unity HDRP) or "arm" (Unreal)
+    /// * `inputs` - Dictionary of map name -> image path. Recognised names: "ao",
+    ///   "roughness", "smoothness", "metallic", "detail". Missing maps are filled with
+    ///   neutral constants; smoothness is derived from roughness (and vice versa).
+    ///   Inputs are resized to the size of the first one.
+    /// * `output_dir` - Optional output directory (if empty, saves next to the first input)
+    /// * `options` - Dictionary, every key optional:
+    ///   - "container": "png" (default), "dds" or "ktx2"
+    ///   - "format", "mip_filter", "quality": DDS/KTX2 settings (see `pack_terrain_3d_manual`)
+    ///
+    /// # Returns
+    /// Dictionary with keys: success, error, preset, output_path,
+    /// resized_inputs (names of inputs that were resized to the first one)
+    #[func]
+    fn pack_preset(
+        &mut self,
+        preset: GString,
+        inputs: Dictionary,
+        output_dir: GString,
+        options: Dictionary,
+    ) -> Dictionary {
+        let mut result = Dictionary::new();
+        let _ = result.insert("success", false);
+        let _ = result.insert("error", "");
+
+        let preset = match PackingPreset::from_name(&preset.to_string()) {
+            Ok(preset) => preset,
+            Err(e) => {
+                let _ = result.insert("error", e);
+                return result;
+            }
+        };
+        let container = options
+            .get("container")
+            .map(|value| value.stringify().to_string())
+            .unwrap_or_default();
+        let texture_options = if container.trim().is_empty() || container.trim().eq_ignore_ascii_case("png") {
+            None
+        } else {
+            match Self::texture_options(&options, "format") {
+                Ok(texture_options) => Some(TextureOptions {
+                    resize: ResizePolicy::MatchFirst,
+                    ..texture_options
+                }),
+                Err(e) => {
+                    let _ = result.insert("error", e);
+                    return result;
+                }
+            }
+        };
+        let block_aligned = texture_options.as_ref().is_some_and(|o| o.format.is_compressed());
+        let _ = result.insert("preset", preset.name());
+
+        godot_print!("📦 Packing {} mask...", preset.name());
+
+        let mut names = Vec::new();
+        let mut paths = Vec::new();
+        let mut images = Vec::new();
+        for (name, path) in inputs.iter_shared() {
+            let name = name.stringify().to_string().to_ascii_lowercase();
+            if !PackingPreset::input_names().contains(&name.as_str()) {
+                godot_warn!("Ignoring unknown preset input '{}'", name);
+                continue;
+            }
+            let path = path.stringify().to_string();
+            match self.load_image(&path) {
+                Ok(img) => images.push(img),
+                Err(e) => {
+                    let _ = result.insert("error", format!("Failed to load '{}': {}", name, e));
+                    return result;
+                }
+            }
+            names.push(name);
+            paths.push(path);
+        }
+
+        if images.is_empty() {
+            let _ = result.insert("error", "No preset inputs given (ao, roughness, smoothness, metallic, detail)");
+            return result;
+        }
+
+        // Inputs are matched to the first one
+        let mapping = preset.mapping(&names);
+        let resized_inputs = Self::resized_inputs(&images.iter().collect::<Vec<_>>(), &names);
+        let packed = match ChannelPacker::pack_channels(&images, &mapping, ResizePolicy::MatchFirst, block_aligned) {
+            Ok(img) => img,
+            Err(e) => {
+                let _ = result.insert("error", e);
+                return result;
+            }
+        };
+
+        // "rock_roughness.png" -> "rock_orm.png"
+        let first_path = PathBuf::from(&paths[0]);
+        let stem = first_path
+            .file_stem()
+            .unwrap_or(std::ffi::OsStr::new("material"))
+            .to_string_lossy()
+            .to_string();
+        let base = stem
+            .strip_suffix(&format!("_{}", names[0]))
+            .unwrap_or(&stem)
+            .to_string();
+
+        let output_path = Self::output_directory(&first_path, &output_dir.to_string());
+        let extension = texture_options.as_ref().map_or("png", |o| o.container.extension());
+        let output_path = output_path.join(format!("{}_{}.{}", base, preset.suffix(), extension));
+
+        let saved = match &texture_options {
+            Some(texture_options) => ChannelPacker::save_texture(&packed, &output_path, texture_options).map(|_| ()),
+            None => packed.save(&output_path).map_err(|e| e.to_string()),
+        };
+        if let Err(e) = saved {
+            let _ = result.insert("error", format!("Failed to save packed mask: {}", e));
+            return result;
+        }
+
+        godot_print!("  ✓ {}", output_path.display());
+
+        let _ = result.insert("success", true);
+        let _ = result.insert("output_path", output_path.to_string_lossy().to_string());
+        let _ = result.insert("resized_inputs", resized_inputs);
+        result
+    }
+
+    /// Create or update a Terrain3DTextureAsset (.tres) for a packed texture pair.
+    ///
+    /// # Arguments
+    /// * `albedo_h_path` - Path to the packed Albedo+Height DDS
+    /// * `normal_r_path` - Path to the packed Normal+Roughness DDS
+    /// * `asset_path` - Output .tres path (if empty, saves `{stem}.tres` next to albedo_h)
+    /// * `uv_scale` - Texture UV scale
+    /// * `detiling_rotation` - Detiling rotation amount (0 disables)
+    /// * `detiling_shift` - Detiling shift amount (0 disables)
+    /// * `assets_path` - Optional Terrain3DAssets (.tres) to append the texture to
+    ///
+    /// # Returns
+    /// Dictionary with keys: success, error, asset_path, texture_id (-1 if not appended)
+    #[func]
+    #[allow(clippy::too_many_arguments)]
+    fn create_terrain_3d_texture_asset(
+        &mut self,
+        albedo_h_path: GString,
+        normal_r_path: GString,
+        asset_path: GString,
+        uv_scale: f32,
+        detiling_rotation: f32,
+        detiling_shift: f32,
+        assets_path: GString,
+    ) -> Dictionary {
+        let mut result = Dictionary::new();
+        let _ = result.insert("success", false);
+        let _ = result.insert("error", "");
+        let _ = result.insert("texture_id", -1);
+
+        let resolve = |path: &GString| {
+            let path = path.to_string();
+            Self::resolve_path(&path).unwrap_or_else(|_| PathBuf::from(&path))
+        };
+        let albedo_h_buf = resolve(&albedo_h_path);
+        let normal_r_buf = resolve(&normal_r_path);
+        let assets_buf = resolve(&assets_path);
+
+        // "rock_albedo_h" -> "rock"
+        let stem = albedo_h_buf
+            .file_stem()
+            .unwrap_or(std::ffi::OsStr::new("terrain"))
+            .to_string_lossy()
+            .trim_end_matches("_albedo_h")
+            .to_string();
+
+        let asset_buf = if asset_path.is_empty() {
+            albedo_h_buf
+                .parent()
+                .unwrap_or(Path::new(""))
+                .join(format!("{}.tres", stem))
+        } else {
+            resolve(&asset_path)
+        };
+
+        godot_print!("🗺️ Creating Terrain3D texture asset: {}", asset_buf.display());
+
+        let settings = TextureAssetSettings {
+            name: stem,
+            uv_scale,
+            detiling_rotation,
+            detiling_shift,
+        };
+
+        // Resolve the slot first so the asset id matches its position in the list
+        let texture_id = if assets_path.is_empty() {
+            None
+        } else {
+            match Terrain3DAssetWriter::texture_slot(&assets_buf, &asset_buf) {
+                Ok(id) => Some(id),
+                Err(e) => {
+                    let _ = result.insert("error", e);
+                    return result;
+                }
+            }
+        };
+
+        if let Err(e) = Terrain3DAssetWriter::write_texture_asset(
+            &asset_buf,
+            &albedo_h_buf,
+            &normal_r_buf,
+            &settings,
+            texture_id,
+        ) {
+            let _ = result.insert("error", e);
+            return result;
+        }
+        godot_print!("  ✓ {}", asset_buf.display());
+
+        if texture_id.is_some() {
+            match Terrain3DAssetWriter::append_to_assets(&assets_buf, &asset_buf) {
+                Ok(id) => {
+                    let _ = result.insert("texture_id", id);
+                    godot_print!("  ✓ Registered as texture {} in {}", id, assets_buf.display());
+                }
+                Err(e) => {
+                    let _ = result.insert("error", format!("Failed to update Terrain3DAssets: {}", e));
+                    return result;
+                }
+            }
+        }
+
+        let _ = result.insert("success", true);
+        let _ = result.insert("asset_path", asset_buf.to_string_lossy().to_string());
+        result
+    }
+
+    /// Convert an image (jpg/jpeg) to PNG
+    ///
+    /// # Arguments
+    /// * `input_path` - Path to the source image
+    /// * `output_dir` - Optional output directory (if empty, saves next to source)
+    ///
+    /// # Returns
+    /// Dictionary with keys: success, error, output_path
+    #[func]
+    fn convert_to_png(&mut self, input_path: GString, output_dir: GString) -> Dictionary {
+        let mut result = Dictionary::new();
+        let _ = result.insert("success", false);
+        let _ = result.insert("error", "");
+
+        let input_str = input_path.to_string();
+        let output_dir_str = output_dir.to_string();
+
+        godot_print!("♻️ Converting image to PNG: {}", input_str);
+
+        // Load source image
+        let image = match self.load_image(&input_str) {
+            Ok(img) => img,
+            Err(e) => {
+                let _ = result.insert("error", format!("Failed to load image: {}", e));
+                return result;
+            }
+        };
+
+        // Determine output path
+        let input_path_buf = PathBuf::from(&input_str);
+        let stem = input_path_buf
+            .file_stem()
+            .unwrap_or(std::ffi::OsStr::new("image"))
+            .to_string_lossy()
+            .to_string();
+
+        let output_path = if output_dir_str.is_empty() {
+            input_path_buf.parent().unwrap_or(Path::new("")).join(format!("{}.png", stem))
+        } else {
+            PathBuf::from(&output_dir_str).join(format!("{}.png", stem))
+        };
+
+        // PNG has no float samples, so EXR/HDR sources are written as 16-bit
+        let image = match image {
+            DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_) => {
+                DynamicImage::ImageRgba16(image.to_rgba16())
+            }
+            other => other,
+        };
+
+        // Save as PNG
+        if let Err(e) = image.save(&output_path) {
+            let _ = result.insert("error", format!("Failed to save PNG: {}", e));
+            return result;
+        }
+
+        godot_print!("  ✓ Saved PNG: {}", output_path.display());
+
+        // Success
+        let _ = result.insert("success", true);
+        let _ = result.insert("output_path", output_path.to_string_lossy().to_string());
+        result
+    }
+
+    /// Convert an image, or every image in a directory, to another format.
+    ///
+    /// # Arguments
+    /// * `input` - Image file or directory (directories convert every image they
+    ///   directly contain)
+    /// * `output_format` - "png", "tga", "tiff", "webp" (lossless), "exr" or "dds"
+    /// * `options` - Dictionary, every key optional:
+    ///   - "output_dir": output directory (default: next to each source)
+    ///   - "bit_depth": "keep" (default), "8", "16" or "32" (limited by the format:
+    ///     TGA/WebP/DDS 8, PNG/TIFF 16, EXR always 32)
+    ///   - "width", "height": target size (0 keeps it; one of them keeps the aspect ratio)
+    ///   - "max_size": downscale so the larger side fits (0 = no limit)
+    ///   - "color": "keep" (default), "to_linear" or "to_srgb"
+    ///   - "strip_alpha": drop the alpha channel
+    ///   - "dds_format": block format for DDS (default "bc7"), "mip_filter" (default "kaiser")
+    ///   - "existing": "overwrite" (default), "skip" or "version"
+    ///
+    /// # Returns
+    /// Dictionary with keys: success, error, output_path (first written file),
+    /// output_paths, skipped, failed ("file: error" per failed image)
+    #[func]
+    fn convert_image(&mut self, input: GString, output_format: GString, options: Dictionary) -> Dictionary {
+        let mut result = Dictionary::new();
+        let _ = result.insert("success", false);
+        let _ = result.insert("error", "");
+
+        let (convert_options, output_dir, existing) = match Self::convert_options(&output_format.to_string(), &options) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                let _ = result.insert("error", e);
+                return result;
+            }
+        };
+
+        let input_path = match Self::resolve_path(&input.to_string()) {
+            Ok(path) => path,
+            Err(e) => {
+                let _ = result.insert("error", e);
+                return result;
+            }
+        };
+        let sources = if input_path.is_dir() {
+            let entries = match std::fs::read_dir(&input_path) {
+                Ok(entries) => entries,
+                Err(e) => {
+                    let _ = result.insert("error", format!("Failed to read directory: {}", e));
+                    return result;
+                }
+            };
+            let mut sources: Vec<PathBuf> = entries
+                .filter_map(|entry| entry.ok().map(|e| e.path()))
+                .filter(|path| {
+                    path.is_file()
+                        && path.extension().is_some_and(|ext| {
+                            let ext = ext.to_string_lossy().to_ascii_lowercase();
+                            CONVERTIBLE_EXTENSIONS.contains(&ext.as_str())
+                        })
+                })
+                .collect();
+            sources.sort();
+            sources
+        } else {
+            vec![input_path]
+        };
+        if sources.is_empty() {
+            let _ = result.insert("error", "No images to convert");
+            return result;
+        }
+
+        godot_print!(
+            "♻️ Converting {} image(s) to {}...",
+            sources.len(),
+            convert_options.format.extension().to_uppercase()
+        );
+
+        let mut output_paths = PackedStringArray::new();
+        let mut skipped = PackedStringArray::new();
+        let mut failed = PackedStringArray::new();
+        for source in &sources {
+            let stem = source.file_stem().unwrap_or_default().to_string_lossy().to_string();
+            let directory = output_dir.clone().unwrap_or_else(|| {
+                source.parent().unwrap_or(Path::new("")).to_path_buf()
+            });
+            let target = directory.join(format!("{}.{}", stem, convert_options.format.extension()));
+
+            let converted = (|| -> Result<Option<PathBuf>, String> {
+                if &target == source && existing == ExistingFilePolicy::Overwrite {
+                    return Err("output would overwrite the source".to_string());
+                }
+                let Some(path) = existing.resolve(&target) else {
+                    return Ok(None);
+                };
+                let image = self.load_image(&source.to_string_lossy())?;
+                let image = ImageConverter::convert(image, &convert_options);
+                std::fs::create_dir_all(&directory)
+                    .map_err(|e| format!("Failed to create {}: {}", directory.display(), e))?;
+                ImageConverter::save(&image, &path, &convert_options)?;
+                Ok(Some(path))
+            })();
+
+            match converted {
+                Ok(Some(path)) => {
+                    godot_print!("  ✓ {}", path.display());
+                    output_paths.push(path.to_string_lossy().to_string().into());
+                }
+                Ok(None) => {
+                    godot_print!("  ⏭️ Exists, skipped: {}", target.display());
+                    skipped.push(target.to_string_lossy().to_string().into());
+                }
+                Err(e) => {
+                    godot_warn!("  ✗ {}: {}", source.display(), e);
+                    failed.push(format!("{}: {}", source.display(), e).into());
+                }
+            }
+        }
+
+        if output_paths.is_empty() && !failed.is_empty() {
+            let _ = result.insert("error", failed.get(0).unwrap_or_default());
+        } else {
+            let _ = result.insert("success", true);
+        }
+        let _ = result.insert("output_path", output_paths.get(0).unwrap_or_default());
+        let _ = result.insert("output_paths", output_paths);
+        let _ = result.insert("skipped", skipped);
+        let _ = result.insert("failed", failed);
+        result
+    }
+
+    /// Split a packed DDS texture back into an RGB image and a grayscale alpha image
+    ///
+    /// `*_albedo_h.dds` unpacks to `*_albedo.png` + `*_height.png` and
+    /// `*_normal_r.dds` to `*_normal.png` + `*_roughness.png`; any other file
+    /// to `*_rgb.png` + `*_alpha.png`; `*_array.dds` texture arrays unpack their first
+    /// layer the same way. Float (RGBA16F/32F) albedo is converted back
+    /// to sRGB; other float data is written as linear-tagged PNGs.
+    ///
+    /// # Arguments
+    /// * `path` - Path to the DDS file (BC1-BC5, BC7 or uncompressed)
+    /// * `output_dir` - Optional output directory (if empty, saves next to source)
+    ///
+    /// # Returns
+    /// Dictionary with keys: success, error, rgb_path, alpha_path
+    #[func]
+    fn unpack_dds(&mut self, path: GString, output_dir: GString) -> Dictionary {
+        let mut result = Dictionary::new();
+        let _ = result.insert("success", false);
+        let _ = result.insert("error", "");
+
+        let path_str = path.to_string();
+        let output_dir_str = output_dir.to_string();
+
+        godot_print!("📦 Unpacking DDS: {}", path_str);
+
+        let image = match self.load_image(&path_str) {
+            Ok(img) => img,
+            Err(e) => {
+                let _ = result.insert("error", format!("Failed to load DDS: {}", e));
+                return result;
+            }
+        };
+
+        let path_buf = Self::resolve_path(&path_str).unwrap_or_else(|_| PathBuf::from(&path_str));
+        let stem = path_buf
+            .file_stem()
+            .unwrap_or(std::ffi::OsStr::new("texture"))
+            .to_string_lossy()
+            .to_string();
+        // Texture arrays unpack like their single-layer counterparts
+        let stem = stem.strip_suffix("_array").unwrap_or(&stem).to_string();
+
+        // Float formats hold linear values: albedo goes back to sRGB, anything else is tagged linear
+        let (image, rgb_space, alpha_space) = match image {
+            DynamicImage::ImageRgba32F(mut float) if stem.ends_with("_albedo_h") => {
+                for pixel in float.pixels_mut() {
+                    for value in &mut pixel.0[..3] {
+                        *value = TransferFunction::Srgb.encode(value.clamp(0.0, 1.0));
+                    }
+                }
+                let rgba = DynamicImage::ImageRgba32F(float).to_rgba8();
+                (rgba, ColorSpace::Srgb, ColorSpace::Linear)
+            }
+            DynamicImage::ImageRgba32F(_) => (image.to_rgba8(), ColorSpace::Linear, ColorSpace::Linear),
+            _ => (image.to_rgba8(), ColorSpace::Unspecified, ColorSpace::Unspecified),
+        };
+        let (rgb, alpha) = ChannelPacker::unpack_rgba(&image);
+        let (rgb_name, alpha_name) = if let Some(base) = stem.strip_suffix("_albedo_h") {
+            (format!("{}_albedo.png", base), format!("{}_height.png", base))
+        } else if let Some(base) = stem.strip_suffix("_normal_r") {
+            (format!("{}_normal.png", base), format!("{}_roughness.png", base))
+        } else {
+            (format!("{}_rgb.png", stem), format!("{}_alpha.png", stem))
+        };
+
+        let output_path = Self::output_directory(&path_buf, &output_dir_str);
+        if let Err(e) = std::fs::create_dir_all(&output_path) {
+            let _ = result.insert("error", format!("Failed to create output directory: {}", e));
+            return result;
+        }
+
+        let rgb_path = output_path.join(rgb_name);
+        let alpha_path = output_path.join(alpha_name);
+
+        if let Err(e) = ColorManagement::save_png(&DynamicImage::ImageRgb8(rgb), &rgb_path, rgb_space) {
+            let _ = result.insert("error", format!("Failed to save RGB image: {}", e));
+            return result;
+        }
+        godot_print!("  ✓ {}", rgb_path.display());
+
+        if let Err(e) = ColorManagement::save_png(&DynamicImage::ImageLuma8(alpha), &alpha_path, alpha_space) {
+            let _ = result.insert("error", format!("Failed to save alpha image: {}", e));
+            return result;
+        }
+        godot_print!("  ✓ {}", alpha_path.display());
+
+        let _ = result.insert("success", true);
+        let _ = result.insert("rgb_path", rgb_path.to_string_lossy().to_string());
+        let _ = result.insert("alpha_path", alpha_path.to_string_lossy().to_string());
+        result
+    }
+
+    /// Parse the `convert_image` format and options dictionary.
+    fn convert_options(
+        output_format: &str,
+        options: &Dictionary,
+    ) -> Result<(ConvertOptions, Option<PathBuf>, ExistingFilePolicy), String> {
+        let get = |key: &str| options.get(key).map(|value| value.stringify().to_string());
+        let get_size = |key: &str| -> Result<u32, String> {
+            get(key).map_or(Ok(0), |value| {
+                value
+                    .trim()
+                    .parse::<f64>()
+                    .map(|v| v.max(0.0) as u32)
+                    .map_err(|_| format!("Invalid {} '{}'", key, value))
+            })
+        };
+
+        let convert_options = ConvertOptions {
+            format: ConvertFormat::from_name(output_format)?,
+            bit_depth: BitDepth::from_name(&get("bit_depth").unwrap_or_default())?,
+            width: get_size("width")?,
+            height: get_size("height")?,
+            max_size: get_size("max_size")?,
+            color: ColorConversion::from_name(&get("color").unwrap_or_default())?,
+            strip_alpha: get("strip_alpha").is_some_and(|v| v.eq_ignore_ascii_case("true")),
+            dds_format: match get("dds_format") {
+                Some(format) if !format.is_empty() => TextureFormat::from_name(&format)?,
+                _ => TextureFormat::Bc7,
+            },
+            mip_filter: MipFilter::from_name(&get("mip_filter").unwrap_or_else(|| "kaiser".to_string()))?,
+        };
+        let output_dir = get("output_dir")
+            .filter(|dir| !dir.is_empty())
+            .map(|dir| Self::resolve_path(&dir))
+            .transpose()?;
+        let existing = ExistingFilePolicy::from_name(&get("existing").unwrap_or_default())?;
+
+        Ok((convert_options, output_dir, existing))
+    }
+
+    /// Parse the packing keys shared by the pack functions: "container", `format_key`,
+    /// "mip_filter" (default "kaiser"), "resize_policy", "max_resolution" and "quality".
+    /// The result is set up for linear data; callers adjust content and colour space.
+    fn texture_options(options: &Dictionary, format_key: &str) -> Result<TextureOptions, String> {
+        let get = |key: &str| options.get(key).map(|value| value.stringify().to_string());
+        let max_resolution = match get("max_resolution") {
+            Some(value) => value
+                .trim()
+                .parse::<f64>()
+                .map(|v| v.max(0.0) as u32)
+                .map_err(|_| format!("Invalid max_resolution '{}'", value))?,
+            None => 0,
+        };
+
+        Ok(TextureOptions {
+            container: Container::from_name(&get("container").unwrap_or_default())?,
+            format: TextureFormat::from_name(&get(format_key).unwrap_or_default())?,
+            mip_filter: MipFilter::from_name(&get("mip_filter").unwrap_or_else(|| "kaiser".to_string()))?,
+            content: TextureContent::Linear,
+            color_space: ColorSpace::Linear,
+            resize: ResizePolicy::from_name(&get("resize_policy").unwrap_or_default(), max_resolution)?,
+            quality: CompressionQuality::from_name(&get("quality").unwrap_or_default())?,
+            weights: ColorWeights::Uniform,
+            normal_encoding: NormalEncoding::Raw,
+        })
+    }
+
+    /// Parse the `pack_terrain_3d_manual` / `pack_terrain_3d_array` options into the
+    /// albedo_h and normal_r texture options.
+    fn terrain_pack_options(options: &Dictionary) -> Result<(TextureOptions, TextureOptions), String> {
+        let normal_encoding = options
+            .get("normal_encoding")
+            .map(|value| value.stringify().to_string())
+            .unwrap_or_default();
+
+        // Albedo is filtered in linear light, fitted with perceptual weights and tagged sRGB;
+        // normals are renormalized per mip, fitted with uniform weights and tagged linear
+        let albedo_h = TextureOptions {
+            content: TextureContent::Color,
+            color_space: ColorSpace::Srgb,
+            weights: ColorWeights::Perceptual,
+            ..Self::texture_options(options, "albedo_h_format")?
+        };
+        let normal_r = TextureOptions {
+            content: TextureContent::Normal,
+            normal_encoding: NormalEncoding::from_name(&normal_encoding)?,
+            ..Self::texture_options(options, "normal_r_format")?
+        };
+        Ok((albedo_h, normal_r))
+    }
+
+    /// Parse de-lighting settings from keys named `{prefix}strength`, `{prefix}radius`,
+    /// `{prefix}occlusion` and `{prefix}highlights` (missing keys keep their defaults).
+    fn delight_options(options: &Dictionary, prefix: &str) -> Result<DelightOptions, String> {
+        let mut delight = DelightOptions::default();
+        for (key, value) in [
+            ("strength", &mut delight.strength),
+            ("radius", &mut delight.radius),
+            ("occlusion", &mut delight.occlusion),
+            ("highlights", &mut delight.highlights),
+        ] {
+            let name = format!("{}{}", prefix, key);
+            if let Some(text) = options.get(name.as_str()).map(|v| v.stringify().to_string()) {
+                *value = text
+                    .trim()
+                    .parse::<f32>()
+                    .map_err(|_| format!("Invalid {} '{}'", name, text))?
+                    .max(0.0);
+            }
+        }
+        Ok(delight)
+    }
+
+    /// Directory next to `source` unless an output directory is given.
+    fn output_directory(source: &Path, output_dir: &str) -> PathBuf {
+        if output_dir.is_empty() {
+            source.parent().unwrap_or(Path::new("")).to_path_buf()
+        } else {
+            PathBuf::from(output_dir)
+        }
+    }
+
+    /// File stem of `source` without the first matching map suffix (e.g. `rock_normal` -> `rock`).
+    fn material_stem(source: &Path, suffixes: &[&str]) -> String {
+        let stem = source.file_stem().unwrap_or_default().to_string_lossy().to_string();
+        suffixes
+            .iter()
+            .find_map(|suffix| stem.strip_suffix(suffix))
+            .filter(|base| !base.is_empty())
+            .unwrap_or(&stem)
+            .to_string()
+    }
+
+    /// Save workflow conversion outputs as `{stem}_{map}.png` (`.exr` for float images),
+    /// returning each path and whether it was skipped.
+    fn save_converted(
+        maps: &[(&str, &DynamicImage, ColorSpace)],
+        directory: &Path,
+        stem: &str,
+        existing: ExistingFilePolicy,
+    ) -> Result<Vec<(PathBuf, bool)>, String> {
+        std::fs::create_dir_all(directory)
+            .map_err(|e| format!("Failed to create {}: {}", directory.display(), e))?;
+        let mut saved = Vec::with_capacity(maps.len());
+        for &(map, image, color_space) in maps {
+            let is_float = matches!(image, DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_));
+            let target = directory.join(format!("{}_{}.{}", stem, map, if is_float { "exr" } else { "png" }));
+            match existing.resolve(&target) {
+                Some(path) => {
+                    if is_float {
+                        OpenExr::save(image, &path)?;
+                    } else {
+                        ColorManagement::save_png(image, &path, color_space)?;
+                    }
+                    godot_print!("  ✓ {} map: {}", map, path.display());
+                    saved.push((path, false));
+                }
+                None => {
+                    godot_print!("  ⏭️ {} map exists, skipped: {}", map, target.display());
+                    saved.push((target, true));
+                }
+            }
+        }
+        Ok(saved)
+    }
+
+    /// `save_converted` with `{map}_path` and `skipped` written into `result`.
+    fn insert_converted(
+        result: &mut Dictionary,
+        maps: &[(&str, &DynamicImage, ColorSpace)],
+        directory: &Path,
+        stem: &str,
+        existing: ExistingFilePolicy,
+    ) {
+        match Self::save_converted(maps, directory, stem, existing) {
+            Ok(saved) => {
+                let mut skipped = PackedStringArray::new();
+                for (&(map, _, _), (path, was_skipped)) in maps.iter().zip(&saved) {
+                    let _ = result.insert(format!("{}_path", map), path.to_string_lossy().to_string());
+                    if *was_skipped {
+                        skipped.push(map.into());
+                    }
+                }
+                let _ = result.insert("success", true);
+                let _ = result.insert("skipped", skipped);
+            }
+            Err(e) => {
+                let _ = result.insert("error", e);
+            }
+        }
+    }
+
+    /// `save_converted` for a single map, with `output_path` and `skipped` written into `result`.
+    /// Returns false (with `error` set) if saving failed.
+    fn insert_converted_output(
+        result: &mut Dictionary,
+        map: (&str, &DynamicImage, ColorSpace),
+        directory: &Path,
+        stem: &str,
+        existing: ExistingFilePolicy,
+    ) -> bool {
+        match Self::save_converted(&[map], directory, stem, existing) {
+            Ok(saved) => {
+                let (path, was_skipped) = &saved[0];
+                let skipped: PackedStringArray = was_skipped.then(|| GString::from(map.0)).into_iter().collect();
+                let _ = result.insert("success", true);
+                let _ = result.insert("skipped", skipped);
+                let _ = result.insert("output_path", path.to_string_lossy().to_string());
+                true
+            }
+            Err(e) => {
+                let _ = result.insert("error", e);
+                false
+            }
+        }
+    }
+
+    /// Add the fields of a normal map analysis to a result dictionary.
+    fn insert_normal_report(result: &mut Dictionary, report: &NormalReport) {
+        let _ = result.insert("average_length", report.average_length);
+        let _ = result.insert("z_violation_percent", report.z_violation_percent);
+        let _ = result.insert("invalid_percent", report.invalid_percent);
+        let _ = result.insert("missing_z", report.missing_z);
+        let _ = result.insert("inverted_z", report.inverted_z);
+        let _ = result.insert("convention", report.convention.map_or("unknown", NormalConvention::name));
+        let _ = result.insert("convention_confidence", report.convention_confidence);
+        let _ = result.insert("banding", report.banding);
+    }
+
+    /// Parse the segmented roughness options (`None` for variance-based roughness).
+    fn material_settings(config: &Dictionary) -> Result<Option<MaterialSettings>, String> {
+        let get = |key: &str| config.get(key).map(|value| value.stringify().to_string());
+        match get("roughness_mode").unwrap_or_default().trim().to_ascii_lowercase().as_str() {
+            "" | "variance" => return Ok(None),
+            "segmented" => {}
+            other => {
+                return Err(format!(
+                    "Unknown roughness_mode '{}' (expected variance or segmented)",
+                    other
+                ))
+            }
+        }
+        Self::material_options(config).map(Some)
+    }
+
+    /// Parse the material count, mask, ranges and blend radius.
+    fn material_options(config: &Dictionary) -> Result<MaterialSettings, String> {
+        let get = |key: &str| config.get(key).map(|value| value.stringify().to_string());
+        let count = match get("material_count") {
+            Some(text) => text
+                .trim()
+                .parse::<usize>()
+                .map_err(|_| format!("Invalid material_count '{}'", text))?,
+            None => 4,
+        };
+        let blend_radius = match get("material_blend") {
+            Some(text) => text
+                .trim()
+                .parse::<f32>()
+                .map_err(|_| format!("Invalid material_blend '{}'", text))?
+                .max(0.0),
+            None => 4.0,
+        };
+
+        // Each entry is a Vector2, a [min, max] array or a "min-max" string
+        let mut ranges = Vec::new();
+        if let Some(value) = config.get("material_ranges") {
+            let entries: Vec<String> = match value.try_to::<VariantArray>() {
+                Ok(array) => array.iter_shared().map(|entry| entry.stringify().to_string()).collect(),
+                Err(_) => match value.try_to::<PackedVector2Array>() {
+                    Ok(packed) => packed.as_slice().iter().map(|v| format!("{} {}", v.x, v.y)).collect(),
+                    Err(_) => value.stringify().to_string().split(';').map(str::to_string).collect(),
+                },
+            };
+            for entry in entries.iter().filter(|entry| !entry.trim().is_empty()) {
+                let numbers: Vec<f32> = entry
+                    .split(|c: char| !(c.is_ascii_digit() || c == '.'))
+                    .filter(|part| !part.is_empty())
+                    .map(|part| part.parse::<f32>())
+                    .collect::<Result<_, _>>()
+                    .map_err(|_| format!("Invalid material range '{}'", entry))?;
+                let [min, max] = numbers[..] else {
+                    return Err(format!("Material range '{}' needs a minimum and a maximum", entry));
+                };
+                ranges.push((min.min(max), min.max(max)));
+            }
+        }
+
+        Ok(MaterialSettings {
+            count,
+            mask_path: get("material_mask").filter(|path| !path.trim().is_empty()),
+            ranges,
+            blend_radius,
+        })
+    }
+
+    /// Cluster the albedo, or read the ID mask when one is given.
+    fn segmentation(
+        &self,
+        albedo: &DynamicImage,
+        transfer: TransferFunction,
+        materials: &MaterialSettings,
+    ) -> Result<Segmentation, String> {
+        match &materials.mask_path {
+            Some(path) => {
+                let mask = self
+                    .load_image(path)
+                    .map_err(|e| format!("Failed to load material mask: {}", e))?;
+                MaterialSegmenter::from_mask(&mask, albedo.width(), albedo.height())
+            }
+            None => MaterialSegmenter::cluster(albedo, transfer, materials.count),
+        }
+    }
+
+    /// Add the material colours (as stored) and coverage to a result dictionary.
+    fn insert_materials(result: &mut Dictionary, segmentation: &Segmentation) {
+        let colors: PackedColorArray = segmentation
+            .materials
+            .iter()
+            .map(|material| Color::from_rgb(material.color[0], material.color[1], material.color[2]))
+            .collect();
+        let coverage: PackedFloat32Array = segmentation.materials.iter().map(|material| material.coverage).collect();
+        let _ = result.insert("material_colors", colors);
+        let _ = result.insert("material_coverage", coverage);
+    }
+
+    /// Read normal blend settings from option keys starting with `prefix`.
+    fn normal_blend_options(options: &Dictionary, prefix: &str) -> Result<NormalBlendOptions, String> {
+        let get = |key: &str| {
+            options
+                .get(format!("{}{}", prefix, key).as_str())
+                .map(|value| value.stringify().to_string())
+        };
+        let mut blend = NormalBlendOptions {
+            mode: NormalBlendMode::from_name(&get("mode").unwrap_or_default())?,
+            ..Default::default()
+        };
+        for (key, value) in [("strength", &mut blend.strength), ("detail_tiling", &mut blend.detail_tiling)] {
+            if let Some(text) = get(key) {
+                *value = text
+                    .trim()
+                    .parse::<f32>()
+                    .map_err(|_| format!("Invalid {}{} '{}'", prefix, key, text))?
+                    .max(0.0);
+            }
+        }
+        if blend.detail_tiling <= 0.0 {
+            return Err(format!("{}detail_tiling must be above 0", prefix));
+        }
+        Ok(blend)
+    }
+
+    /// Parse the `generate_maps_with_config` dictionary.
+    fn generation_settings(config: &Dictionary) -> Result<GenerationSettings, String> {
+        let get = |key: &str| config.get(key).map(|value| value.stringify().to_string());
+        let get_angle = |key: &str| -> Result<Option<f32>, String> {
+            get(key)
+                .map(|value| value.trim().parse::<f32>().map_err(|_| format!("Invalid {} '{}'", key, value)))
+                .transpose()
+        };
+
+        let mut output = OutputConfig::default();
+        if let Some(template) = get("template").filter(|t| !t.trim().is_empty()) {
+            output.template = template;
+        }
+        if let Some(format) = get("format") {
+            output.default_format = MapFormat::from_name(&format)?;
+        }
+        for map in ["height", "normal", "roughness", "albedo"] {
+            if let Some(format) = get(&format!("{}_format", map)) {
+                if let Some(format) = MapFormat::from_name(&format)? {
+                    output.map_formats.push((map.to_string(), format));
+                }
+            }
+        }
+        if let Some(existing) = get("existing") {
+            output.existing = ExistingFilePolicy::from_name(&existing)?;
+        }
+
+        let processing = ColorProcessing::from_name(&get("color_processing").unwrap_or_default())?;
+        if processing == ColorProcessing::Legacy {
+            output.color_space = ColorSpace::Unspecified;
+        }
+        let source_transfer = TransferFunction::from_name(&get("source_color_space").unwrap_or_default())?;
+
+        let delight_enabled = get("delight").is_some_and(|value| value.eq_ignore_ascii_case("true"));
+        let delight = Self::delight_options(config, "delight_")?;
+
+        let height_mode = HeightMode::from_name(&get("height_mode").unwrap_or_default())?;
+        let azimuth = get_angle("light_azimuth")?;
+        let elevation = get_angle("light_elevation")?;
+        let light = (azimuth.is_some() || elevation.is_some()).then(|| LightDirection {
+            azimuth: azimuth.unwrap_or(LightDirection::default().azimuth),
+            elevation: elevation.unwrap_or(LightDirection::default().elevation).clamp(1.0, 90.0),
+        });
+
+        Ok(GenerationSettings {
+            output,
+            processing,
+            source_transfer,
+            delight: delight_enabled.then_some(delight),
+            height_mode,
+            light,
+            wrap: get("tiling").is_none_or(|value| !value.eq_ignore_ascii_case("false")),
+            normal_detail: Self::normal_detail(config)?,
+            materials: Self::material_settings(config)?,
+            normal_blend: match get("blend_normal").filter(|path| !path.trim().is_empty()) {
+                Some(base_path) => Some(NormalBlendLayer {
+                    base_path,
+                    mask_path: get("blend_mask").filter(|path| !path.trim().is_empty()),
+                    options: Self::normal_blend_options(config, "blend_")?,
+                }),
+                None => None,
+            },
+        })
+    }
+
+    /// Parse the multi-scale normal options (`None` for single-scale normals).
+    fn normal_detail(config: &Dictionary) -> Result<Option<NormalDetail>, String> {
+        let get = |key: &str| config.get(key).map(|value| value.stringify().to_string());
+        match get("normal_mode").unwrap_or_default().trim().to_ascii_lowercase().as_str() {
+            "" | "single" => return Ok(None),
+            "multiscale" | "multi_scale" => {}
+            other => {
+                return Err(format!(
+                    "Unknown normal_mode '{}' (expected single or multiscale)",
+                    other
+                ))
+            }
+        }
+
+        let mut detail = NormalDetail::default();
+        for (name, level) in NormalDetail::LEVEL_NAMES.iter().zip(detail.levels.iter_mut()) {
+            for (key, value) in [
+                (format!("normal_{}", name), &mut level.weight),
+                (format!("normal_{}_strength", name), &mut level.strength),
+            ] {
+                if let Some(text) = get(&key) {
+                    *value = text
+                        .trim()
+                        .parse::<f32>()
+                        .map_err(|_| format!("Invalid {} '{}'", key, text))?
+                        .max(0.0);
+                }
+            }
+        }
+        if detail.levels.iter().all(|level| level.weight <= 0.0) {
+            return Err("Multi-scale normals need at least one level with a weight above 0".to_string());
+        }
+        Ok(Some(detail))
+    }
+
+    /// Names of the inputs the "match" resize policy scales to the first input's size.
+    fn resized_inputs<S: AsRef<str>>(inputs: &[&DynamicImage], names: &[S]) -> PackedStringArray {
+        let resized: PackedStringArray = ChannelPacker::mismatched_inputs(inputs)
+            .into_iter()
+            .map(|index| GString::from(names[index].as_ref()))
+            .collect();
+        if !resized.is_empty() {
+            godot_print!("  ↔️ Resized to the first input's size: {}", resized);
+        }
+        resized
+    }
+
+    /// Turn a Godot resource path into a filesystem path (other paths are unchanged).
+    fn resolve_path(path: &str) -> Result<PathBuf, String> {
+        match path.strip_prefix("res://") {
+            Some(relative) => {
+                let project_path = std::env::current_dir()
+                    .map_err(|e| format!("Failed to get current directory: {}", e))?;
+                Ok(project_path.join(relative))
+            }
+            None => Ok(PathBuf::from(path)),
+        }
+    }
+
+    /// Load an image from a Godot resource path or filesystem path.
+    /// EXR files are loaded at 32-bit float precision.
+    fn load_image(&self, path: &str) -> Result<DynamicImage, String> {
+        let absolute_path = Self::resolve_path(path)?;
+
+        let is_dds = absolute_path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("dds"));
+        if is_dds {
+            return DdsReader::load(&absolute_path).map_err(|e| {
+                format!("Failed to open DDS at '{}': {}", absolute_path.display(), e)
+            });
+        }
+
+        let is_exr = absolute_path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("exr"));
+        if is_exr {
+            return OpenExr::load(&absolute_path).map_err(|e| {
+                format!("Failed to open EXR at '{}': {}", absolute_path.display(), e)
+            });
+        }
 
         image::open(&absolute_path).map_err(|e| {
             format!(